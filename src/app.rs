@@ -55,6 +55,19 @@ impl winit::application::ApplicationHandler for AppState {
             } => {
                 event_loop.exit();
             }
+            winit::event::WindowEvent::KeyboardInput {
+                event:
+                    winit::event::KeyEvent {
+                        state: winit::event::ElementState::Pressed,
+                        physical_key:
+                            winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::KeyT),
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                app.scene.cycle_tonemapper();
+            }
             winit::event::WindowEvent::Resized(physical_size) => app.resize(physical_size),
             winit::event::WindowEvent::RedrawRequested => {
                 app.scene.update();
@@ -79,6 +92,33 @@ impl App {
         event_loop.run_app(&mut app_state).unwrap();
     }
 
+    /// Renders `frame_count` frames into an offscreen texture without opening a window, saving
+    /// each of them as a PNG in `output_directory`. Time advances by a fixed step per frame, so
+    /// the output is reproducible.
+    pub fn run_headless(
+        width: u32,
+        height: u32,
+        frame_count: u32,
+        output_directory: &std::path::Path,
+    ) {
+        let gpu = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(graphics::Gpu::new_headless(width, height, false))
+            .unwrap_or_else(|error| panic!("{error}"));
+
+        let mut scene =
+            Scene::setup_deterministic(gpu, None, std::time::Duration::from_secs_f32(1.0 / 60.0));
+
+        std::fs::create_dir_all(output_directory).unwrap();
+
+        for frame_index in 0..frame_count {
+            let capture = scene.capture_frame().unwrap();
+            capture
+                .save_png(output_directory.join(format!("frame_{frame_index:04}.png")))
+                .unwrap();
+        }
+    }
+
     fn new(event_loop: &winit::event_loop::ActiveEventLoop) -> Self {
         let window = Arc::new(
             event_loop
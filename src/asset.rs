@@ -1,3 +1,5 @@
+mod texture_compression;
+
 use crate::graphics;
 use std::collections::{HashMap, VecDeque};
 use std::hash::Hash;
@@ -8,16 +10,61 @@ pub type MeshId = u32;
 pub type MaterialId = u32;
 pub type ModelId = usize;
 
+/// The number of texture coordinates sets per vertex.
+pub const TEX_COORDS_SET_COUNT: u32 = 2;
+
+/// Sizes the longer side of decoded images is resized to, so that they share texture arrays.
+/// Images are scaled up to the next size, keeping their aspect ratio, and larger images are
+/// rejected.
+pub const TEXTURE_SIZE_CLASSES: [u32; 6] = [256, 512, 1024, 2048, 4096, 8192];
+
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub struct TextureReference {
     pub texture_array_id: u32,
     pub texture_id: u32,
+    pub texture_index: u32, // Among all textures, as bound in binding arrays.
+    pub tex_coords_set: u32,
+    pub transform: TextureTransform,
+}
+
+/// Transform of the texture coordinates before sampling, as in `KHR_texture_transform`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TextureTransform {
+    pub offset: [HashableF32; 2],
+    pub rotation: HashableF32, // In radians, counter-clockwise.
+    pub scale: [HashableF32; 2],
+}
+
+impl TextureTransform {
+    pub fn new(offset: [f32; 2], rotation: f32, scale: [f32; 2]) -> Self {
+        Self {
+            offset: offset.map(HashableF32::from),
+            rotation: rotation.into(),
+            scale: scale.map(HashableF32::from),
+        }
+    }
+
+    /// Scales, rotates, then offsets the texture coordinates.
+    pub fn matrix(&self) -> glam::Affine2 {
+        // The texture coordinates go down the image, which flips the rotation.
+        glam::Affine2::from_scale_angle_translation(
+            glam::Vec2::from(self.scale.map(|scale| *scale)),
+            -*self.rotation,
+            glam::Vec2::from(self.offset.map(|offset| *offset)),
+        )
+    }
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        Self::new([0.0; 2], 0.0, [1.0; 2])
+    }
 }
 
 pub struct AssetLoader {
     pub mesh_map: MeshMap,
     pub texture_arrays: TextureArrays,
-    pub texture_dictionary: HashMap<String, (TextureArray, TextureId)>,
+    pub texture_dictionary: HashMap<(String, TextureUsage), (TextureArray, TextureId)>,
     pub material_map: MaterialMap,
     pub model_map: ModelMap,
 }
@@ -59,19 +106,43 @@ impl AssetLoader {
             path: path.to_string_lossy().to_string(),
         })?;
 
-        let gltf::Gltf { document, .. } = gltf::Gltf::open(path).unwrap();
-        let buffers = gltf::import_buffers(&document, Some(directory_path), None).unwrap();
+        // The blob holds the binary chunk of GLB files.
+        let gltf::Gltf { document, blob } =
+            gltf::Gltf::open(path).map_err(|error| AssetError::InvalidGltf {
+                path: canonicalized_path.clone(),
+                reason: error.to_string(),
+            })?;
+        let buffers =
+            gltf::import_buffers(&document, Some(directory_path), blob).map_err(|error| {
+                AssetError::UnreadableGltfBuffers {
+                    path: canonicalized_path.clone(),
+                    reason: error.to_string(),
+                }
+            })?;
+        let gltf_images = GltfImages {
+            model_path: &canonicalized_path,
+            directory_path,
+            buffers: &buffers,
+        };
         let mut nodes = Vec::new();
         let mut stack = VecDeque::new();
 
-        let default_scene = document.default_scene().unwrap();
+        let default_scene =
+            document
+                .default_scene()
+                .ok_or_else(|| AssetError::GltfWithoutDefaultScene {
+                    path: canonicalized_path.clone(),
+                })?;
         for gltf_node in default_scene.nodes() {
             stack.push_back(gltf_node);
         }
 
         while let Some(gltf_node) = stack.pop_front() {
+            let (translation, rotation, scale) = gltf_node.transform().decomposed();
             let mut node = Node {
-                name: gltf_node.name().map(|s| s.to_string()),
+                translation: glam::Vec3::from(translation),
+                rotation: glam::Quat::from_array(rotation),
+                scale: glam::Vec3::from(scale),
                 object_group: None,
                 children: Vec::new(),
             };
@@ -82,131 +153,234 @@ impl AssetLoader {
                 };
 
                 for (i, primitive) in gltf_mesh.primitives().enumerate() {
-                    let name = format!("{}/{}", gltf_mesh.name().unwrap(), i);
+                    // Meshes of binary exports are often unnamed.
+                    let name = match gltf_mesh.name() {
+                        Some(mesh_name) => format!("{mesh_name}/{i}"),
+                        None => format!("mesh{}/{i}", gltf_mesh.index()),
+                    };
                     let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
                     let gltf_material = primitive.material();
                     let pbr_metallic_roughness = gltf_material.pbr_metallic_roughness();
-                    let base_color_texture_information = pbr_metallic_roughness
-                        .base_color_texture()
-                        .ok_or(AssetError::PrimitiveWithoutBaseColor { name: name.clone() })?;
-
-                    let base_color_texture_reference =
-                        if let gltf::image::Source::Uri { uri, .. } =
-                            base_color_texture_information.texture().source().source()
-                        {
-                            let texture_path =
-                                directory_path.join(uri).canonicalize().map_err(|path| {
-                                    AssetError::InvalidPath {
-                                        path: path.to_string(),
-                                    }
-                                })?;
-
-                            let (texture_array, texture_id) = self.load_texture(&texture_path)?;
-
-                            TextureReference {
-                                texture_array_id: texture_array.id(),
-                                texture_id,
-                            }
-                        } else {
-                            return Err(AssetError::NonUriImageSource { name: name.clone() });
-                        };
 
-                    let tex_coords_set_index = base_color_texture_information.tex_coord();
-                    let tex_coords = if let gltf::mesh::util::ReadTexCoords::F32(tex_coords_iter) =
-                        reader.read_tex_coords(tex_coords_set_index).ok_or(
-                            AssetError::PrimitiveWithoutTextureCoordinates { name: name.clone() },
-                        )? {
-                        tex_coords_iter
+                    let base_color_factor = pbr_metallic_roughness.base_color_factor();
+                    let base_color = if let Some(texture_information) =
+                        pbr_metallic_roughness.base_color_texture()
+                    {
+                        let (tex_coords_set, transform) =
+                            gltf_texture_info_tex_coords(&texture_information);
+                        BaseColor::Texture {
+                            texture_reference: self.load_gltf_texture(
+                                &gltf_images,
+                                texture_information.texture(),
+                                TextureUsage::Color,
+                                tex_coords_set,
+                                transform,
+                            )?,
+                            factor: base_color_factor.map(HashableF32::from),
+                        }
                     } else {
-                        return Err(AssetError::NonF32TextureCoordinates { name: name.clone() });
+                        let [r, g, b, a] = base_color_factor.map(HashableF32::from);
+                        BaseColor::Solid { r, g, b, a }
                     };
 
-                    let normal_texture_reference = if let gltf::image::Source::Uri { uri, .. } =
-                        gltf_material
-                            .normal_texture()
-                            .unwrap()
-                            .texture()
-                            .source()
-                            .source()
-                    {
-                        let texture_path =
-                            directory_path.join(uri).canonicalize().map_err(|path| {
-                                AssetError::InvalidPath {
-                                    path: path.to_string(),
-                                }
-                            })?;
+                    let normal = if let Some(normal_texture) = gltf_material.normal_texture() {
+                        let (tex_coords_set, transform) = gltf_texture_extension_tex_coords(
+                            normal_texture.tex_coord(),
+                            normal_texture.extension_value("KHR_texture_transform"),
+                        );
+                        Some(Normal {
+                            texture_reference: self.load_gltf_texture(
+                                &gltf_images,
+                                normal_texture.texture(),
+                                TextureUsage::Normal,
+                                tex_coords_set,
+                                transform,
+                            )?,
+                            scale: normal_texture.scale().into(),
+                        })
+                    } else {
+                        None
+                    };
 
-                        let (texture_array, texture_id) = self.load_texture(&texture_path)?;
+                    let roughness_factor = pbr_metallic_roughness.roughness_factor().into();
+                    let metallic_factor = pbr_metallic_roughness.metallic_factor().into();
+                    let mut metallic_roughness_image = None;
+                    let (roughness, metallic) = if let Some(texture_information) =
+                        pbr_metallic_roughness.metallic_roughness_texture()
+                    {
+                        let (tex_coords_set, transform) =
+                            gltf_texture_info_tex_coords(&texture_information);
+                        let texture_reference = self.load_gltf_texture(
+                            &gltf_images,
+                            texture_information.texture(),
+                            TextureUsage::Linear,
+                            tex_coords_set,
+                            transform,
+                        )?;
+                        metallic_roughness_image = Some((
+                            texture_information.texture().source().index(),
+                            texture_reference,
+                        ));
+                        (
+                            Roughness::Texture {
+                                texture_reference,
+                                channel: 1,
+                                factor: roughness_factor,
+                            },
+                            Metallic::Texture {
+                                texture_reference,
+                                channel: 2,
+                                factor: metallic_factor,
+                            },
+                        )
+                    } else {
+                        (
+                            Roughness::Solid(roughness_factor),
+                            Metallic::Solid(metallic_factor),
+                        )
+                    };
 
-                        TextureReference {
-                            texture_array_id: texture_array.id(),
-                            texture_id,
+                    // Occlusion is always in the red channel, which is unused by metallic and
+                    // roughness, so an ORM packed image is shared between the three.
+                    let occlusion = if let Some(occlusion_texture) =
+                        gltf_material.occlusion_texture()
+                    {
+                        let (tex_coords_set, transform) = gltf_texture_extension_tex_coords(
+                            occlusion_texture.tex_coord(),
+                            occlusion_texture.extension_value("KHR_texture_transform"),
+                        );
+                        let texture_reference = match metallic_roughness_image {
+                            Some((image_index, texture_reference))
+                                if image_index == occlusion_texture.texture().source().index() =>
+                            {
+                                TextureReference {
+                                    tex_coords_set,
+                                    transform,
+                                    ..texture_reference
+                                }
+                            }
+                            _ => self.load_gltf_texture(
+                                &gltf_images,
+                                occlusion_texture.texture(),
+                                TextureUsage::Linear,
+                                tex_coords_set,
+                                transform,
+                            )?,
+                        };
+                        Occlusion::Texture {
+                            texture_reference,
+                            channel: 0,
+                            strength: occlusion_texture.strength().into(),
                         }
                     } else {
-                        return Err(AssetError::NonUriImageSource { name: name.clone() });
+                        Occlusion::Solid(1.0.into())
                     };
 
-                    let metallic_roughness_texture_information =
-                        pbr_metallic_roughness.metallic_roughness_texture().unwrap();
-
-                    let metallic_roughness_texture_reference =
-                        if let gltf::image::Source::Uri { uri, .. } =
-                            metallic_roughness_texture_information
-                                .texture()
-                                .source()
-                                .source()
-                        {
-                            let texture_path =
-                                directory_path.join(uri).canonicalize().map_err(|path| {
-                                    AssetError::InvalidPath {
-                                        path: path.to_string(),
-                                    }
-                                })?;
-
-                            let (texture_array, texture_id) = self.load_texture(&texture_path)?;
-
-                            TextureReference {
-                                texture_array_id: texture_array.id(),
-                                texture_id,
+                    // The strength scales the factor, which is otherwise limited to one.
+                    let emissive_factor = gltf_material.emissive_factor().map(|factor| {
+                        HashableF32::from(factor * gltf_material.emissive_strength().unwrap_or(1.0))
+                    });
+                    let emissive =
+                        if let Some(texture_information) = gltf_material.emissive_texture() {
+                            let (tex_coords_set, transform) =
+                                gltf_texture_info_tex_coords(&texture_information);
+                            Emissive::Texture {
+                                texture_reference: self.load_gltf_texture(
+                                    &gltf_images,
+                                    texture_information.texture(),
+                                    TextureUsage::Color,
+                                    tex_coords_set,
+                                    transform,
+                                )?,
+                                factor: emissive_factor,
                             }
                         } else {
-                            return Err(AssetError::NonUriImageSource { name: name.clone() });
+                            let [r, g, b] = emissive_factor;
+                            Emissive::Solid { r, g, b }
                         };
 
                     let material = Material {
-                        base_color: BaseColor::Texture(base_color_texture_reference),
-                        normal: Some(normal_texture_reference),
-                        occlusion: Occlusion::Texture {
-                            texture_reference: metallic_roughness_texture_reference,
-                            channel: 0,
-                        },
-                        roughness: Roughness::Texture {
-                            texture_reference: metallic_roughness_texture_reference,
-                            channel: 1,
-                        },
-                        metallic: Metallic::Texture {
-                            texture_reference: metallic_roughness_texture_reference,
-                            channel: 2,
+                        base_color,
+                        normal,
+                        occlusion,
+                        roughness,
+                        metallic,
+                        emissive,
+                        alpha_mode: match gltf_material.alpha_mode() {
+                            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                            gltf::material::AlphaMode::Mask => AlphaMode::Mask {
+                                cutoff: gltf_material.alpha_cutoff().unwrap_or(0.5).into(),
+                            },
+                            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
                         },
+                        double_sided: gltf_material.double_sided(),
                     };
 
-                    let material_index = self.material_map.add(material);
-
                     let vertex_positions = reader.read_positions().ok_or(
                         AssetError::PrimitiveWithoutVertexPositions { name: name.clone() },
                     )?;
+                    let vertex_count = vertex_positions.len();
+
+                    if let Some(texture_reference) =
+                        material.texture_references().find(|texture_reference| {
+                            texture_reference.tex_coords_set >= TEX_COORDS_SET_COUNT
+                        })
+                    {
+                        return Err(AssetError::UnsupportedTextureCoordinatesSet {
+                            name: name.clone(),
+                            tex_coords_set: texture_reference.tex_coords_set,
+                        });
+                    }
+
+                    // Missing sets are zeroed, unless a texture of the material samples them.
+                    let read_tex_coords =
+                        |tex_coords_set| match reader.read_tex_coords(tex_coords_set) {
+                            Some(tex_coords_iter) => Ok(tex_coords_iter.into_f32().collect()),
+                            None if material.texture_references().all(|texture_reference| {
+                                texture_reference.tex_coords_set != tex_coords_set
+                            }) =>
+                            {
+                                Ok(vec![[0.0; 2]; vertex_count])
+                            }
+                            None => Err(AssetError::PrimitiveWithoutTextureCoordinates {
+                                name: name.clone(),
+                            }),
+                        };
+                    let tex_coords_0: Vec<[f32; 2]> = read_tex_coords(0)?;
+                    let tex_coords_1: Vec<[f32; 2]> = read_tex_coords(1)?;
+
+                    let material_index = self.material_map.add(material);
+
                     let vertex_normals = reader
                         .read_normals()
                         .ok_or(AssetError::PrimitiveWithoutVertexNormals { name: name.clone() })?;
-                    let vertex_tangents = reader
-                        .read_tangents()
-                        .ok_or(AssetError::PrimitiveWithoutVertexTangents { name: name.clone() })?;
 
-                    let mut vertices = Vec::with_capacity(vertex_positions.len());
-                    for (position, tex_coord, normal, tangent) in itertools::izip!(
+                    // Tangents are only needed to apply a normal texture, otherwise any vector
+                    // orthogonal to the normal does.
+                    let vertex_tangents: Box<dyn Iterator<Item = [f32; 4]>> =
+                        match reader.read_tangents() {
+                            Some(vertex_tangents) => Box::new(vertex_tangents),
+                            None if normal.is_none() => Box::new(
+                                reader.read_normals().into_iter().flatten().map(|normal| {
+                                    glam::Vec3::from(normal)
+                                        .any_orthonormal_vector()
+                                        .extend(1.0)
+                                        .into()
+                                }),
+                            ),
+                            None => {
+                                return Err(AssetError::PrimitiveWithoutVertexTangents {
+                                    name: name.clone(),
+                                });
+                            }
+                        };
+
+                    let mut vertices = Vec::with_capacity(vertex_count);
+                    for (position, tex_coords_0, tex_coords_1, normal, tangent) in itertools::izip!(
                         vertex_positions,
-                        tex_coords,
+                        tex_coords_0,
+                        tex_coords_1,
                         vertex_normals,
                         vertex_tangents
                     ) {
@@ -216,7 +390,8 @@ impl AssetLoader {
 
                         vertices.push(graphics::Vertex::new(
                             position,
-                            tex_coord,
+                            tex_coords_0,
+                            tex_coords_1,
                             normal,
                             [tangent[0], tangent[1], tangent[2]],
                             bitangent.into(),
@@ -250,8 +425,10 @@ impl AssetLoader {
             }
 
             for child in gltf_node.children() {
+                // Nodes are pushed in breadth-first order, so the child ends up after the current
+                // node and every node already queued.
+                node.children.push(nodes.len() + 1 + stack.len());
                 stack.push_back(child);
-                node.children.push(nodes.len());
             }
 
             nodes.push(node);
@@ -269,29 +446,66 @@ impl AssetLoader {
         Ok(model_index)
     }
 
+    /// Loads the image of a glTF texture, and references it with the texture coordinates it is
+    /// sampled with.
+    fn load_gltf_texture(
+        &mut self,
+        gltf_images: &GltfImages,
+        texture: gltf::Texture,
+        usage: TextureUsage,
+        tex_coords_set: u32,
+        transform: TextureTransform,
+    ) -> Result<TextureReference, AssetError> {
+        let image = texture.source();
+        let key = gltf_images.key(&image)?;
+        let (texture_array, texture_id) =
+            self.load_texture_data(&key, usage, || gltf_images.data(&image, &key))?;
+
+        Ok(TextureReference {
+            texture_array_id: texture_array.id(),
+            texture_id,
+            texture_index: self.texture_arrays.texture_index(texture_array, texture_id),
+            tex_coords_set,
+            transform,
+        })
+    }
+
     pub fn load_texture<P>(
         &mut self,
         texture_path: P,
+        usage: TextureUsage,
     ) -> Result<(TextureArray, TextureId), AssetError>
     where
         P: AsRef<std::path::Path>,
     {
         let texture_path_str = texture_path.as_ref().to_str().unwrap();
 
-        let (texture_array, texture_id) = if let Some((texture_array, texture_id)) =
-            self.texture_dictionary.get(texture_path_str)
-        {
-            (*texture_array, *texture_id)
-        } else {
-            let texture_data = std::fs::read(texture_path_str).unwrap();
-            let texture_reader = ktx2::Reader::new(&texture_data).unwrap();
-            let (texture_array, texture_id) = self
-                .texture_arrays
-                .add(texture_path_str.to_string(), texture_reader)?;
-            self.texture_dictionary
-                .insert(texture_path_str.to_string(), (texture_array, texture_id));
-            (texture_array, texture_id)
-        };
+        self.load_texture_data(texture_path_str, usage, || {
+            std::fs::read(texture_path_str).map_err(|_| AssetError::InvalidPath {
+                path: texture_path_str.to_string(),
+            })
+        })
+    }
+
+    /// Loads a texture once per key and usage, only reading its data when it is not loaded yet.
+    fn load_texture_data<F>(
+        &mut self,
+        key: &str,
+        usage: TextureUsage,
+        read_texture_data: F,
+    ) -> Result<(TextureArray, TextureId), AssetError>
+    where
+        F: FnOnce() -> Result<Vec<u8>, AssetError>,
+    {
+        let dictionary_key = (key.to_string(), usage);
+        if let Some(&(texture_array, texture_id)) = self.texture_dictionary.get(&dictionary_key) {
+            return Ok((texture_array, texture_id));
+        }
+
+        let texture_data = TextureData::decode(key, &read_texture_data()?, usage)?;
+        let (texture_array, texture_id) = self.texture_arrays.add(key.to_string(), texture_data)?;
+        self.texture_dictionary
+            .insert(dictionary_key, (texture_array, texture_id));
 
         Ok((texture_array, texture_id))
     }
@@ -308,46 +522,51 @@ impl AssetLoader {
     where
         P: AsRef<std::path::Path>,
     {
-        let (texture_array_positive_x, positive_x) = self.load_texture(&path_positive_x)?;
+        let (texture_array_positive_x, positive_x) =
+            self.load_texture(&path_positive_x, TextureUsage::Color)?;
         let texture_array = texture_array_positive_x;
 
-        const VALID_CUBEMAP_TEXTURE_ARRAYS: [TextureArray; 1] =
-            [TextureArray::NoMipRgbBc6hSFloat1024];
-        if !VALID_CUBEMAP_TEXTURE_ARRAYS.contains(&texture_array) {
+        let class = texture_array.class();
+        if class.format != wgpu::TextureFormat::Bc6hRgbFloat || class.width != class.height {
             return Err(AssetError::InvalidCubemapTexture {
                 name: path_positive_x.as_ref().to_str().unwrap().to_string(),
             });
         }
 
-        let (texture_array_negative_x, negative_x) = self.load_texture(&path_negative_x)?;
+        let (texture_array_negative_x, negative_x) =
+            self.load_texture(&path_negative_x, TextureUsage::Color)?;
         if texture_array_negative_x != texture_array {
             return Err(AssetError::NonMatchingCubemapTexture {
                 name: path_negative_x.as_ref().to_str().unwrap().to_string(),
             });
         }
 
-        let (texture_array_positive_y, positive_y) = self.load_texture(&path_positive_y)?;
+        let (texture_array_positive_y, positive_y) =
+            self.load_texture(&path_positive_y, TextureUsage::Color)?;
         if texture_array_positive_y != texture_array {
             return Err(AssetError::NonMatchingCubemapTexture {
                 name: path_positive_y.as_ref().to_str().unwrap().to_string(),
             });
         }
 
-        let (texture_array_negative_y, negative_y) = self.load_texture(&path_negative_y)?;
+        let (texture_array_negative_y, negative_y) =
+            self.load_texture(&path_negative_y, TextureUsage::Color)?;
         if texture_array_negative_y != texture_array {
             return Err(AssetError::NonMatchingCubemapTexture {
                 name: path_negative_y.as_ref().to_str().unwrap().to_string(),
             });
         }
 
-        let (texture_array_positive_z, positive_z) = self.load_texture(&path_positive_z)?;
+        let (texture_array_positive_z, positive_z) =
+            self.load_texture(&path_positive_z, TextureUsage::Color)?;
         if texture_array_positive_z != texture_array {
             return Err(AssetError::NonMatchingCubemapTexture {
                 name: path_positive_z.as_ref().to_str().unwrap().to_string(),
             });
         }
 
-        let (texture_array_negative_z, negative_z) = self.load_texture(&path_negative_z)?;
+        let (texture_array_negative_z, negative_z) =
+            self.load_texture(&path_negative_z, TextureUsage::Color)?;
         if texture_array_negative_z != texture_array {
             return Err(AssetError::NonMatchingCubemapTexture {
                 name: path_negative_z.as_ref().to_str().unwrap().to_string(),
@@ -364,6 +583,18 @@ impl AssetLoader {
             negative_z,
         })
     }
+
+    /// Loads a 3D lookup table from an Adobe `.cube` file.
+    pub fn load_color_grading_lut<P>(&self, path: P) -> Result<ColorGradingLut, AssetError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let name = path.as_ref().to_str().unwrap().to_string();
+        let text = std::fs::read_to_string(&path)
+            .map_err(|_| AssetError::InvalidPath { path: name.clone() })?;
+
+        parse_cube_lut(&name, &text)
+    }
 }
 
 #[derive(Debug)]
@@ -372,9 +603,11 @@ pub struct Model {
     pub nodes: Vec<Node>,
 }
 
-#[derive(Default, Debug)]
+#[derive(Debug)]
 pub struct Node {
-    pub name: Option<String>,
+    pub translation: glam::Vec3,
+    pub rotation: glam::Quat,
+    pub scale: glam::Vec3,
     pub object_group: Option<ObjectGroup>,
     pub children: Vec<usize>,
 }
@@ -413,12 +646,6 @@ impl ModelMap {
     pub fn index(&self, i: ModelId) -> Option<&Model> {
         self.models.get(i)
     }
-
-    pub fn get(&self, name: &str) -> Option<&Model> {
-        self.map
-            .get(name)
-            .map(|&model_index| &self.models[model_index])
-    }
 }
 
 #[derive(Default, Debug)]
@@ -467,34 +694,30 @@ impl MeshMap {
 pub struct TextureMap {
     pub map: HashMap<String, u32>,
     pub mip_levels: Vec<(usize, usize)>, // (data_offset, data_length)
-    pub dimension: u32,
-    pub mip_level_count: u32,
+    pub class: TextureClass,
     pub data: Vec<u8>,
-    pub format: wgpu::TextureFormat,
 }
 
 impl TextureMap {
-    pub fn new(dimension: u32, format: wgpu::TextureFormat, mip_level_count: u32) -> Self {
+    pub fn new(class: TextureClass) -> Self {
         Self {
             map: HashMap::new(),
             mip_levels: vec![],
-            dimension,
-            mip_level_count,
+            class,
             data: vec![],
-            format,
         }
     }
 
     pub fn count(&self) -> usize {
-        self.mip_levels.len() / self.mip_level_count as usize
+        self.mip_levels.len() / self.class.mip_level_count as usize
     }
 
-    pub fn add(&mut self, name: String, texture: ktx2::Reader<&Vec<u8>>) -> TextureId {
+    pub fn add(&mut self, name: String, texture: &TextureData) -> TextureId {
         if let Some(&texture_index) = self.map.get(&name) {
             return texture_index;
         }
 
-        for mip_level in texture.levels() {
+        for mip_level in &texture.levels {
             let offset = self.data.len();
             self.data.extend(mip_level);
             self.mip_levels.push((offset, mip_level.len()));
@@ -507,14 +730,14 @@ impl TextureMap {
     }
 
     pub fn get(&self, layer_index: u32, mip_level_index: u32) -> Result<&[u8], AssetError> {
-        if mip_level_index >= self.mip_level_count {
+        if mip_level_index >= self.class.mip_level_count {
             return Err(AssetError::InvalidMipLevel {
                 mip_level: mip_level_index,
             });
         }
 
         let texture_information_index =
-            (layer_index * self.mip_level_count + mip_level_index) as usize;
+            (layer_index * self.class.mip_level_count + mip_level_index) as usize;
         if texture_information_index >= self.mip_levels.len() {
             return Err(AssetError::TextureLayerOutOfBounds { layer_index });
         }
@@ -524,231 +747,284 @@ impl TextureMap {
     }
 }
 
+/// What a texture holds, which decides how decoded images are compressed.
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
-pub enum TextureArray {
-    RgBc5Unorm512,
-    RgBc5Unorm1024,
-    RgBc5Unorm2048,
-    RgBc5Unorm4096,
-    RgbBc7Unorm512,
-    RgbBc7Unorm1024,
-    RgbBc7Unorm2048,
-    RgbBc7Unorm4096,
-    RgbaBc7Srgb512,
-    RgbaBc7Srgb1024,
-    RgbaBc7Srgb2048,
-    RgbaBc7Srgb4096,
-    NoMipRgbBc6hSFloat1024,
+pub enum TextureUsage {
+    // sRGB colors, compressed to BC7.
+    Color,
+    // Tangent space normals, of which X and Y are compressed to BC5.
+    Normal,
+    // Linear data such as occlusion, roughness and metalness, compressed to BC7.
+    Linear,
 }
 
-impl TextureArray {
-    pub const fn id(&self) -> TextureArrayId {
-        match self {
-            Self::RgBc5Unorm512 => 0,
-            Self::RgBc5Unorm1024 => 1,
-            Self::RgBc5Unorm2048 => 2,
-            Self::RgBc5Unorm4096 => 3,
-            Self::RgbBc7Unorm512 => 4,
-            Self::RgbBc7Unorm1024 => 5,
-            Self::RgbBc7Unorm2048 => 6,
-            Self::RgbBc7Unorm4096 => 7,
-            Self::RgbaBc7Srgb512 => 8,
-            Self::RgbaBc7Srgb1024 => 9,
-            Self::RgbaBc7Srgb2048 => 10,
-            Self::RgbaBc7Srgb4096 => 11,
-            Self::NoMipRgbBc6hSFloat1024 => 12,
+/// The mip levels of a texture, from the largest, in the layout of a KTX2 file.
+pub struct TextureData {
+    pub format: Option<ktx2::Format>,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl TextureData {
+    /// Decodes a KTX2 file, or a PNG or JPEG image. Images and uncompressed KTX2 files are
+    /// mip-mapped and compressed according to their usage, and Zstandard supercompressed KTX2
+    /// levels are decompressed.
+    pub fn decode(name: &str, data: &[u8], usage: TextureUsage) -> Result<Self, AssetError> {
+        let invalid_texture_data = || AssetError::InvalidTextureData {
+            name: name.to_string(),
+        };
+
+        let (width, height, rgba) = match ktx2::Reader::new(data) {
+            Ok(texture) => {
+                let header = texture.header();
+                if is_basis_universal(&texture) {
+                    return Err(AssetError::UnsupportedBasisUniversalTexture {
+                        name: name.to_string(),
+                    });
+                }
+
+                let mut levels = ktx2_levels(name, &texture)?;
+                match header.format {
+                    Some(ktx2::Format::R8G8B8A8_UNORM | ktx2::Format::R8G8B8A8_SRGB) => {
+                        if levels.is_empty() {
+                            return Err(invalid_texture_data());
+                        }
+                        (
+                            header.pixel_width,
+                            header.pixel_height,
+                            levels.swap_remove(0),
+                        )
+                    }
+                    format => {
+                        return Ok(Self {
+                            format,
+                            width: header.pixel_width,
+                            height: header.pixel_height,
+                            levels,
+                        });
+                    }
+                }
+            }
+            Err(_) => {
+                let image = image::load_from_memory(data)
+                    .map_err(|_| invalid_texture_data())?
+                    .into_rgba8();
+                (image.width(), image.height(), image.into_raw())
+            }
+        };
+
+        if rgba.len() != (width * height * 4) as usize {
+            return Err(invalid_texture_data());
         }
+
+        Self::compress(name, width, height, rgba, usage)
     }
 
-    pub const fn size(&self) -> (usize, usize) {
-        match self {
-            Self::RgBc5Unorm512 | Self::RgbBc7Unorm512 | Self::RgbaBc7Srgb512 => (512, 512),
-            Self::RgBc5Unorm1024
-            | Self::RgbBc7Unorm1024
-            | Self::RgbaBc7Srgb1024
-            | Self::NoMipRgbBc6hSFloat1024 => (1024, 1024),
-            Self::RgBc5Unorm2048 | Self::RgbBc7Unorm2048 | Self::RgbaBc7Srgb2048 => (2048, 2048),
-            Self::RgBc5Unorm4096 | Self::RgbBc7Unorm4096 | Self::RgbaBc7Srgb4096 => (4096, 4096),
-        }
+    /// Resizes an RGBA8 image to its size class, then generates its mip levels and block
+    /// compresses them.
+    pub fn compress(
+        name: &str,
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+        usage: TextureUsage,
+    ) -> Result<Self, AssetError> {
+        let (format, srgb) = match usage {
+            TextureUsage::Color => (ktx2::Format::BC7_SRGB_BLOCK, true),
+            TextureUsage::Normal => (ktx2::Format::BC5_UNORM_BLOCK, false),
+            TextureUsage::Linear => (ktx2::Format::BC7_UNORM_BLOCK, false),
+        };
+
+        let (resized_width, resized_height) =
+            texture_size(width, height).ok_or_else(|| AssetError::TextureTooLarge {
+                name: name.to_string(),
+                width,
+                height,
+            })?;
+        let (width, height, rgba) = if (width, height) == (resized_width, resized_height) {
+            (width, height, rgba)
+        } else {
+            let image = image::RgbaImage::from_raw(width, height, rgba).unwrap();
+            let resized = image::imageops::resize(
+                &image,
+                resized_width,
+                resized_height,
+                image::imageops::FilterType::Triangle,
+            );
+            (resized_width, resized_height, resized.into_raw())
+        };
+
+        let levels = texture_compression::generate_mip_chain(width, height, rgba, srgb)
+            .iter()
+            .enumerate()
+            .map(|(mip_level, rgba)| {
+                let (mip_width, mip_height) =
+                    ((width >> mip_level).max(1), (height >> mip_level).max(1));
+                match usage {
+                    TextureUsage::Normal => {
+                        texture_compression::compress_bc5(mip_width, mip_height, rgba)
+                    }
+                    TextureUsage::Color | TextureUsage::Linear => {
+                        texture_compression::compress_bc7(mip_width, mip_height, rgba)
+                    }
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            format: Some(format),
+            width,
+            height,
+            levels,
+        })
     }
+}
+
+/// The format, size and mip level count shared by the textures of a texture array.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TextureClass {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub mip_level_count: u32,
+}
 
-    pub const fn mip_level_count(&self) -> u32 {
-        match self {
-            Self::RgBc5Unorm512 | Self::RgbBc7Unorm512 | Self::RgbaBc7Srgb512 => 10,
-            Self::RgBc5Unorm1024 | Self::RgbBc7Unorm1024 | Self::RgbaBc7Srgb1024 => 11,
-            Self::RgBc5Unorm2048 | Self::RgbBc7Unorm2048 | Self::RgbaBc7Srgb2048 => 12,
-            Self::RgBc5Unorm4096 | Self::RgbBc7Unorm4096 | Self::RgbaBc7Srgb4096 => 13,
-            Self::NoMipRgbBc6hSFloat1024 => 1,
+impl TextureClass {
+    /// Returns `None` for formats without texture arrays, for sizes or mip level counts the format
+    /// cannot hold, and for levels that are not exactly the blocks covering their size.
+    pub fn of(texture: &TextureData) -> Option<Self> {
+        let format = match texture.format? {
+            ktx2::Format::BC5_UNORM_BLOCK => wgpu::TextureFormat::Bc5RgUnorm,
+            ktx2::Format::BC7_UNORM_BLOCK => wgpu::TextureFormat::Bc7RgbaUnorm,
+            ktx2::Format::BC7_SRGB_BLOCK => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+            ktx2::Format::BC6H_SFLOAT_BLOCK => wgpu::TextureFormat::Bc6hRgbFloat,
+            _ => return None,
+        };
+
+        let (block_width, block_height) = format.block_dimensions();
+        let mip_level_count = texture.levels.len() as u32;
+        let max_mip_level_count = u32::BITS - texture.width.max(texture.height).leading_zeros();
+        if texture.width == 0
+            || texture.height == 0
+            || !texture.width.is_multiple_of(block_width)
+            || !texture.height.is_multiple_of(block_height)
+            || mip_level_count == 0
+            || mip_level_count > max_mip_level_count
+        {
+            return None;
         }
+
+        let block_size = format.block_copy_size(None)?;
+        let levels_match_blocks = texture.levels.iter().zip(0..).all(|(level, mip_level)| {
+            let width_blocks = (texture.width >> mip_level).max(1).div_ceil(block_width);
+            let height_blocks = (texture.height >> mip_level).max(1).div_ceil(block_height);
+            level.len() == (width_blocks * height_blocks * block_size) as usize
+        });
+        if !levels_match_blocks {
+            return None;
+        }
+
+        Some(Self {
+            format,
+            width: texture.width,
+            height: texture.height,
+            mip_level_count,
+        })
     }
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TextureArray {
+    id: TextureArrayId,
+    class: TextureClass,
+}
+
+impl TextureArray {
+    pub const fn id(&self) -> TextureArrayId {
+        self.id
+    }
+
+    pub const fn class(&self) -> TextureClass {
+        self.class
+    }
+}
+
+/// One texture map per texture class, indexed by texture array id. Texture arrays are created
+/// as textures of new classes are added.
+#[derive(Default)]
 pub struct TextureArrays {
-    pub rg_bc5_unorm_512: TextureMap,
-    pub rg_bc5_unorm_1024: TextureMap,
-    pub rg_bc5_unorm_2048: TextureMap,
-    pub rg_bc5_unorm_4096: TextureMap,
-    pub rgb_bc7_unorm_512: TextureMap,
-    pub rgb_bc7_unorm_1024: TextureMap,
-    pub rgb_bc7_unorm_2048: TextureMap,
-    pub rgb_bc7_unorm_4096: TextureMap,
-    pub rgba_bc7_srgb_512: TextureMap,
-    pub rgba_bc7_srgb_1024: TextureMap,
-    pub rgba_bc7_srgb_2048: TextureMap,
-    pub rgba_bc7_srgb_4096: TextureMap,
-    pub no_mip_rgb_bc6h_sfloat_1024: TextureMap,
+    pub texture_maps: Vec<TextureMap>,
+    pub textures: Vec<(TextureArray, TextureId)>, // In the order they were added.
 }
 
 impl TextureArrays {
     pub fn new() -> Self {
-        Self {
-            rg_bc5_unorm_512: TextureMap::new(
-                512,
-                wgpu::TextureFormat::Bc5RgUnorm,
-                TextureArray::RgBc5Unorm512.mip_level_count(),
-            ),
-            rg_bc5_unorm_1024: TextureMap::new(
-                1024,
-                wgpu::TextureFormat::Bc5RgUnorm,
-                TextureArray::RgBc5Unorm1024.mip_level_count(),
-            ),
-            rg_bc5_unorm_2048: TextureMap::new(
-                2048,
-                wgpu::TextureFormat::Bc5RgUnorm,
-                TextureArray::RgBc5Unorm2048.mip_level_count(),
-            ),
-            rg_bc5_unorm_4096: TextureMap::new(
-                4096,
-                wgpu::TextureFormat::Bc5RgUnorm,
-                TextureArray::RgBc5Unorm4096.mip_level_count(),
-            ),
-            rgb_bc7_unorm_512: TextureMap::new(
-                512,
-                wgpu::TextureFormat::Bc7RgbaUnorm,
-                TextureArray::RgbBc7Unorm512.mip_level_count(),
-            ),
-            rgb_bc7_unorm_1024: TextureMap::new(
-                1024,
-                wgpu::TextureFormat::Bc7RgbaUnorm,
-                TextureArray::RgbBc7Unorm1024.mip_level_count(),
-            ),
-            rgb_bc7_unorm_2048: TextureMap::new(
-                2048,
-                wgpu::TextureFormat::Bc7RgbaUnorm,
-                TextureArray::RgbBc7Unorm2048.mip_level_count(),
-            ),
-            rgb_bc7_unorm_4096: TextureMap::new(
-                4096,
-                wgpu::TextureFormat::Bc7RgbaUnorm,
-                TextureArray::RgbBc7Unorm4096.mip_level_count(),
-            ),
-            rgba_bc7_srgb_512: TextureMap::new(
-                512,
-                wgpu::TextureFormat::Bc7RgbaUnormSrgb,
-                TextureArray::RgbaBc7Srgb512.mip_level_count(),
-            ),
-            rgba_bc7_srgb_1024: TextureMap::new(
-                1024,
-                wgpu::TextureFormat::Bc7RgbaUnormSrgb,
-                TextureArray::RgbaBc7Srgb1024.mip_level_count(),
-            ),
-            rgba_bc7_srgb_2048: TextureMap::new(
-                2048,
-                wgpu::TextureFormat::Bc7RgbaUnormSrgb,
-                TextureArray::RgbaBc7Srgb2048.mip_level_count(),
-            ),
-            rgba_bc7_srgb_4096: TextureMap::new(
-                4096,
-                wgpu::TextureFormat::Bc7RgbaUnormSrgb,
-                TextureArray::RgbaBc7Srgb4096.mip_level_count(),
-            ),
-            no_mip_rgb_bc6h_sfloat_1024: TextureMap::new(
-                1024,
-                wgpu::TextureFormat::Bc6hRgbFloat,
-                TextureArray::NoMipRgbBc6hSFloat1024.mip_level_count(),
-            ),
-        }
+        Self::default()
     }
 
     pub fn add(
         &mut self,
         name: String,
-        texture: ktx2::Reader<&Vec<u8>>,
+        texture: TextureData,
     ) -> Result<(TextureArray, u32), AssetError> {
-        let ktx2::Header {
-            format,
-            pixel_width: width,
-            pixel_height: height,
-            level_count: mip_level_count,
-            ..
-        } = texture.header();
-
-        let (texture_array, texture_index) = match (format, width, height, mip_level_count) {
-            (Some(ktx2::Format::BC5_UNORM_BLOCK), 512, 512, 10) => (
-                TextureArray::RgBc5Unorm512,
-                self.rg_bc5_unorm_512.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC5_UNORM_BLOCK), 1024, 1024, 11) => (
-                TextureArray::RgBc5Unorm1024,
-                self.rg_bc5_unorm_1024.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC5_UNORM_BLOCK), 2048, 2048, 12) => (
-                TextureArray::RgBc5Unorm2048,
-                self.rg_bc5_unorm_2048.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC5_UNORM_BLOCK), 4096, 4096, 13) => (
-                TextureArray::RgBc5Unorm4096,
-                self.rg_bc5_unorm_4096.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC7_UNORM_BLOCK), 512, 512, 10) => (
-                TextureArray::RgbBc7Unorm512,
-                self.rgb_bc7_unorm_512.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC7_UNORM_BLOCK), 1024, 1024, 11) => (
-                TextureArray::RgbBc7Unorm1024,
-                self.rgb_bc7_unorm_1024.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC7_UNORM_BLOCK), 2048, 2048, 12) => (
-                TextureArray::RgbBc7Unorm2048,
-                self.rgb_bc7_unorm_2048.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC7_UNORM_BLOCK), 4096, 4096, 13) => (
-                TextureArray::RgbBc7Unorm4096,
-                self.rgb_bc7_unorm_4096.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC7_SRGB_BLOCK), 512, 512, 10) => (
-                TextureArray::RgbaBc7Srgb512,
-                self.rgba_bc7_srgb_512.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC7_SRGB_BLOCK), 1024, 1024, 11) => (
-                TextureArray::RgbaBc7Srgb1024,
-                self.rgba_bc7_srgb_1024.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC7_SRGB_BLOCK), 2048, 2048, 12) => (
-                TextureArray::RgbaBc7Srgb2048,
-                self.rgba_bc7_srgb_2048.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC7_SRGB_BLOCK), 4096, 4096, 13) => (
-                TextureArray::RgbaBc7Srgb4096,
-                self.rgba_bc7_srgb_4096.add(name, texture),
-            ),
-            (Some(ktx2::Format::BC6H_SFLOAT_BLOCK), 1024, 1024, 1) => (
-                TextureArray::NoMipRgbBc6hSFloat1024,
-                self.no_mip_rgb_bc6h_sfloat_1024.add(name, texture),
-            ),
-            _ => {
-                return Err(AssetError::UnsupportedTextureFormat {
-                    name,
-                    format,
-                    width,
-                    height,
-                    mip_level_count,
-                });
+        let Some(class) = TextureClass::of(&texture) else {
+            return Err(AssetError::UnsupportedTextureFormat {
+                name,
+                format: texture.format,
+                width: texture.width,
+                height: texture.height,
+                mip_level_count: texture.levels.len() as u32,
+            });
+        };
+
+        let texture_map_index = match self
+            .texture_maps
+            .iter()
+            .position(|texture_map| texture_map.class == class)
+        {
+            Some(texture_map_index) => texture_map_index,
+            None => {
+                self.texture_maps.push(TextureMap::new(class));
+                self.texture_maps.len() - 1
             }
         };
+        let texture_map = &mut self.texture_maps[texture_map_index];
+        let texture_count = texture_map.map.len();
+        let texture_index = texture_map.add(name, &texture);
+
+        let texture_array = TextureArray {
+            id: texture_map_index as TextureArrayId,
+            class,
+        };
+        if texture_map.map.len() > texture_count {
+            self.textures.push((texture_array, texture_index));
+        }
 
         Ok((texture_array, texture_index))
     }
+
+    pub fn get(&self, texture_array: TextureArray) -> &TextureMap {
+        &self.texture_maps[texture_array.id as usize]
+    }
+
+    /// The index of a texture among all textures, in the order they were added.
+    pub fn texture_index(&self, texture_array: TextureArray, texture_id: TextureId) -> u32 {
+        self.textures
+            .iter()
+            .position(|&texture| texture == (texture_array, texture_id))
+            .unwrap() as u32
+    }
+
+    pub fn texture_arrays(&self) -> impl Iterator<Item = TextureArray> + '_ {
+        self.texture_maps
+            .iter()
+            .enumerate()
+            .map(|(texture_map_index, texture_map)| TextureArray {
+                id: texture_map_index as TextureArrayId,
+                class: texture_map.class,
+            })
+    }
 }
 
 #[derive(Default, Debug)]
@@ -782,6 +1058,242 @@ pub struct Cubemap {
     pub negative_z: TextureId,
 }
 
+/// Cubic lookup table mapping display colors to graded colors. The red coordinate varies
+/// fastest, then green, then blue.
+pub struct ColorGradingLut {
+    pub size: u32,
+    pub colors: Vec<[f32; 3]>,
+}
+
+fn parse_cube_lut(name: &str, text: &str) -> Result<ColorGradingLut, AssetError> {
+    let invalid_line = |line: usize| AssetError::InvalidCubeLutLine {
+        name: name.to_string(),
+        line,
+    };
+
+    let mut size = None;
+    let mut colors = Vec::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            _ if keyword.starts_with('#') => {}
+            "TITLE" => {}
+            "LUT_3D_SIZE" => {
+                let lut_size = tokens
+                    .next()
+                    .and_then(|token| token.parse::<u32>().ok())
+                    .filter(|lut_size| *lut_size >= 2)
+                    .ok_or_else(|| invalid_line(line_number))?;
+                size = Some(lut_size);
+            }
+            "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                let bounds = tokens
+                    .map(|token| token.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| invalid_line(line_number))?;
+                if bounds.len() != 3 {
+                    return Err(invalid_line(line_number));
+                }
+                if bounds.iter().any(|bound| *bound != expected) {
+                    return Err(AssetError::UnsupportedCubeLutDomain {
+                        name: name.to_string(),
+                    });
+                }
+            }
+            _ => {
+                let color = std::iter::once(keyword)
+                    .chain(tokens)
+                    .map(|token| token.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| invalid_line(line_number))?;
+                if color.len() != 3 {
+                    return Err(invalid_line(line_number));
+                }
+                colors.push([color[0], color[1], color[2]]);
+            }
+        }
+    }
+
+    match size {
+        Some(size) if colors.len() == (size * size * size) as usize => {
+            Ok(ColorGradingLut { size, colors })
+        }
+        _ => Err(AssetError::InvalidCubeLutSize {
+            name: name.to_string(),
+        }),
+    }
+}
+
+/// Where the images of a glTF model are read from: files next to it, data URIs, or buffer views
+/// such as the binary chunk of GLB files.
+struct GltfImages<'a> {
+    model_path: &'a str,
+    directory_path: &'a std::path::Path,
+    buffers: &'a [gltf::buffer::Data],
+}
+
+impl GltfImages<'_> {
+    /// The key the image is loaded once per: the canonicalized path of files, or the model path and
+    /// image index of embedded images.
+    fn key(&self, image: &gltf::Image) -> Result<String, AssetError> {
+        match image.source() {
+            gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                let image_path = self.directory_path.join(uri);
+                image_path
+                    .canonicalize()
+                    .ok()
+                    .and_then(|image_path| image_path.to_str().map(|s| s.to_string()))
+                    .ok_or(AssetError::InvalidPath {
+                        path: image_path.to_string_lossy().to_string(),
+                    })
+            }
+            _ => Ok(format!("{}#image{}", self.model_path, image.index())),
+        }
+    }
+
+    fn data(&self, image: &gltf::Image, key: &str) -> Result<Vec<u8>, AssetError> {
+        match image.source() {
+            gltf::image::Source::Uri { uri, .. } => match uri.strip_prefix("data:") {
+                // Only base64 encoded data URIs can hold binary data.
+                Some(data_uri) => data_uri
+                    .split_once(";base64,")
+                    .and_then(|(_, data)| base64::decode(data).ok())
+                    .ok_or(AssetError::InvalidDataUri {
+                        name: key.to_string(),
+                    }),
+                None => std::fs::read(key).map_err(|_| AssetError::InvalidPath {
+                    path: key.to_string(),
+                }),
+            },
+            gltf::image::Source::View { view, .. } => self.buffers[view.buffer().index()]
+                .get(view.offset()..view.offset() + view.length())
+                .map(|data| data.to_vec())
+                .ok_or_else(|| AssetError::InvalidGltf {
+                    path: self.model_path.to_string(),
+                    reason: format!("buffer view {} runs past its buffer", view.index()),
+                }),
+        }
+    }
+}
+
+/// The texture coordinates set and transform of a base color, metallic-roughness or emissive
+/// texture.
+fn gltf_texture_info_tex_coords(
+    texture_information: &gltf::texture::Info,
+) -> (u32, TextureTransform) {
+    match texture_information.texture_transform() {
+        Some(texture_transform) => (
+            texture_transform
+                .tex_coord()
+                .unwrap_or(texture_information.tex_coord()),
+            TextureTransform::new(
+                texture_transform.offset(),
+                texture_transform.rotation(),
+                texture_transform.scale(),
+            ),
+        ),
+        None => (texture_information.tex_coord(), TextureTransform::default()),
+    }
+}
+
+/// The texture coordinates set and transform of a normal or occlusion texture, whose
+/// `KHR_texture_transform` extension is not parsed by `gltf`.
+fn gltf_texture_extension_tex_coords(
+    tex_coords_set: u32,
+    texture_transform: Option<&gltf::json::Value>,
+) -> (u32, TextureTransform) {
+    match texture_transform.and_then(|texture_transform| {
+        gltf::json::deserialize::from_value::<gltf::json::extensions::texture::TextureTransform>(
+            texture_transform.clone(),
+        )
+        .ok()
+    }) {
+        Some(texture_transform) => (
+            texture_transform.tex_coord.unwrap_or(tex_coords_set),
+            TextureTransform::new(
+                texture_transform.offset.0,
+                texture_transform.rotation.0,
+                texture_transform.scale.0,
+            ),
+        ),
+        None => (tex_coords_set, TextureTransform::default()),
+    }
+}
+
+/// Whether a KTX2 file holds Basis Universal ETC1S or UASTC data, which must be transcoded to a
+/// GPU format. Transcoding them is a separate follow-up to Zstandard supercompression, until
+/// which these files are rejected instead of uploaded.
+fn is_basis_universal(texture: &ktx2::Reader<&[u8]>) -> bool {
+    texture
+        .header()
+        .supercompression_scheme
+        .is_some_and(|scheme| scheme == ktx2::SupercompressionScheme::BasisLZ)
+        || texture.data_format_descriptors().any(|descriptor| {
+            descriptor.header == ktx2::DataFormatDescriptorHeader::BASIC
+                && ktx2::BasicDataFormatDescriptor::parse(descriptor.data).is_ok_and(|descriptor| {
+                    matches!(
+                        descriptor.color_model,
+                        Some(ktx2::ColorModel::ETC1S | ktx2::ColorModel::UASTC)
+                    )
+                })
+        })
+}
+
+/// The mip levels of a KTX2 file, decompressed when they are Zstandard supercompressed.
+fn ktx2_levels(name: &str, texture: &ktx2::Reader<&[u8]>) -> Result<Vec<Vec<u8>>, AssetError> {
+    match texture.header().supercompression_scheme {
+        None => Ok(texture.levels().map(|level| level.to_vec()).collect()),
+        Some(ktx2::SupercompressionScheme::Zstandard) => texture
+            .levels()
+            .map(|level| {
+                let mut decompressed_level = Vec::new();
+                ruzstd::decoding::StreamingDecoder::new(level)
+                    .ok()
+                    .and_then(|mut decoder| {
+                        std::io::Read::read_to_end(&mut decoder, &mut decompressed_level).ok()
+                    })
+                    .ok_or_else(|| AssetError::InvalidTextureData {
+                        name: name.to_string(),
+                    })?;
+                Ok(decompressed_level)
+            })
+            .collect(),
+        Some(supercompression_scheme) => Err(AssetError::UnsupportedSupercompression {
+            name: name.to_string(),
+            supercompression_scheme,
+        }),
+    }
+}
+
+/// The size an image is resized to. The longer side is scaled to the smallest size class holding
+/// it, and the shorter side by the same factor, rounded to whole blocks. Returns `None` for images
+/// larger than the largest size class.
+fn texture_size(width: u32, height: u32) -> Option<(u32, u32)> {
+    let longer_side = width.max(height);
+    let size_class = TEXTURE_SIZE_CLASSES
+        .into_iter()
+        .find(|&size_class| size_class >= longer_side)?;
+
+    let block_size = texture_compression::BLOCK_SIZE as u32;
+    let scale = |side: u32| {
+        let scaled_side = side as f64 * size_class as f64 / longer_side as f64;
+        ((scaled_side / block_size as f64).round() as u32).max(1) * block_size
+    };
+
+    Some(if width >= height {
+        (size_class, scale(height))
+    } else {
+        (scale(width), size_class)
+    })
+}
+
 pub fn assets_path() -> std::path::PathBuf {
     std::path::Path::new(env!("OUT_DIR")).join("assets")
 }
@@ -789,10 +1301,53 @@ pub fn assets_path() -> std::path::PathBuf {
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub struct Material {
     pub base_color: BaseColor,
-    pub normal: Option<TextureReference>,
+    pub normal: Option<Normal>,
     pub occlusion: Occlusion,
     pub roughness: Roughness,
     pub metallic: Metallic,
+    pub emissive: Emissive,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl Material {
+    pub fn texture_references(&self) -> impl Iterator<Item = TextureReference> {
+        let base_color = match self.base_color {
+            BaseColor::Solid { .. } => None,
+            BaseColor::Texture {
+                texture_reference, ..
+            } => Some(texture_reference),
+        };
+        let normal = self.normal.map(|normal| normal.texture_reference);
+        let occlusion = match self.occlusion {
+            Occlusion::Solid(_) => None,
+            Occlusion::Texture {
+                texture_reference, ..
+            } => Some(texture_reference),
+        };
+        let roughness = match self.roughness {
+            Roughness::Solid(_) => None,
+            Roughness::Texture {
+                texture_reference, ..
+            } => Some(texture_reference),
+        };
+        let metallic = match self.metallic {
+            Metallic::Solid(_) => None,
+            Metallic::Texture {
+                texture_reference, ..
+            } => Some(texture_reference),
+        };
+        let emissive = match self.emissive {
+            Emissive::Solid { .. } => None,
+            Emissive::Texture {
+                texture_reference, ..
+            } => Some(texture_reference),
+        };
+
+        [base_color, normal, occlusion, roughness, metallic, emissive]
+            .into_iter()
+            .flatten()
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -840,36 +1395,78 @@ pub enum BaseColor {
         b: HashableF32,
         a: HashableF32,
     },
-    Texture(TextureReference),
+    // The factor is multiplied into the sampled color.
+    Texture {
+        texture_reference: TextureReference,
+        factor: [HashableF32; 4],
+    },
+}
+
+/// A tangent space normal texture, whose X and Y are multiplied by the scale.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Normal {
+    pub texture_reference: TextureReference,
+    pub scale: HashableF32,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Occlusion {
     Solid(HashableF32),
+    // The strength blends from no occlusion to the sampled channel.
     Texture {
         texture_reference: TextureReference,
         channel: u32,
+        strength: HashableF32,
     },
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Roughness {
     Solid(HashableF32),
+    // The factor is multiplied into the sampled channel.
     Texture {
         texture_reference: TextureReference,
         channel: u32,
+        factor: HashableF32,
     },
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
 pub enum Metallic {
     Solid(HashableF32),
+    // The factor is multiplied into the sampled channel.
     Texture {
         texture_reference: TextureReference,
         channel: u32,
+        factor: HashableF32,
     },
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Emissive {
+    Solid {
+        r: HashableF32,
+        g: HashableF32,
+        b: HashableF32,
+    },
+    // The factor is multiplied into the sampled color.
+    Texture {
+        texture_reference: TextureReference,
+        factor: [HashableF32; 3],
+    },
+}
+
+/// How the base color alpha is interpreted, as in glTF.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum AlphaMode {
+    // The alpha is ignored.
+    Opaque,
+    // Fragments with an alpha below the cutoff are discarded, the others are opaque.
+    Mask { cutoff: HashableF32 },
+    // Blended over what is behind, drawn back to front after the opaque surfaces.
+    Blend,
+}
+
 #[derive(Eq, PartialEq, Debug)]
 pub enum AssetError {
     UnsupportedTextureFormat {
@@ -897,17 +1494,41 @@ pub enum AssetError {
     InvalidParentPath {
         path: String,
     },
-    PrimitiveWithoutBaseColor {
+    InvalidGltf {
+        path: String,
+        reason: String,
+    },
+    UnreadableGltfBuffers {
+        path: String,
+        reason: String,
+    },
+    GltfWithoutDefaultScene {
+        path: String,
+    },
+    InvalidTextureData {
         name: String,
     },
-    NonUriImageSource {
+    TextureTooLarge {
+        name: String,
+        width: u32,
+        height: u32,
+    },
+    InvalidDataUri {
+        name: String,
+    },
+    UnsupportedSupercompression {
+        name: String,
+        supercompression_scheme: ktx2::SupercompressionScheme,
+    },
+    UnsupportedBasisUniversalTexture {
         name: String,
     },
     PrimitiveWithoutTextureCoordinates {
         name: String,
     },
-    NonF32TextureCoordinates {
+    UnsupportedTextureCoordinatesSet {
         name: String,
+        tex_coords_set: u32,
     },
     PrimitiveWithoutVertexPositions {
         name: String,
@@ -921,6 +1542,16 @@ pub enum AssetError {
     PrimitiveWithoutIndices {
         name: String,
     },
+    InvalidCubeLutLine {
+        name: String,
+        line: usize,
+    },
+    InvalidCubeLutSize {
+        name: String,
+    },
+    UnsupportedCubeLutDomain {
+        name: String,
+    },
 }
 
 impl std::error::Error for AssetError {}
@@ -962,19 +1593,63 @@ impl std::fmt::Display for AssetError {
             Self::InvalidParentPath { path } => {
                 write!(f, "invalid parent path \"{path}\"")
             }
-            Self::PrimitiveWithoutBaseColor { name } => {
-                write!(f, "primitive \"{name}\" misses a base color texture")
+            Self::InvalidGltf { path, reason } => {
+                write!(f, "invalid glTF file \"{path}\": {reason}")
+            }
+            Self::UnreadableGltfBuffers { path, reason } => {
+                write!(
+                    f,
+                    "failed to read the buffers of glTF file \"{path}\": {reason}"
+                )
+            }
+            Self::GltfWithoutDefaultScene { path } => {
+                write!(f, "glTF file \"{path}\" has no default scene")
+            }
+            Self::TextureTooLarge {
+                name,
+                width,
+                height,
+            } => {
+                write!(
+                    f,
+                    "texture \"{name}\" of size {width}*{height} is larger than the largest size class {}",
+                    TEXTURE_SIZE_CLASSES[TEXTURE_SIZE_CLASSES.len() - 1]
+                )
+            }
+            Self::InvalidTextureData { name } => {
+                write!(
+                    f,
+                    "texture \"{name}\" is neither a valid KTX2 file nor a PNG or JPEG image"
+                )
             }
-            Self::NonUriImageSource { name } => {
-                write!(f, "primitive \"{name}\" contains an non-URI image source")
+            Self::UnsupportedSupercompression {
+                name,
+                supercompression_scheme,
+            } => {
+                write!(
+                    f,
+                    "unsupported KTX2 supercompression {supercompression_scheme:?} for \"{name}\""
+                )
+            }
+            Self::UnsupportedBasisUniversalTexture { name } => {
+                write!(
+                    f,
+                    "Basis Universal texture \"{name}\" is not transcoded on load, transcode it to BC5, BC6H or BC7 offline"
+                )
+            }
+            Self::InvalidDataUri { name } => {
+                write!(f, "image \"{name}\" has an invalid data URI")
             }
             Self::PrimitiveWithoutTextureCoordinates { name } => {
                 write!(f, "primitive \"{name}\" misses texture coordinates")
             }
-            Self::NonF32TextureCoordinates { name } => {
+            Self::UnsupportedTextureCoordinatesSet {
+                name,
+                tex_coords_set,
+            } => {
                 write!(
                     f,
-                    "primitive \"{name}\" contains non-F32 texture coordinates"
+                    "primitive \"{name}\" samples unsupported texture coordinates set {tex_coords_set}"
                 )
             }
             Self::PrimitiveWithoutVertexPositions { name } => {
@@ -989,6 +1664,362 @@ impl std::fmt::Display for AssetError {
             Self::PrimitiveWithoutIndices { name } => {
                 write!(f, "primitive \"{name}\" misses indices")
             }
+            Self::InvalidCubeLutLine { name, line } => {
+                write!(f, "invalid line {line} in LUT \"{name}\"")
+            }
+            Self::InvalidCubeLutSize { name } => {
+                write!(
+                    f,
+                    "LUT \"{name}\" misses a 3D size or has a different number of entries"
+                )
+            }
+            Self::UnsupportedCubeLutDomain { name } => {
+                write!(f, "LUT \"{name}\" has a domain other than [0, 1]")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cube_lut_with_red_varying_fastest() {
+        let text = "\
+# Comment
+TITLE \"identity\"
+LUT_3D_SIZE 2
+DOMAIN_MIN 0.0 0.0 0.0
+DOMAIN_MAX 1.0 1.0 1.0
+
+0.0 0.0 0.0
+1.0 0.0 0.0
+0.0 1.0 0.0
+1.0 1.0 0.0
+0.0 0.0 1.0
+1.0 0.0 1.0
+0.0 1.0 1.0
+1.0 1.0 1.0
+";
+        let lut = parse_cube_lut("identity.cube", text).unwrap();
+
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.colors.len(), 8);
+        assert_eq!(lut.colors[1], [1.0, 0.0, 0.0]);
+        assert_eq!(lut.colors[2], [0.0, 1.0, 0.0]);
+        assert_eq!(lut.colors[4], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn rejects_malformed_cube_luts() {
+        assert_eq!(
+            parse_cube_lut("short.cube", "LUT_3D_SIZE 2\n0 0 0\n").err(),
+            Some(AssetError::InvalidCubeLutSize {
+                name: "short.cube".to_string()
+            })
+        );
+        assert_eq!(
+            parse_cube_lut("line.cube", "LUT_3D_SIZE 2\n0 0\n").err(),
+            Some(AssetError::InvalidCubeLutLine {
+                name: "line.cube".to_string(),
+                line: 2,
+            })
+        );
+        assert_eq!(
+            parse_cube_lut("domain.cube", "DOMAIN_MAX 2 2 2\n").err(),
+            Some(AssetError::UnsupportedCubeLutDomain {
+                name: "domain.cube".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn transforms_texture_coordinates_as_khr_texture_transform() {
+        let identity = TextureTransform::default().matrix();
+        assert_eq!(identity, glam::Affine2::IDENTITY);
+
+        // Scaled first, then rotated, then offset.
+        let transform =
+            TextureTransform::new([0.5, 0.25], std::f32::consts::FRAC_PI_2, [2.0, 1.0]).matrix();
+        let tex_coords = transform.transform_point2(glam::Vec2::new(1.0, 0.0));
+        assert!(tex_coords.abs_diff_eq(glam::Vec2::new(0.5, -1.75), 1e-6));
+    }
+
+    #[test]
+    fn reads_gltf_images_from_data_uris_and_buffer_views() {
+        let gltf::Gltf { document, .. } = gltf::Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "buffers": [{ "byteLength": 4 }],
+                "bufferViews": [{ "buffer": 0, "byteOffset": 1, "byteLength": 2 }],
+                "images": [
+                    { "uri": "data:image/ktx2;base64,AQID" },
+                    { "bufferView": 0, "mimeType": "image/ktx2" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let buffers = [gltf::buffer::Data(vec![4, 5, 6, 7])];
+        let gltf_images = GltfImages {
+            model_path: "model.glb",
+            directory_path: std::path::Path::new("."),
+            buffers: &buffers,
+        };
+
+        let images = document.images().collect::<Vec<_>>();
+        let key = gltf_images.key(&images[0]).unwrap();
+        assert_eq!(key, "model.glb#image0");
+        assert_eq!(gltf_images.data(&images[0], &key), Ok(vec![1, 2, 3]));
+
+        let key = gltf_images.key(&images[1]).unwrap();
+        assert_eq!(key, "model.glb#image1");
+        assert_eq!(gltf_images.data(&images[1], &key), Ok(vec![5, 6]));
+    }
+
+    #[test]
+    fn reports_unreadable_gltf_files_as_asset_errors() {
+        let directory = std::env::temp_dir().join(format!("merlin_gltf_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let invalid_path = directory.join("invalid.gltf");
+        std::fs::write(&invalid_path, "not json").unwrap();
+        let missing_buffer_path = directory.join("missing_buffer.gltf");
+        std::fs::write(
+            &missing_buffer_path,
+            r#"{
+                "asset": { "version": "2.0" },
+                "buffers": [{ "uri": "missing.bin", "byteLength": 4 }]
+            }"#,
+        )
+        .unwrap();
+
+        // The image view runs past the 12 bytes of the buffer holding the single position.
+        let truncated_buffer_path = directory.join("truncated_buffer.gltf");
+        std::fs::write(
+            &truncated_buffer_path,
+            r#"{
+                "asset": { "version": "2.0" },
+                "scene": 0,
+                "scenes": [{ "nodes": [0] }],
+                "nodes": [{ "mesh": 0 }],
+                "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+                "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }],
+                "textures": [{ "source": 0 }],
+                "images": [{ "bufferView": 1, "mimeType": "image/png" }],
+                "accessors": [{
+                    "bufferView": 0,
+                    "componentType": 5126,
+                    "count": 1,
+                    "type": "VEC3",
+                    "min": [0, 0, 0],
+                    "max": [0, 0, 0]
+                }],
+                "bufferViews": [
+                    { "buffer": 0, "byteLength": 12 },
+                    { "buffer": 0, "byteOffset": 4, "byteLength": 64 }
+                ],
+                "buffers": [{
+                    "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAA",
+                    "byteLength": 12
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let mut asset_loader = AssetLoader::new();
+        let invalid = asset_loader.load_gltf_model(&invalid_path);
+        let missing_buffer = asset_loader.load_gltf_model(&missing_buffer_path);
+        let truncated_buffer = asset_loader.load_gltf_model(&truncated_buffer_path);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(matches!(invalid, Err(AssetError::InvalidGltf { .. })));
+        assert!(matches!(
+            missing_buffer,
+            Err(AssetError::UnreadableGltfBuffers { .. })
+        ));
+        assert!(matches!(
+            truncated_buffer,
+            Err(AssetError::InvalidGltf { .. })
+        ));
+    }
+
+    #[test]
+    fn loads_the_flight_helmet() {
+        let mut asset_loader = AssetLoader::new();
+        let model_id = asset_loader
+            .load_gltf_model(assets_path().join("flight_helmet/flight_helmet.gltf"))
+            .unwrap();
+
+        assert!(asset_loader.model_map.index(model_id).is_some());
+        assert_eq!(asset_loader.texture_arrays.textures.len(), 12);
+    }
+
+    #[test]
+    fn adds_textures_to_texture_arrays_by_class() {
+        // 16 bytes per 4x4 block, with at least one block per mip level.
+        let texture = |width: u32, height: u32, mip_level_count: u32| TextureData {
+            format: Some(ktx2::Format::BC7_UNORM_BLOCK),
+            width,
+            height,
+            levels: (0..mip_level_count)
+                .map(|mip_level| {
+                    let width_blocks = (width >> mip_level).max(1).div_ceil(4);
+                    let height_blocks = (height >> mip_level).max(1).div_ceil(4);
+                    vec![0; (width_blocks * height_blocks * 16) as usize]
+                })
+                .collect(),
+        };
+        let mut texture_arrays = TextureArrays::new();
+
+        let (wide, wide_texture) = texture_arrays
+            .add("wide".to_string(), texture(8, 4, 1))
+            .unwrap();
+        let (square, square_texture) = texture_arrays
+            .add("square".to_string(), texture(4, 4, 3))
+            .unwrap();
+        let (other_wide, other_wide_texture) = texture_arrays
+            .add("other_wide".to_string(), texture(8, 4, 1))
+            .unwrap();
+
+        assert_eq!((wide.id(), wide_texture), (0, 0));
+        assert_eq!((square.id(), square_texture), (1, 0));
+        assert_eq!((other_wide, other_wide_texture), (wide, 1));
+        assert_eq!(
+            texture_arrays.texture_arrays().collect::<Vec<_>>(),
+            [wide, square]
+        );
+        assert_eq!(texture_arrays.texture_index(square, square_texture), 1);
+        assert_eq!(texture_arrays.texture_index(wide, other_wide_texture), 2);
+
+        // Sizes must be whole blocks, with at most a full mip chain.
+        assert!(texture_arrays
+            .add("odd".to_string(), texture(6, 4, 1))
+            .is_err());
+        assert!(texture_arrays
+            .add("deep".to_string(), texture(4, 4, 4))
+            .is_err());
+        let mut truncated = texture(8, 4, 1);
+        truncated.levels[0].truncate(16);
+        assert!(texture_arrays
+            .add("truncated".to_string(), truncated)
+            .is_err());
+    }
+
+    #[test]
+    fn resizes_decoded_images_to_their_size_class() {
+        let compress = |width: u32, height: u32| {
+            let rgba = vec![128; (width * height * 4) as usize];
+            TextureData::compress("texture", width, height, rgba, TextureUsage::Linear)
+        };
+
+        let wide = compress(100, 60).unwrap();
+        assert_eq!((wide.width, wide.height), (256, 152));
+        assert!((wide.width as f32 / wide.height as f32 - 100.0 / 60.0).abs() < 0.02);
+        assert_eq!(wide.levels.len(), 9);
+        let tall = compress(64, 256).unwrap();
+        assert_eq!((tall.width, tall.height), (64, 256));
+        assert!(TextureClass::of(&wide).is_some());
+        assert!(TextureClass::of(&tall).is_some());
+
+        assert_eq!(texture_size(300, 200), Some((512, 340)));
+        assert_eq!(texture_size(8192, 4096), Some((8192, 4096)));
+        assert_eq!(texture_size(5000, 5000), Some((8192, 8192)));
+        assert_eq!(texture_size(8193, 100), None);
+        assert!(matches!(
+            compress(8200, 4),
+            Err(AssetError::TextureTooLarge { .. })
+        ));
+    }
+
+    /// A KTX2 file with a basic data format descriptor of the color model and no samples.
+    fn ktx2_file(
+        format: u32,
+        supercompression_scheme: u32,
+        color_model: u32,
+        size: u32,
+        levels: &[Vec<u8>],
+    ) -> Vec<u8> {
+        const HEADER_LENGTH: usize = 80;
+        const LEVEL_INDEX_LENGTH: usize = 24;
+        const DFD_LENGTH: usize = 28;
+
+        let dfd_offset = HEADER_LENGTH + levels.len() * LEVEL_INDEX_LENGTH;
+        let mut level_offset = dfd_offset + DFD_LENGTH;
+
+        let mut file = vec![
+            0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+        ];
+        for value in [format, 1, size, size, 0, 0, 1, levels.len() as u32] {
+            file.extend(value.to_le_bytes());
+        }
+        for value in [
+            supercompression_scheme,
+            dfd_offset as u32,
+            DFD_LENGTH as u32,
+            0,
+            0,
+        ] {
+            file.extend(value.to_le_bytes());
+        }
+        file.extend([0; 16]);
+
+        for level in levels {
+            for value in [level_offset, level.len(), level.len()] {
+                file.extend((value as u64).to_le_bytes());
+            }
+            level_offset += level.len();
+        }
+
+        for value in [DFD_LENGTH as u32, 0, 2 | (24 << 16), color_model, 0, 0, 0] {
+            file.extend(value.to_le_bytes());
+        }
+        file.extend(levels.concat());
+
+        file
+    }
+
+    #[test]
+    fn decompresses_zstandard_supercompressed_ktx2_levels() {
+        let levels = [(0..64).collect::<Vec<u8>>(), vec![7; 16]];
+        let compressed_levels = levels
+            .iter()
+            .map(|level| {
+                ruzstd::encoding::compress_to_vec(
+                    &level[..],
+                    ruzstd::encoding::CompressionLevel::Fastest,
+                )
+            })
+            .collect::<Vec<_>>();
+        let data = ktx2_file(
+            ktx2::Format::BC7_UNORM_BLOCK.0.get(),
+            ktx2::SupercompressionScheme::Zstandard.0.get(),
+            ktx2::ColorModel::BC7.0.get(),
+            8,
+            &compressed_levels,
+        );
+
+        let texture = TextureData::decode("texture.ktx2", &data, TextureUsage::Linear).unwrap();
+        assert_eq!(texture.format, Some(ktx2::Format::BC7_UNORM_BLOCK));
+        assert_eq!((texture.width, texture.height), (8, 8));
+        assert_eq!(texture.levels, levels);
+    }
+
+    #[test]
+    fn rejects_basis_universal_ktx2_textures() {
+        let uastc = ktx2_file(0, 0, ktx2::ColorModel::UASTC.0.get(), 4, &[vec![0; 16]]);
+        let etc1s = ktx2_file(
+            0,
+            ktx2::SupercompressionScheme::BasisLZ.0.get(),
+            ktx2::ColorModel::ETC1S.0.get(),
+            4,
+            &[vec![0; 8]],
+        );
+
+        for data in [uastc, etc1s] {
+            assert!(matches!(
+                TextureData::decode("texture.ktx2", &data, TextureUsage::Color),
+                Err(AssetError::UnsupportedBasisUniversalTexture { .. })
+            ));
         }
     }
 }
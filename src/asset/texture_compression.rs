@@ -0,0 +1,381 @@
+//! CPU mip-mapping and block compression of decoded RGBA8 images, for textures that do not ship as
+//! compressed KTX2 files.
+
+pub const BLOCK_SIZE: usize = 4;
+
+// Interpolation weights of the 4-bit BC7 indices, out of 64.
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Halves the image until it is 1x1, starting with the image itself. sRGB colors are averaged in
+/// linear space.
+pub fn generate_mip_chain(width: u32, height: u32, rgba: Vec<u8>, srgb: bool) -> Vec<Vec<u8>> {
+    let to_linear = |value: u8| {
+        let value = value as f32 / 255.0;
+        if !srgb {
+            value
+        } else if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let from_linear = |value: f32| {
+        let value = if !srgb {
+            value
+        } else if value <= 0.0031308 {
+            value * 12.92
+        } else {
+            1.055 * value.powf(1.0 / 2.4) - 0.055
+        };
+        (value * 255.0).round().clamp(0.0, 255.0) as u8
+    };
+
+    let (mut width, mut height) = (width as usize, height as usize);
+    let mut mip_levels = vec![rgba];
+    while width > 1 || height > 1 {
+        let (mip_width, mip_height) = ((width / 2).max(1), (height / 2).max(1));
+        let previous = mip_levels.last().unwrap();
+
+        let mut mip_level = Vec::with_capacity(mip_width * mip_height * 4);
+        for y in 0..mip_height {
+            for x in 0..mip_width {
+                let (x0, y0) = ((x * 2).min(width - 1), (y * 2).min(height - 1));
+                let (x1, y1) = ((x * 2 + 1).min(width - 1), (y * 2 + 1).min(height - 1));
+                for channel in 0..4 {
+                    let texel = |x: usize, y: usize| previous[(y * width + x) * 4 + channel];
+                    // Alpha is always linear.
+                    let sum = if channel < 3 {
+                        to_linear(texel(x0, y0))
+                            + to_linear(texel(x1, y0))
+                            + to_linear(texel(x0, y1))
+                            + to_linear(texel(x1, y1))
+                    } else {
+                        (texel(x0, y0) as f32
+                            + texel(x1, y0) as f32
+                            + texel(x0, y1) as f32
+                            + texel(x1, y1) as f32)
+                            / 255.0
+                    };
+                    mip_level.push(if channel < 3 {
+                        from_linear(sum / 4.0)
+                    } else {
+                        (sum / 4.0 * 255.0).round() as u8
+                    });
+                }
+            }
+        }
+
+        mip_levels.push(mip_level);
+        (width, height) = (mip_width, mip_height);
+    }
+
+    mip_levels
+}
+
+/// Compresses the red and green channels into BC5 blocks.
+pub fn compress_bc5(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    compress_blocks(width, height, rgba, |block| {
+        let mut compressed_block = [0; 16];
+        compressed_block[..8].copy_from_slice(&compress_bc4_channel(block, 0));
+        compressed_block[8..].copy_from_slice(&compress_bc4_channel(block, 1));
+        compressed_block
+    })
+}
+
+/// Compresses into BC7 mode 6 blocks: a single pair of RGBA endpoints per block, with 16
+/// interpolated values.
+pub fn compress_bc7(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    compress_blocks(width, height, rgba, compress_bc7_block)
+}
+
+/// Splits the image into 4x4 blocks, repeating the edge texels of images that are not a multiple of
+/// the block size.
+fn compress_blocks<F>(width: u32, height: u32, rgba: &[u8], compress_block: F) -> Vec<u8>
+where
+    F: Fn(&[[u8; 4]; 16]) -> [u8; 16],
+{
+    let (width, height) = (width as usize, height as usize);
+    let (block_columns, block_rows) = (width.div_ceil(BLOCK_SIZE), height.div_ceil(BLOCK_SIZE));
+
+    let mut compressed = Vec::with_capacity(block_columns * block_rows * 16);
+    for block_row in 0..block_rows {
+        for block_column in 0..block_columns {
+            let mut block = [[0; 4]; 16];
+            for (i, texel) in block.iter_mut().enumerate() {
+                let x = (block_column * BLOCK_SIZE + i % BLOCK_SIZE).min(width - 1);
+                let y = (block_row * BLOCK_SIZE + i / BLOCK_SIZE).min(height - 1);
+                let offset = (y * width + x) * 4;
+                texel.copy_from_slice(&rgba[offset..offset + 4]);
+            }
+            compressed.extend(compress_block(&block));
+        }
+    }
+
+    compressed
+}
+
+fn compress_bc4_channel(block: &[[u8; 4]; 16], channel: usize) -> [u8; 8] {
+    let values = block.map(|texel| texel[channel]);
+    let max = *values.iter().max().unwrap();
+    let min = *values.iter().min().unwrap();
+
+    // With the first endpoint greater, the palette interpolates 6 values in between.
+    let palette: [u32; 8] = std::array::from_fn(|i| match i {
+        0 => max as u32,
+        1 => min as u32,
+        _ => ((8 - i as u32) * max as u32 + (i as u32 - 1) * min as u32) / 7,
+    });
+
+    let mut indices = 0u64;
+    for (i, &value) in values.iter().enumerate() {
+        let index = (0..8)
+            .min_by_key(|&index| palette[index].abs_diff(value as u32))
+            .unwrap() as u64;
+        indices |= index << (i * 3);
+    }
+
+    let mut compressed_block = [0; 8];
+    compressed_block[0] = max;
+    compressed_block[1] = min;
+    compressed_block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    compressed_block
+}
+
+fn compress_bc7_block(block: &[[u8; 4]; 16]) -> [u8; 16] {
+    let texels = block.map(|texel| glam::Vec4::from_array(texel.map(|value| value as f32)));
+
+    // The endpoints are the extremes of the texels along their principal axis.
+    let mean = texels.iter().sum::<glam::Vec4>() / 16.0;
+    let axis = principal_axis(&texels, mean);
+    let (min, max) = texels
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), &texel| {
+            let projection = (texel - mean).dot(axis);
+            (min.min(projection), max.max(projection))
+        });
+    let endpoints = [mean + axis * min, mean + axis * max].map(quantize_bc7_endpoint);
+    let palette: [glam::Vec4; 16] = std::array::from_fn(|i| {
+        let weight = BC7_WEIGHTS_4[i];
+        let interpolate = |channel: usize| {
+            ((64 - weight) * endpoints[0].0[channel] as u32
+                + weight * endpoints[1].0[channel] as u32
+                + 32)
+                >> 6
+        };
+        glam::Vec4::new(
+            interpolate(0) as f32,
+            interpolate(1) as f32,
+            interpolate(2) as f32,
+            interpolate(3) as f32,
+        )
+    });
+
+    let mut indices = texels.map(|texel| {
+        (0..16)
+            .min_by(|&a, &b| {
+                palette[a]
+                    .distance_squared(texel)
+                    .total_cmp(&palette[b].distance_squared(texel))
+            })
+            .unwrap() as u32
+    });
+
+    // The highest bit of the first index is implied to be zero.
+    let mut endpoints = endpoints;
+    if indices[0] >= 8 {
+        endpoints.swap(0, 1);
+        indices = indices.map(|index| 15 - index);
+    }
+
+    let mut bits = BitWriter::default();
+    bits.write(1 << 6, 7);
+    for channel in 0..4 {
+        for (endpoint, _) in &endpoints {
+            bits.write(endpoint[channel] as u32 >> 1, 7);
+        }
+    }
+    for (_, p_bit) in &endpoints {
+        bits.write(*p_bit, 1);
+    }
+    for (i, &index) in indices.iter().enumerate() {
+        bits.write(index, if i == 0 { 3 } else { 4 });
+    }
+
+    bits.bits.to_le_bytes()
+}
+
+fn principal_axis(texels: &[glam::Vec4; 16], mean: glam::Vec4) -> glam::Vec4 {
+    let covariance = texels.iter().fold(glam::Mat4::ZERO, |covariance, &texel| {
+        let offset = texel - mean;
+        covariance
+            + glam::Mat4::from_cols(
+                offset * offset.x,
+                offset * offset.y,
+                offset * offset.z,
+                offset * offset.w,
+            )
+    });
+
+    // Power iteration, from the diagonal of the bounding box.
+    let (min, max) = texels
+        .iter()
+        .fold((glam::Vec4::MAX, glam::Vec4::MIN), |(min, max), &texel| {
+            (min.min(texel), max.max(texel))
+        });
+    let mut axis = max - min;
+    for _ in 0..8 {
+        let next_axis = covariance * axis;
+        if next_axis.length_squared() < f32::EPSILON {
+            break;
+        }
+        axis = next_axis.normalize();
+    }
+
+    axis.try_normalize().unwrap_or(glam::Vec4::ZERO)
+}
+
+/// Quantizes an endpoint to 7 bits per channel and a shared lowest bit, picking the lowest bit
+/// closest to the endpoint.
+fn quantize_bc7_endpoint(endpoint: glam::Vec4) -> ([u8; 4], u32) {
+    let endpoint = endpoint.clamp(glam::Vec4::ZERO, glam::Vec4::splat(255.0));
+    [0, 1]
+        .map(|p_bit| {
+            let quantized = endpoint.to_array().map(|value| {
+                let high_bits = ((value - p_bit as f32) / 2.0).round().clamp(0.0, 127.0) as u8;
+                (high_bits << 1) | p_bit as u8
+            });
+            let error = glam::Vec4::from_array(quantized.map(|value| value as f32))
+                .distance_squared(endpoint);
+            (quantized, p_bit, error)
+        })
+        .into_iter()
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(quantized, p_bit, _)| (quantized, p_bit))
+        .unwrap()
+}
+
+/// Writes a block from its lowest bit.
+#[derive(Default)]
+struct BitWriter {
+    bits: u128,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bit_count: u32) {
+        self.bits |= (value as u128) << self.bit_count;
+        self.bit_count += bit_count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_bc4_channel(compressed_block: &[u8]) -> [u8; 16] {
+        let (max, min) = (compressed_block[0] as u32, compressed_block[1] as u32);
+        let mut index_bytes = [0; 8];
+        index_bytes[..6].copy_from_slice(&compressed_block[2..8]);
+        let indices = u64::from_le_bytes(index_bytes);
+
+        std::array::from_fn(|i| {
+            let index = ((indices >> (i * 3)) & 0b111) as u32;
+            (match index {
+                0 => max,
+                1 => min,
+                _ => ((8 - index) * max + (index - 1) * min) / 7,
+            }) as u8
+        })
+    }
+
+    fn decode_bc7_mode_6(compressed_block: &[u8]) -> [[u8; 4]; 16] {
+        let bits = u128::from_le_bytes(compressed_block.try_into().unwrap());
+        let mut offset = 0;
+        let mut read = |bit_count: u32| {
+            let value = ((bits >> offset) & ((1 << bit_count) - 1)) as u32;
+            offset += bit_count;
+            value
+        };
+
+        assert_eq!(read(7), 1 << 6);
+        let mut endpoints = [[0; 4]; 2];
+        for channel in 0..4 {
+            for endpoint in &mut endpoints {
+                endpoint[channel] = read(7) << 1;
+            }
+        }
+        for endpoint in &mut endpoints {
+            let p_bit = read(1);
+            endpoint.iter_mut().for_each(|value| *value |= p_bit);
+        }
+
+        std::array::from_fn(|i| {
+            let weight = BC7_WEIGHTS_4[read(if i == 0 { 3 } else { 4 }) as usize];
+            std::array::from_fn(|channel| {
+                (((64 - weight) * endpoints[0][channel] + weight * endpoints[1][channel] + 32) >> 6)
+                    as u8
+            })
+        })
+    }
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                [
+                    (x * 255 / (width - 1)) as u8,
+                    (y * 255 / (height - 1)) as u8,
+                    128,
+                    255,
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compresses_bc5_blocks_close_to_the_source() {
+        let rgba = gradient(4, 4);
+        let compressed = compress_bc5(4, 4, &rgba);
+        assert_eq!(compressed.len(), 16);
+
+        let red = decode_bc4_channel(&compressed[..8]);
+        let green = decode_bc4_channel(&compressed[8..]);
+        for i in 0..16 {
+            assert!(red[i].abs_diff(rgba[i * 4]) <= 19);
+            assert!(green[i].abs_diff(rgba[i * 4 + 1]) <= 19);
+        }
+    }
+
+    #[test]
+    fn compresses_bc7_blocks_close_to_the_source() {
+        // Mode 6 interpolates between two colors, so the texels vary along a single line.
+        let rgba = (0..16u32)
+            .flat_map(|i| {
+                [
+                    (i * 17) as u8,
+                    200 - (i * 10) as u8,
+                    64,
+                    255 - (i * 5) as u8,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let compressed = compress_bc7(4, 4, &rgba);
+        assert_eq!(compressed.len(), 16);
+
+        for (i, texel) in decode_bc7_mode_6(&compressed).iter().enumerate() {
+            for channel in 0..4 {
+                assert!(texel[channel].abs_diff(rgba[i * 4 + channel]) <= 4);
+            }
+        }
+    }
+
+    #[test]
+    fn generates_mip_chains_down_to_one_texel() {
+        let mip_levels = generate_mip_chain(8, 2, gradient(8, 2), true);
+        let sizes = mip_levels.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(sizes, [8 * 2 * 4, 4 * 4, 2 * 4, 4]);
+
+        // Compression pads the blocks of mip levels smaller than 4x4.
+        assert_eq!(compress_bc7(2, 1, &mip_levels[2]).len(), 16);
+    }
+}
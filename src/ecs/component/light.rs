@@ -0,0 +1,29 @@
+use bevy_ecs::component::Component;
+
+/// Emits light in every direction from the position of its `GlobalTransform`.
+#[derive(Component, Clone, Debug)]
+pub struct PointLight {
+    pub color: glam::Vec3,
+    pub strength: f32,
+    pub range: f32,
+    /// Renders an omnidirectional shadow map, which is costly as the scene is drawn once per cube
+    /// face.
+    pub cast_shadows: bool,
+}
+
+/// Emits parallel light along the forward axis (-Z) of its `GlobalTransform`.
+#[derive(Component, Clone, Debug)]
+pub struct DirectionalLight {
+    pub color: glam::Vec3,
+    pub strength: f32,
+}
+
+/// Emits a cone of light from the position of its `GlobalTransform` along its forward axis (-Z).
+#[derive(Component, Clone, Debug)]
+pub struct SpotLight {
+    pub color: glam::Vec3,
+    pub strength: f32,
+    pub range: f32,
+    pub inner_angle: f32, // In radians.
+    pub outer_angle: f32, // In radians.
+}
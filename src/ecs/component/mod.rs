@@ -1,6 +1,9 @@
 mod mesh;
 pub use mesh::Mesh;
 
+mod light;
+pub use light::{DirectionalLight, PointLight, SpotLight};
+
 mod material;
 pub use material::Material;
 
@@ -24,7 +24,7 @@ impl DerefMut for GlobalTransform {
     }
 }
 
-#[derive(Component, Debug)]
+#[derive(Component, Copy, Clone, Debug)]
 pub struct Transform {
     pub translation: glam::Vec3,
     pub rotation: glam::Quat,
@@ -40,3 +40,16 @@ impl Default for Transform {
         }
     }
 }
+
+impl Transform {
+    pub fn from_translation(translation: glam::Vec3) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn compute_affine(&self) -> glam::Affine3A {
+        glam::Affine3A::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
@@ -9,6 +9,29 @@ pub struct Camera {
     pub fov: f32, // In radians.
     pub near: f32,
     pub far: f32,
+    /// Exposure compensation in stops, applied before tonemapping.
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+}
+
+/// Curve mapping the HDR scene color to the display range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tonemapper {
+    #[default]
+    Aces = 0,
+    AgX = 1,
+    Reinhard = 2,
+}
+
+impl Tonemapper {
+    /// Cycles through the tonemappers, wrapping around after the last one.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Aces => Self::AgX,
+            Self::AgX => Self::Reinhard,
+            Self::Reinhard => Self::Aces,
+        }
+    }
 }
 
 impl Camera {
@@ -35,6 +58,8 @@ impl Default for Camera {
             fov: 40.0_f32.to_radians(),
             near: 0.1,
             far: 100.0,
+            exposure: 0.0,
+            tonemapper: Tonemapper::default(),
         }
     }
 }
@@ -53,71 +78,18 @@ pub struct Frustum {
 
 impl Frustum {
     pub fn from_view_projection_matrix(view_projection_matrix: &glam::Mat4) -> Self {
-        let left_plane = Plane {
-            normal: glam::Vec3::new(
-                view_projection_matrix.row(3)[0] + view_projection_matrix.row(0)[0],
-                view_projection_matrix.row(3)[1] + view_projection_matrix.row(0)[1],
-                view_projection_matrix.row(3)[2] + view_projection_matrix.row(0)[2],
-            )
-            .normalize()
-            .into(),
-            distance: view_projection_matrix.row(3)[3] + view_projection_matrix.row(0)[3],
-        };
-
-        let right_plane = Plane {
-            normal: glam::Vec3::new(
-                view_projection_matrix.row(3)[0] - view_projection_matrix.row(0)[0],
-                view_projection_matrix.row(3)[1] - view_projection_matrix.row(0)[1],
-                view_projection_matrix.row(3)[2] - view_projection_matrix.row(0)[2],
-            )
-            .normalize()
-            .into(),
-            distance: view_projection_matrix.row(3)[3] - view_projection_matrix.row(0)[3],
-        };
+        let row_0 = view_projection_matrix.row(0);
+        let row_1 = view_projection_matrix.row(1);
+        let row_2 = view_projection_matrix.row(2);
+        let row_3 = view_projection_matrix.row(3);
 
-        let bottom_plane = Plane {
-            normal: glam::Vec3::new(
-                view_projection_matrix.row(3)[0] + view_projection_matrix.row(1)[0],
-                view_projection_matrix.row(3)[1] + view_projection_matrix.row(1)[1],
-                view_projection_matrix.row(3)[2] + view_projection_matrix.row(1)[2],
-            )
-            .normalize()
-            .into(),
-            distance: view_projection_matrix.row(3)[3] + view_projection_matrix.row(1)[3],
-        };
-
-        let top_plane = Plane {
-            normal: glam::Vec3::new(
-                view_projection_matrix.row(3)[0] - view_projection_matrix.row(1)[0],
-                view_projection_matrix.row(3)[1] - view_projection_matrix.row(1)[1],
-                view_projection_matrix.row(3)[2] - view_projection_matrix.row(1)[2],
-            )
-            .normalize()
-            .into(),
-            distance: view_projection_matrix.row(3)[3] - view_projection_matrix.row(1)[3],
-        };
-
-        let near_plane = Plane {
-            normal: glam::Vec3::new(
-                view_projection_matrix.row(3)[0] + view_projection_matrix.row(2)[0],
-                view_projection_matrix.row(3)[1] + view_projection_matrix.row(2)[1],
-                view_projection_matrix.row(3)[2] + view_projection_matrix.row(2)[2],
-            )
-            .normalize()
-            .into(),
-            distance: view_projection_matrix.row(3)[3] + view_projection_matrix.row(2)[3],
-        };
-
-        let far_plane = Plane {
-            normal: glam::Vec3::new(
-                view_projection_matrix.row(3)[0] - view_projection_matrix.row(2)[0],
-                view_projection_matrix.row(3)[1] - view_projection_matrix.row(2)[1],
-                view_projection_matrix.row(3)[2] - view_projection_matrix.row(2)[2],
-            )
-            .normalize()
-            .into(),
-            distance: view_projection_matrix.row(3)[3] - view_projection_matrix.row(2)[3],
-        };
+        let left_plane = Plane::from_coefficients(row_3 + row_0);
+        let right_plane = Plane::from_coefficients(row_3 - row_0);
+        let bottom_plane = Plane::from_coefficients(row_3 + row_1);
+        let top_plane = Plane::from_coefficients(row_3 - row_1);
+        // Clip space depth ranges from 0 to 1 in wgpu.
+        let near_plane = Plane::from_coefficients(row_2);
+        let far_plane = Plane::from_coefficients(row_3 - row_2);
 
         let mut corners: [[f32; 4]; 8] = Default::default();
         let inverse_view_projection_matrix = view_projection_matrix.inverse();
@@ -125,7 +97,7 @@ impl Frustum {
         for (i, corner) in corners.iter_mut().enumerate() {
             let x = if (i & 1) == 0 { -1.0 } else { 1.0 };
             let y = if (i & 2) == 0 { -1.0 } else { 1.0 };
-            let z = if (i & 4) == 0 { -1.0 } else { 1.0 };
+            let z = if (i & 4) == 0 { 0.0 } else { 1.0 };
 
             let clip_space_corner = glam::Vec4::new(x, y, z, 1.0);
             let world_space_corner = inverse_view_projection_matrix * clip_space_corner;
@@ -149,6 +121,28 @@ impl Frustum {
             corners,
         }
     }
+
+    /// A frustum containing every bounding box, for passes that must not cull anything.
+    pub fn unbounded() -> Self {
+        // Planes with a zero normal are protruded by every box, and the corners straddle every box
+        // on each axis.
+        let plane = Plane {
+            normal: [0.0; 3],
+            distance: 0.0,
+        };
+        let mut corners = [[f32::MIN, f32::MIN, f32::MIN, 0.0]; 8];
+        corners[4..].fill([f32::MAX, f32::MAX, f32::MAX, 0.0]);
+
+        Self {
+            left_plane: plane,
+            right_plane: plane,
+            bottom_plane: plane,
+            top_plane: plane,
+            near_plane: plane,
+            far_plane: plane,
+            corners,
+        }
+    }
 }
 
 #[repr(C)]
@@ -157,3 +151,365 @@ pub struct Plane {
     pub normal: [f32; 3],
     pub distance: f32,
 }
+
+impl Plane {
+    pub fn from_coefficients(coefficients: glam::Vec4) -> Self {
+        let length = coefficients.truncate().length();
+
+        Self {
+            normal: (coefficients.truncate() / length).into(),
+            distance: coefficients.w / length,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics;
+
+    // Mirrors `cs_main` and `intersects_frustum` in the frustum culling shader, and is checked
+    // against it by `mirrors_the_frustum_culling_shader`.
+    impl Frustum {
+        fn intersects_bounding_box(&self, bounding_box: &BoundingBox) -> bool {
+            [
+                self.left_plane,
+                self.right_plane,
+                self.bottom_plane,
+                self.top_plane,
+                self.near_plane,
+                self.far_plane,
+            ]
+            .iter()
+            .all(|plane| plane.is_protruded_by(bounding_box))
+                && !self.is_outside_bounding_box(bounding_box)
+        }
+
+        fn is_outside_bounding_box(&self, bounding_box: &BoundingBox) -> bool {
+            (0..3).any(|axis| {
+                self.corners
+                    .iter()
+                    .all(|corner| corner[axis] < bounding_box.min[axis])
+                    || self
+                        .corners
+                        .iter()
+                        .all(|corner| corner[axis] > bounding_box.max[axis])
+            })
+        }
+    }
+
+    impl Plane {
+        fn is_protruded_by(&self, bounding_box: &BoundingBox) -> bool {
+            let normal = glam::Vec3::from(self.normal);
+            let p = glam::Vec3::select(
+                normal.cmpgt(glam::Vec3::ZERO),
+                bounding_box.max,
+                bounding_box.min,
+            );
+
+            normal.dot(p) + self.distance >= 0.0
+        }
+    }
+
+    struct BoundingBox {
+        min: glam::Vec3,
+        max: glam::Vec3,
+    }
+
+    impl BoundingBox {
+        fn new(min: [f32; 3], max: [f32; 3]) -> Self {
+            let bounding_box = graphics::BoundingBox::new(min, max);
+
+            Self {
+                min: bounding_box.min.into(),
+                max: bounding_box.max.into(),
+            }
+        }
+
+        fn transform(&self, transform: &glam::Affine3A) -> Self {
+            let center = transform.transform_point3((self.min + self.max) * 0.5);
+            let extents = glam::Mat3::from_cols(
+                transform.matrix3.x_axis.abs().into(),
+                transform.matrix3.y_axis.abs().into(),
+                transform.matrix3.z_axis.abs().into(),
+            ) * ((self.max - self.min) * 0.5);
+
+            Self {
+                min: center - extents,
+                max: center + extents,
+            }
+        }
+    }
+
+    /// Culls each bounding box, with its instance transform, through the frustum culling shader
+    /// and returns which ones are kept. Each box is its own mesh and batch.
+    fn cull_with_shader(
+        gpu: &graphics::Gpu,
+        frustum: Frustum,
+        instances: &[([f32; 3], [f32; 3], glam::Affine3A)],
+    ) -> Vec<bool> {
+        use graphics::pipeline::compute::frustum_culling;
+        use wgpu::util::DeviceExt;
+
+        let device = &gpu.device;
+        let storage_buffer = |contents: &[u8], usage: wgpu::BufferUsages| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents,
+                usage: wgpu::BufferUsages::STORAGE | usage,
+            })
+        };
+
+        let bounding_boxes = instances
+            .iter()
+            .map(|&(min, max, _)| graphics::BoundingBox::new(min, max))
+            .collect::<Vec<_>>();
+        let culling_information = (0..instances.len() as u32)
+            .map(|index| frustum_culling::InstanceCullingInformation {
+                batch_id: index,
+                mesh_id: index,
+            })
+            .collect::<Vec<_>>();
+        let transforms = instances
+            .iter()
+            .map(|(_, _, transform)| glam::Mat4::from(*transform).to_cols_array())
+            .collect::<Vec<_>>();
+        let draw_commands = (0..instances.len() as u32)
+            .map(|index| wgpu::util::DrawIndexedIndirectArgs {
+                index_count: 0,
+                instance_count: 0,
+                first_index: 0,
+                base_vertex: 0,
+                first_instance: index,
+            })
+            .collect::<Vec<_>>();
+        let draw_commands_bytes = draw_commands
+            .iter()
+            .flat_map(|draw_command| draw_command.as_bytes().to_vec())
+            .collect::<Vec<_>>();
+
+        let bounding_boxes_buffer = storage_buffer(
+            bytemuck::cast_slice(&bounding_boxes),
+            wgpu::BufferUsages::empty(),
+        );
+        let culling_information_buffer = storage_buffer(
+            bytemuck::cast_slice(&culling_information),
+            wgpu::BufferUsages::empty(),
+        );
+        let draw_commands_buffer =
+            storage_buffer(&draw_commands_bytes, wgpu::BufferUsages::COPY_SRC);
+        let indirect_instances_buffer =
+            frustum_culling::create_indirect_instances_buffer(device, instances.len());
+        let frustum_buffer = frustum_culling::create_frustum_buffer(device, frustum);
+        let instance_count_buffer =
+            frustum_culling::create_instance_count_buffer(device, instances.len() as u32);
+        let transforms_buffer = storage_buffer(
+            bytemuck::cast_slice(&transforms),
+            wgpu::BufferUsages::empty(),
+        );
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: draw_commands_buffer.size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let frustum_culling = graphics::pipeline::compute::FrustumCulling::new(device);
+        let bind_group = frustum_culling.create_bind_group_frustum_culling(
+            device,
+            bounding_boxes_buffer.as_entire_binding(),
+            culling_information_buffer.as_entire_binding(),
+            draw_commands_buffer.as_entire_binding(),
+            indirect_instances_buffer.as_entire_binding(),
+            frustum_buffer.as_entire_binding(),
+            instance_count_buffer.as_entire_binding(),
+            transforms_buffer.as_entire_binding(),
+        );
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&Default::default());
+            frustum_culling.prepare(&mut compute_pass, &bind_group);
+            compute_pass.dispatch_workgroups((instances.len() as u32).div_ceil(64), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(
+            &draw_commands_buffer,
+            0,
+            &readback_buffer,
+            0,
+            readback_buffer.size(),
+        );
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+
+        let buffer_slice = readback_buffer.slice(..);
+        buffer_slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::Maintain::Wait);
+        let draw_commands_data = buffer_slice.get_mapped_range();
+        let draw_commands: &[[u32; 5]] = bytemuck::cast_slice(&draw_commands_data);
+
+        draw_commands
+            .iter()
+            .map(|&[_, instance_count, ..]| instance_count == 1)
+            .collect()
+    }
+
+    fn frustum(camera: &Camera) -> Frustum {
+        Frustum::from_view_projection_matrix(&(camera.perspective() * camera.view_matrix()))
+    }
+
+    fn unit_box_at(translation: glam::Vec3) -> BoundingBox {
+        BoundingBox::new([-0.5, -0.5, -0.5], [0.5, 0.5, 0.5])
+            .transform(&glam::Affine3A::from_translation(translation))
+    }
+
+    #[test]
+    fn keeps_boxes_in_front_of_the_camera() {
+        let frustum = frustum(&Camera::default());
+
+        assert!(frustum.intersects_bounding_box(&unit_box_at(glam::Vec3::new(0.0, 1.0, -5.0))));
+        // Straddling the near plane.
+        assert!(frustum.intersects_bounding_box(&unit_box_at(glam::Vec3::new(0.0, 1.0, 0.0))));
+        // Straddling the far plane.
+        assert!(frustum.intersects_bounding_box(&unit_box_at(glam::Vec3::new(0.0, 1.0, -100.0))));
+    }
+
+    #[test]
+    fn culls_boxes_outside_each_plane() {
+        let frustum = frustum(&Camera::default());
+
+        for translation in [
+            glam::Vec3::new(0.0, 1.0, 5.0),
+            glam::Vec3::new(0.0, 1.0, -101.0),
+            glam::Vec3::new(-10.0, 1.0, -5.0),
+            glam::Vec3::new(10.0, 1.0, -5.0),
+            glam::Vec3::new(0.0, -10.0, -5.0),
+            glam::Vec3::new(0.0, 10.0, -5.0),
+        ] {
+            assert!(
+                !frustum.intersects_bounding_box(&unit_box_at(translation)),
+                "box at {translation} should be culled"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_boxes_containing_the_frustum() {
+        let frustum = frustum(&Camera::default());
+
+        assert!(frustum.intersects_bounding_box(&BoundingBox::new(
+            [-1000.0, -1000.0, -1000.0],
+            [1000.0, 1000.0, 1000.0],
+        )));
+    }
+
+    #[test]
+    fn culls_large_boxes_protruding_every_plane() {
+        let frustum = frustum(&Camera {
+            far: 10.0,
+            ..Default::default()
+        });
+
+        // Reaches past the far plane, and sits to the right of the far corners.
+        let bounding_box = BoundingBox::new([7.0, -20.0, -30.0], [30.0, 20.0, -9.0]);
+        assert!(!frustum.intersects_bounding_box(&bounding_box));
+    }
+
+    #[test]
+    fn unbounded_frustum_keeps_every_box() {
+        let frustum = Frustum::unbounded();
+
+        for translation in [
+            glam::Vec3::ZERO,
+            glam::Vec3::new(0.0, 1.0, 5.0),
+            glam::Vec3::new(-1000.0, 1000.0, -1000.0),
+        ] {
+            assert!(frustum.intersects_bounding_box(&unit_box_at(translation)));
+        }
+        assert!(frustum.intersects_bounding_box(&BoundingBox::new(
+            [-1000.0, -1000.0, -1000.0],
+            [1000.0, 1000.0, 1000.0],
+        )));
+    }
+
+    #[test]
+    #[ignore = "needs a software adapter, run with `cargo test -- --ignored`"]
+    fn mirrors_the_frustum_culling_shader() {
+        let gpu = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(graphics::Gpu::new_headless(1, 1, true))
+            .unwrap();
+
+        let unit_box = ([-0.5, -0.5, -0.5], [0.5, 0.5, 0.5]);
+        let mut instances = [-101.0, -100.0, -5.0, 0.0, 5.0]
+            .into_iter()
+            .flat_map(|z| {
+                [-10.0, -3.0, 0.0, 1.0, 3.0, 10.0]
+                    .into_iter()
+                    .map(move |xy| glam::Vec3::new(xy, xy.abs().max(1.0), z))
+            })
+            .map(|translation| {
+                (
+                    unit_box.0,
+                    unit_box.1,
+                    glam::Affine3A::from_translation(translation),
+                )
+            })
+            .collect::<Vec<_>>();
+        instances.extend([
+            (
+                [-1000.0, -1000.0, -1000.0],
+                [1000.0, 1000.0, 1000.0],
+                glam::Affine3A::IDENTITY,
+            ),
+            (
+                [7.0, -20.0, -30.0],
+                [30.0, 20.0, -9.0],
+                glam::Affine3A::IDENTITY,
+            ),
+            (
+                unit_box.0,
+                unit_box.1,
+                glam::Affine3A::from_scale_rotation_translation(
+                    glam::Vec3::splat(2.0),
+                    glam::Quat::from_rotation_y(std::f32::consts::FRAC_PI_4),
+                    glam::Vec3::new(0.0, 1.0, -5.0),
+                ),
+            ),
+        ]);
+
+        for frustum in [
+            frustum(&Camera::default()),
+            frustum(&Camera {
+                far: 10.0,
+                ..Default::default()
+            }),
+            Frustum::unbounded(),
+        ] {
+            let kept = cull_with_shader(&gpu, frustum, &instances);
+
+            for (&(min, max, transform), kept) in instances.iter().zip(kept) {
+                let bounding_box = BoundingBox::new(min, max).transform(&transform);
+                assert_eq!(
+                    frustum.intersects_bounding_box(&bounding_box),
+                    kept,
+                    "box from {min:?} to {max:?} with {transform} disagrees with the shader"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn applies_instance_transform() {
+        let frustum = frustum(&Camera::default());
+        let bounding_box = BoundingBox::new([-0.5, -0.5, -0.5], [0.5, 0.5, 0.5]);
+
+        assert!(!frustum.intersects_bounding_box(&bounding_box));
+        assert!(frustum.intersects_bounding_box(&bounding_box.transform(
+            &glam::Affine3A::from_scale_rotation_translation(
+                glam::Vec3::splat(2.0),
+                glam::Quat::from_rotation_y(std::f32::consts::FRAC_PI_4),
+                glam::Vec3::new(0.0, 1.0, -5.0),
+            )
+        )));
+    }
+}
@@ -0,0 +1,8 @@
+use bevy_ecs::system::Resource;
+
+/// Tints and scales the image-based lighting computed from the skybox.
+#[derive(Resource, Clone, Debug)]
+pub struct AmbientLight {
+    pub color: glam::Vec3,
+    pub strength: f32,
+}
@@ -1,2 +1,8 @@
-mod camera;
+pub mod camera;
 pub use camera::{Camera, Frustum};
+
+mod light;
+pub use light::AmbientLight;
+
+mod post_processing;
+pub use post_processing::PostProcessing;
@@ -0,0 +1,25 @@
+use bevy_ecs::system::Resource;
+
+/// Effects applied to the rendered frame after the main pass. Disabled effects are skipped. FXAA is
+/// chosen when the scene is set up instead, see `scene::FXAA`.
+#[derive(Resource, Clone, Debug)]
+pub struct PostProcessing {
+    pub bloom: bool,
+    pub bloom_intensity: f32, // Share of the blurred image mixed into the frame.
+    pub vignette: bool,
+    pub vignette_intensity: f32,
+    /// Applies the color grading lookup table after tonemapping.
+    pub color_grading: bool,
+}
+
+impl Default for PostProcessing {
+    fn default() -> Self {
+        Self {
+            bloom: true,
+            bloom_intensity: 0.04,
+            vignette: false,
+            vignette_intensity: 0.3,
+            color_grading: false,
+        }
+    }
+}
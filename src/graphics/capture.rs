@@ -0,0 +1,101 @@
+/// An RGBA8 image read back from the GPU.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    PngEncodingError(image::ImageError),
+}
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PngEncodingError(error) => write!(f, "failed to save PNG: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl Capture {
+    pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> Result<(), CaptureError> {
+        image::save_buffer_with_format(
+            path,
+            &self.pixels,
+            self.width,
+            self.height,
+            image::ExtendedColorType::Rgba8,
+            image::ImageFormat::Png,
+        )
+        .map_err(CaptureError::PngEncodingError)
+    }
+}
+
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Capture {
+    const BYTES_PER_PIXEL: u32 = 4;
+
+    let width = texture.width();
+    let height = texture.height();
+
+    // Rows copied into a buffer must be aligned to COPY_BYTES_PER_ROW_ALIGNMENT.
+    let unpadded_bytes_per_row = width * BYTES_PER_PIXEL;
+    let padded_bytes_per_row =
+        unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback_buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("readback_encoder"),
+    });
+
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &readback_buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let buffer_slice = readback_buffer.slice(..);
+    let (sender, receiver) = crossbeam::channel::bounded(1);
+    buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+        sender.send(result).unwrap();
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv().unwrap().unwrap();
+
+    let pixels = buffer_slice
+        .get_mapped_range()
+        .chunks_exact(padded_bytes_per_row as usize)
+        .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+        .copied()
+        .collect();
+
+    readback_buffer.unmap();
+
+    Capture {
+        width,
+        height,
+        pixels,
+    }
+}
@@ -1,15 +1,67 @@
 use std::sync::Arc;
 
+/// Format of the scene color targets, tonemapped into the surface format at the end of a frame.
+pub const HDR_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Features needed to sample materials from one binding array of textures, indexed per fragment.
+pub const TEXTURE_BINDING_ARRAY_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_BINDING_ARRAY
+    .union(wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
+
 #[derive(bevy_ecs::system::Resource, Debug)]
 pub struct Gpu<'a> {
-    pub instance: wgpu::Instance,
-    pub surface: wgpu::Surface<'a>,
+    pub target: Target<'a>,
     pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
+    // Whether material textures are bound as a binding array rather than texture arrays.
+    pub texture_binding_arrays: bool,
+}
+
+#[derive(Debug)]
+pub enum Target<'a> {
+    Surface(wgpu::Surface<'a>),
+    Offscreen(wgpu::Texture),
+}
+
+pub struct Frame {
+    surface_texture: Option<wgpu::SurfaceTexture>,
+    pub view: wgpu::TextureView,
+}
+
+impl Frame {
+    pub fn present(self) {
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GpuError {
+    AdapterNotFound,
+    FeatureNotSupported(wgpu::Features),
+    DownlevelFlagsNotSupported(wgpu::DownlevelFlags),
+    RequestDeviceError(wgpu::RequestDeviceError),
+}
+
+impl std::fmt::Display for GpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AdapterNotFound => write!(f, "no suitable GPU adapter found"),
+            Self::FeatureNotSupported(features) => {
+                write!(f, "{features:?} not supported by GPU")
+            }
+            Self::DownlevelFlagsNotSupported(flags) => {
+                write!(f, "{flags:?} not supported by GPU")
+            }
+            Self::RequestDeviceError(error) => write!(f, "failed to request device: {error}"),
+        }
+    }
 }
 
+impl std::error::Error for GpuError {}
+
 impl<'a> Gpu<'a> {
     pub async fn new(window: Arc<winit::window::Window>) -> Self {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -49,69 +101,200 @@ impl<'a> Gpu<'a> {
             desired_maximum_frame_latency: 2,
         };
 
-        let mut required_features = wgpu::Features::empty();
+        let (device, queue, texture_binding_arrays) = request_device(&adapter)
+            .await
+            .unwrap_or_else(|error| panic!("{error}"));
 
-        if !adapter
-            .features()
-            .contains(wgpu::Features::TEXTURE_COMPRESSION_BC)
-        {
-            panic!("BCn texture compression not supported by GPU");
-        } else {
-            required_features.set(wgpu::Features::TEXTURE_COMPRESSION_BC, true);
-        }
+        surface.configure(&device, &config);
 
-        if !adapter
-            .features()
-            .contains(wgpu::Features::INDIRECT_FIRST_INSTANCE)
-        {
-            panic!("indirect first instance feature not supported by GPU");
-        } else {
-            required_features.set(wgpu::Features::INDIRECT_FIRST_INSTANCE, true);
+        Self {
+            target: Target::Surface(surface),
+            adapter,
+            device,
+            queue,
+            config,
+            texture_binding_arrays,
         }
+    }
 
-        if !adapter
-            .features()
-            .contains(wgpu::Features::MULTI_DRAW_INDIRECT)
+    /// Creates a GPU rendering into an offscreen texture instead of a window surface. Falls back to
+    /// a software adapter when there is no hardware one, so that frames can be rendered without a
+    /// GPU or a display. `force_fallback_adapter` always picks the software adapter, whose frames
+    /// do not depend on the GPU of the machine.
+    pub async fn new_headless(
+        width: u32,
+        height: u32,
+        force_fallback_adapter: bool,
+    ) -> Result<Self, GpuError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let adapter_options = |force_fallback_adapter| wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+            force_fallback_adapter,
+        };
+        let adapter = match instance
+            .request_adapter(&adapter_options(force_fallback_adapter))
+            .await
         {
-            panic!("indirect first instance feature not supported by GPU");
-        } else {
-            required_features.set(wgpu::Features::MULTI_DRAW_INDIRECT, true);
-        }
+            Some(adapter) => adapter,
+            None => instance
+                .request_adapter(&adapter_options(true))
+                .await
+                .ok_or(GpuError::AdapterNotFound)?,
+        };
 
-        if !adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::VERTEX_AND_INSTANCE_INDEX_RESPECTS_RESPECTIVE_FIRST_VALUE_IN_INDIRECT_DRAW) {
-            panic!("VERTEX_AND_INSTANCE_INDEX_RESPECTS_RESPECTIVE_FIRST_VALUE_IN_INDIRECT_DRAW not supported by GPU");
-        }
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
 
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features,
-                    ..Default::default()
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let (device, queue, texture_binding_arrays) = request_device(&adapter).await?;
 
-        surface.configure(&device, &config);
+        let offscreen_texture = create_offscreen_texture(&device, &config);
 
-        Self {
-            instance,
-            surface,
+        Ok(Self {
+            target: Target::Offscreen(offscreen_texture),
             adapter,
             device,
             queue,
             config,
-        }
+            texture_binding_arrays,
+        })
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
         self.config.width = width;
         self.config.height = height;
-        self.surface.configure(&self.device, &self.config);
+        match &mut self.target {
+            Target::Surface(surface) => surface.configure(&self.device, &self.config),
+            Target::Offscreen(texture) => {
+                *texture = create_offscreen_texture(&self.device, &self.config);
+            }
+        }
+    }
+
+    pub fn current_frame(&self) -> Frame {
+        match &self.target {
+            Target::Surface(surface) => {
+                let surface_texture = surface.get_current_texture().unwrap();
+                let view = surface_texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                Frame {
+                    surface_texture: Some(surface_texture),
+                    view,
+                }
+            }
+            Target::Offscreen(texture) => Frame {
+                surface_texture: None,
+                view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            },
+        }
+    }
+
+    /// Reads the last rendered frame back to the CPU. Only offscreen targets can be captured.
+    pub fn capture_frame(&self) -> Option<super::Capture> {
+        match &self.target {
+            Target::Surface(_) => None,
+            Target::Offscreen(texture) => Some(super::capture::read_texture(
+                &self.device,
+                &self.queue,
+                texture,
+            )),
+        }
     }
 }
 
+/// Requests a device, with multi draw indirect and binding arrays of textures when the adapter
+/// supports them.
+async fn request_device(
+    adapter: &wgpu::Adapter,
+) -> Result<(wgpu::Device, wgpu::Queue, bool), GpuError> {
+    let required_features =
+        wgpu::Features::TEXTURE_COMPRESSION_BC | wgpu::Features::INDIRECT_FIRST_INSTANCE;
+
+    let missing_features = required_features - adapter.features();
+    if !missing_features.is_empty() {
+        return Err(GpuError::FeatureNotSupported(missing_features));
+    }
+
+    let required_downlevel_flags = wgpu::DownlevelFlags::VERTEX_AND_INSTANCE_INDEX_RESPECTS_RESPECTIVE_FIRST_VALUE_IN_INDIRECT_DRAW
+        | wgpu::DownlevelFlags::CUBE_ARRAY_TEXTURES;
+    if !adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(required_downlevel_flags)
+    {
+        return Err(GpuError::DownlevelFlagsNotSupported(
+            required_downlevel_flags,
+        ));
+    }
+
+    // Software adapters may lack multi draw indirect, the draw commands are then issued one by one.
+    let required_features =
+        required_features | (adapter.features() & wgpu::Features::MULTI_DRAW_INDIRECT);
+
+    // Every texture of a binding array counts as a sampled texture.
+    let texture_binding_arrays = adapter.features().contains(TEXTURE_BINDING_ARRAY_FEATURES);
+    let (required_features, required_limits) = if texture_binding_arrays {
+        (
+            required_features | TEXTURE_BINDING_ARRAY_FEATURES,
+            wgpu::Limits {
+                max_sampled_textures_per_shader_stage: adapter
+                    .limits()
+                    .max_sampled_textures_per_shader_stage,
+                ..Default::default()
+            },
+        )
+    } else {
+        (required_features, wgpu::Limits::default())
+    };
+
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                required_features,
+                required_limits,
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .map_err(GpuError::RequestDeviceError)?;
+
+    Ok((device, queue, texture_binding_arrays))
+}
+
+fn create_offscreen_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("offscreen_texture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    })
+}
+
 pub fn create_depth_buffer(
     device: &wgpu::Device,
     width: u32,
@@ -140,7 +323,6 @@ pub fn create_msaa_buffer(
     device: &wgpu::Device,
     width: u32,
     height: u32,
-    texture_format: wgpu::TextureFormat,
     msaa_sample_count: u32,
 ) -> wgpu::TextureView {
     let msaa_texture = device.create_texture(&wgpu::TextureDescriptor {
@@ -153,10 +335,30 @@ pub fn create_msaa_buffer(
         mip_level_count: 1,
         sample_count: msaa_sample_count,
         dimension: wgpu::TextureDimension::D2,
-        format: texture_format,
+        format: HDR_TEXTURE_FORMAT,
         usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
         view_formats: &[],
     });
 
     msaa_texture.create_view(&wgpu::TextureViewDescriptor::default())
 }
+
+/// Creates the single sampled target the MSAA buffer resolves into, read by the tonemapping pass.
+pub fn create_hdr_buffer(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+    let hdr_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("hdr_texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HDR_TEXTURE_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+
+    hdr_texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
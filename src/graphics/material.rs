@@ -12,59 +12,68 @@ bitflags::bitflags! {
         const OCCLUSION_FLAG = 1 << 3;
         const ROUGHNESS_FLAG = 1 << 4;
         const METALLIC_FLAG = 1 << 5;
+        const ALPHA_MASK_FLAG = 1 << 6;
+        const ALPHA_BLEND_FLAG = 1 << 7;
+        const DOUBLE_SIDED_FLAG = 1 << 8;
+        const EMISSIVE_FLAG = 1 << 9;
     }
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable, Debug)]
 pub struct Material {
-    pub base_color_rgba: [f32; 4],
-    pub normal_solid: [f32; 3],
+    pub base_color_factor: [f32; 4], // The solid color when there is no texture.
+    pub emissive_factor: [f32; 3],   // The solid color when there is no texture.
+    pub normal_scale: f32,
     pub base_color_texture: TextureReference,
     pub normal_texture: TextureReference,
-    pub occlusion_solid: f32,
+    pub occlusion_strength: f32, // The solid occlusion when there is no texture.
     pub occlusion_texture: TextureReference,
     pub occlusion_texture_channel: u32,
-    pub roughness_solid: f32,
+    pub roughness_factor: f32,
     pub roughness_texture: TextureReference,
     pub roughness_texture_channel: u32,
-    pub metallic_solid: f32,
+    pub metallic_factor: f32,
     pub metallic_texture: TextureReference,
     pub metallic_texture_channel: u32,
     pub bitmask: MaterialBitmask,
+    pub emissive_texture: TextureReference,
+    pub alpha_cutoff: f32,
 }
 
 impl From<asset::Material> for Material {
     fn from(material: asset::Material) -> Self {
-        let (base_color_flag, base_color_rgba, base_color_texture) = match material.base_color {
+        let (base_color_flag, base_color_factor, base_color_texture) = match material.base_color {
             asset::BaseColor::Solid { r, g, b, a } => (
                 MaterialBitmask::CLEAR,
                 [*r, *g, *b, *a],
                 TextureReference::default(),
             ),
-            asset::BaseColor::Texture(texture_reference) => (
+            asset::BaseColor::Texture {
+                texture_reference,
+                factor,
+            } => (
                 MaterialBitmask::BASE_COLOR_FLAG,
-                <[f32; 4]>::default(),
+                factor.map(|factor| *factor),
                 texture_reference.into(),
             ),
         };
 
-        let (normal_flag, normal_solid, normal_texture) =
-            if let Some(normal_texture) = material.normal {
-                (
-                    MaterialBitmask::NORMAL_FLAG,
-                    <[f32; 3]>::default(),
-                    normal_texture.into(),
-                )
-            } else {
-                (
-                    MaterialBitmask::CLEAR,
-                    [0.5, 0.5, 1.0],
-                    TextureReference::default(),
-                )
-            };
+        let (normal_flag, normal_scale, normal_texture) = if let Some(normal) = material.normal {
+            (
+                MaterialBitmask::NORMAL_FLAG,
+                *normal.scale,
+                normal.texture_reference.into(),
+            )
+        } else {
+            (
+                MaterialBitmask::CLEAR,
+                f32::default(),
+                TextureReference::default(),
+            )
+        };
 
-        let (occlusion_flag, occlusion_solid, occlusion_texture, occlusion_texture_channel) =
+        let (occlusion_flag, occlusion_strength, occlusion_texture, occlusion_texture_channel) =
             match material.occlusion {
                 asset::Occlusion::Solid(occlusion_solid) => (
                     MaterialBitmask::CLEAR,
@@ -75,15 +84,16 @@ impl From<asset::Material> for Material {
                 asset::Occlusion::Texture {
                     texture_reference,
                     channel,
+                    strength,
                 } => (
                     MaterialBitmask::OCCLUSION_FLAG,
-                    f32::default(),
+                    *strength,
                     texture_reference.into(),
                     channel,
                 ),
             };
 
-        let (roughness_flag, roughness_solid, roughness_texture, roughness_texture_channel) =
+        let (roughness_flag, roughness_factor, roughness_texture, roughness_texture_channel) =
             match material.roughness {
                 asset::Roughness::Solid(roughness_solid) => (
                     MaterialBitmask::CLEAR,
@@ -94,15 +104,16 @@ impl From<asset::Material> for Material {
                 asset::Roughness::Texture {
                     texture_reference,
                     channel,
+                    factor,
                 } => (
                     MaterialBitmask::ROUGHNESS_FLAG,
-                    f32::default(),
+                    *factor,
                     texture_reference.into(),
                     channel,
                 ),
             };
 
-        let (metallic_flag, metallic_solid, metallic_texture, metallic_texture_channel) =
+        let (metallic_flag, metallic_factor, metallic_texture, metallic_texture_channel) =
             match material.metallic {
                 asset::Metallic::Solid(metallic_solid) => (
                     MaterialBitmask::CLEAR,
@@ -113,42 +124,94 @@ impl From<asset::Material> for Material {
                 asset::Metallic::Texture {
                     texture_reference,
                     channel,
+                    factor,
                 } => (
                     MaterialBitmask::METALLIC_FLAG,
-                    f32::default(),
+                    *factor,
                     texture_reference.into(),
                     channel,
                 ),
             };
 
+        let (emissive_flag, emissive_factor, emissive_texture) = match material.emissive {
+            asset::Emissive::Solid { r, g, b } => (
+                MaterialBitmask::CLEAR,
+                [*r, *g, *b],
+                TextureReference::default(),
+            ),
+            asset::Emissive::Texture {
+                texture_reference,
+                factor,
+            } => (
+                MaterialBitmask::EMISSIVE_FLAG,
+                factor.map(|factor| *factor),
+                texture_reference.into(),
+            ),
+        };
+
+        let (alpha_flag, alpha_cutoff) = match material.alpha_mode {
+            asset::AlphaMode::Opaque => (MaterialBitmask::CLEAR, f32::default()),
+            asset::AlphaMode::Mask { cutoff } => (MaterialBitmask::ALPHA_MASK_FLAG, *cutoff),
+            asset::AlphaMode::Blend => (MaterialBitmask::ALPHA_BLEND_FLAG, f32::default()),
+        };
+
+        let double_sided_flag = if material.double_sided {
+            MaterialBitmask::DOUBLE_SIDED_FLAG
+        } else {
+            MaterialBitmask::CLEAR
+        };
+
         Self {
             bitmask: base_color_flag
                 | normal_flag
                 | occlusion_flag
                 | roughness_flag
-                | metallic_flag,
-            base_color_rgba,
+                | metallic_flag
+                | emissive_flag
+                | alpha_flag
+                | double_sided_flag,
+            base_color_factor,
             base_color_texture,
-            normal_solid,
+            normal_scale,
             normal_texture,
-            occlusion_solid,
+            occlusion_strength,
             occlusion_texture,
             occlusion_texture_channel,
-            roughness_solid,
+            roughness_factor,
             roughness_texture,
             roughness_texture_channel,
-            metallic_solid,
+            metallic_factor,
             metallic_texture,
             metallic_texture_channel,
+            emissive_texture,
+            emissive_factor,
+            alpha_cutoff,
         }
     }
 }
 
+impl Material {
+    pub fn is_alpha_blended(&self) -> bool {
+        self.bitmask.contains(MaterialBitmask::ALPHA_BLEND_FLAG)
+    }
+
+    pub fn is_alpha_masked(&self) -> bool {
+        self.bitmask.contains(MaterialBitmask::ALPHA_MASK_FLAG)
+    }
+
+    pub fn is_double_sided(&self) -> bool {
+        self.bitmask.contains(MaterialBitmask::DOUBLE_SIDED_FLAG)
+    }
+}
+
 #[repr(C)]
-#[derive(Copy, Clone, PartialEq, Hash, bytemuck::Pod, bytemuck::Zeroable, Default, Debug)]
+#[derive(Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable, Default, Debug)]
 pub struct TextureReference {
     texture_array_id: u32,
     texture_id: u32,
+    texture_index: u32,
+    tex_coords_set: u32,
+    tex_coords_transform: [f32; 6], // Columns of a 3x2 matrix.
 }
 
 impl From<asset::TextureReference> for TextureReference {
@@ -156,6 +219,9 @@ impl From<asset::TextureReference> for TextureReference {
         Self {
             texture_array_id: texture_reference.texture_array_id,
             texture_id: texture_reference.texture_id,
+            texture_index: texture_reference.texture_index,
+            tex_coords_set: texture_reference.tex_coords_set,
+            tex_coords_transform: texture_reference.transform.matrix().to_cols_array(),
         }
     }
 }
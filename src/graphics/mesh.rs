@@ -2,7 +2,8 @@
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Debug)]
 pub struct Vertex {
     pub position: [f32; 3],
-    pub tex_coords: [f32; 2],
+    pub tex_coords_0: [f32; 2],
+    pub tex_coords_1: [f32; 2], // Typically for lightmaps and occlusion.
     pub normal: [f32; 3],
     pub tangent: [f32; 3],
     pub bitangent: [f32; 3],
@@ -11,14 +12,16 @@ pub struct Vertex {
 impl Vertex {
     pub fn new(
         position: [f32; 3],
-        tex_coords: [f32; 2],
+        tex_coords_0: [f32; 2],
+        tex_coords_1: [f32; 2],
         normal: [f32; 3],
         tangent: [f32; 3],
         bitangent: [f32; 3],
     ) -> Self {
         Self {
             position,
-            tex_coords,
+            tex_coords_0,
+            tex_coords_1,
             normal,
             tangent,
             bitangent,
@@ -31,30 +34,35 @@ impl Vertex {
             step_mode: wgpu::VertexStepMode::Vertex,
             attributes: &[
                 wgpu::VertexAttribute {
-                    offset: 0,
+                    offset: std::mem::offset_of!(Self, position) as wgpu::BufferAddress,
                     shader_location: 0,
                     format: wgpu::VertexFormat::Float32x3,
                 },
                 wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    offset: std::mem::offset_of!(Self, tex_coords_0) as wgpu::BufferAddress,
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x2,
                 },
                 wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+                    offset: std::mem::offset_of!(Self, tex_coords_1) as wgpu::BufferAddress,
                     shader_location: 2,
-                    format: wgpu::VertexFormat::Float32x3,
+                    format: wgpu::VertexFormat::Float32x2,
                 },
                 wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    offset: std::mem::offset_of!(Self, normal) as wgpu::BufferAddress,
                     shader_location: 3,
                     format: wgpu::VertexFormat::Float32x3,
                 },
                 wgpu::VertexAttribute {
-                    offset: std::mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+                    offset: std::mem::offset_of!(Self, tangent) as wgpu::BufferAddress,
                     shader_location: 4,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::offset_of!(Self, bitangent) as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
             ],
         }
     }
@@ -155,7 +163,8 @@ pub mod primitive {
 
                 Vertex {
                     position: position.into(),
-                    tex_coords: [azimuthal_angle, polar_angle],
+                    tex_coords_0: [azimuthal_angle, polar_angle],
+                    tex_coords_1: [azimuthal_angle, polar_angle],
                     normal: normal.into(),
                     tangent: tangent.into(),
                     bitangent: bitangent.into(),
@@ -1,3 +1,6 @@
+pub mod capture;
+pub use capture::Capture;
+
 pub mod gpu;
 pub use gpu::Gpu;
 
@@ -7,6 +10,4 @@ pub use material::Material;
 pub mod mesh;
 pub use mesh::{BoundingBox, Mesh, Vertex};
 
-mod texture;
-
 pub mod pipeline;
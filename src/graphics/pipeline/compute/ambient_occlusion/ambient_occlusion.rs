@@ -0,0 +1,275 @@
+use wgpu::util::DeviceExt;
+
+pub const AMBIENT_OCCLUSION_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+pub const AMBIENT_OCCLUSION_RADIUS: f32 = 0.5;
+pub const AMBIENT_OCCLUSION_BIAS: f32 = 0.025;
+pub const AMBIENT_OCCLUSION_POWER: f32 = 1.5;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Screen-space ambient occlusion computed from the depth buffer, then blurred.
+pub struct AmbientOcclusion {
+    compute_pipeline: wgpu::ComputePipeline,
+    compute_pipeline_blur: wgpu::ComputePipeline,
+    bind_group_layout_ambient_occlusion: wgpu::BindGroupLayout,
+    bind_group_layout_ambient_occlusion_blur: wgpu::BindGroupLayout,
+}
+
+impl AmbientOcclusion {
+    pub fn new(device: &wgpu::Device, msaa_sample_count: u32) -> Self {
+        let multisampled = msaa_sample_count > 1;
+
+        let depth_buffer_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled,
+            },
+            count: None,
+        };
+        let settings_entry = wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let ambient_occlusion_entry = wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: AMBIENT_OCCLUSION_TEXTURE_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+
+        let bind_group_layout_ambient_occlusion =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout_ambient_occlusion"),
+                entries: &[depth_buffer_entry, settings_entry, ambient_occlusion_entry],
+            });
+
+        let bind_group_layout_ambient_occlusion_blur =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout_ambient_occlusion_blur"),
+                entries: &[
+                    depth_buffer_entry,
+                    settings_entry,
+                    ambient_occlusion_entry,
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let source = include_str!("ambient_occlusion.wgsl");
+        let source = if multisampled {
+            source.replace(
+                "var depth_buffer: texture_2d<f32>;",
+                "var depth_buffer: texture_multisampled_2d<f32>;",
+            )
+        } else {
+            source.to_string()
+        };
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader_ambient_occlusion"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("pipeline_layout_ambient_occlusion"),
+            bind_group_layouts: &[&bind_group_layout_ambient_occlusion],
+            push_constant_ranges: &[],
+        });
+
+        let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("compute_pipeline_ambient_occlusion"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let pipeline_layout_blur = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("pipeline_layout_ambient_occlusion_blur"),
+            bind_group_layouts: &[&bind_group_layout_ambient_occlusion_blur],
+            push_constant_ranges: &[],
+        });
+
+        let compute_pipeline_blur =
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("compute_pipeline_ambient_occlusion_blur"),
+                layout: Some(&pipeline_layout_blur),
+                module: &shader,
+                entry_point: Some("cs_blur"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            });
+
+        Self {
+            compute_pipeline,
+            compute_pipeline_blur,
+            bind_group_layout_ambient_occlusion,
+            bind_group_layout_ambient_occlusion_blur,
+        }
+    }
+
+    pub fn prepare(
+        &self,
+        compute_pass: &mut wgpu::ComputePass,
+        bind_group_ambient_occlusion: &wgpu::BindGroup,
+    ) {
+        compute_pass.set_pipeline(&self.compute_pipeline);
+        compute_pass.set_bind_group(0, bind_group_ambient_occlusion, &[]);
+    }
+
+    pub fn prepare_blur(
+        &self,
+        compute_pass: &mut wgpu::ComputePass,
+        bind_group_ambient_occlusion_blur: &wgpu::BindGroup,
+    ) {
+        compute_pass.set_pipeline(&self.compute_pipeline_blur);
+        compute_pass.set_bind_group(0, bind_group_ambient_occlusion_blur, &[]);
+    }
+
+    /// Dispatches one invocation per pixel of the target.
+    pub fn dispatch(compute_pass: &mut wgpu::ComputePass, width: u32, height: u32) {
+        compute_pass.dispatch_workgroups(
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            1,
+        );
+    }
+
+    pub fn create_bind_group_ambient_occlusion(
+        &self,
+        device: &wgpu::Device,
+        binding_resource_depth_buffer: wgpu::BindingResource,
+        binding_resource_ambient_occlusion_buffer: wgpu::BindingResource,
+        binding_resource_ambient_occlusion_texture: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group_ambient_occlusion"),
+            layout: &self.bind_group_layout_ambient_occlusion,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: binding_resource_depth_buffer,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: binding_resource_ambient_occlusion_buffer,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: binding_resource_ambient_occlusion_texture,
+                },
+            ],
+        })
+    }
+
+    pub fn create_bind_group_ambient_occlusion_blur(
+        &self,
+        device: &wgpu::Device,
+        binding_resource_depth_buffer: wgpu::BindingResource,
+        binding_resource_ambient_occlusion_buffer: wgpu::BindingResource,
+        binding_resource_ambient_occlusion_texture: wgpu::BindingResource,
+        binding_resource_raw_ambient_occlusion_texture: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group_ambient_occlusion_blur"),
+            layout: &self.bind_group_layout_ambient_occlusion_blur,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: binding_resource_depth_buffer,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: binding_resource_ambient_occlusion_buffer,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: binding_resource_ambient_occlusion_texture,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: binding_resource_raw_ambient_occlusion_texture,
+                },
+            ],
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct AmbientOcclusionSettings {
+    pub projection: [[f32; 4]; 4],
+    pub inverse_projection: [[f32; 4]; 4],
+    pub radius: f32, // In view space units.
+    pub bias: f32,
+    pub power: f32,
+    pub _padding: f32,
+}
+
+impl AmbientOcclusionSettings {
+    pub fn new(projection: glam::Mat4) -> Self {
+        Self {
+            projection: projection.to_cols_array_2d(),
+            inverse_projection: projection.inverse().to_cols_array_2d(),
+            radius: AMBIENT_OCCLUSION_RADIUS,
+            bias: AMBIENT_OCCLUSION_BIAS,
+            power: AMBIENT_OCCLUSION_POWER,
+            _padding: 0.0,
+        }
+    }
+}
+
+pub fn create_ambient_occlusion_buffer(
+    device: &wgpu::Device,
+    ambient_occlusion_settings: &AmbientOcclusionSettings,
+) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("ambient_occlusion_buffer"),
+        contents: bytemuck::cast_slice(&[*ambient_occlusion_settings]),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+pub fn create_ambient_occlusion_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("ambient_occlusion_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: AMBIENT_OCCLUSION_TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
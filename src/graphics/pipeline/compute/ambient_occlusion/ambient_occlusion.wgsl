@@ -0,0 +1,148 @@
+struct AmbientOcclusionSettings {
+  projection: mat4x4<f32>,
+  inverse_projection: mat4x4<f32>,
+  radius: f32, // View space radius of the sampled hemisphere.
+  bias: f32,
+  power: f32,
+}
+
+// Replaced with `texture_multisampled_2d<f32>` when the depth buffer is multisampled, in which case
+// the first sample is read. Depth is bound as an unfilterable float texture rather than a depth
+// texture, which OpenGL cannot load from.
+@group(0) @binding(0)
+var depth_buffer: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> settings: AmbientOcclusionSettings;
+@group(0) @binding(2)
+var ambient_occlusion: texture_storage_2d<r32float, write>;
+@group(0) @binding(3)
+var raw_ambient_occlusion: texture_2d<f32>;
+
+const PI: f32 = 3.14159265359;
+const GOLDEN_ANGLE: f32 = 2.39996322973;
+const SAMPLE_COUNT: u32 = 16u;
+const BLUR_RADIUS: i32 = 2;
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+  let size = textureDimensions(depth_buffer);
+  if any(global_invocation_id.xy >= size) {
+    return;
+  }
+
+  let pixel = vec2<i32>(global_invocation_id.xy);
+  let depth = load_depth(pixel);
+
+  // Nothing was drawn at the far plane.
+  if depth >= 1.0 {
+    textureStore(ambient_occlusion, pixel, vec4<f32>(1.0));
+    return;
+  }
+
+  let position = view_position(pixel, depth);
+  let tbn = tangent_frame(reconstruct_normal(pixel, position));
+
+  // Rotates the sample pattern per pixel, turning banding into noise that the blur removes.
+  let rotation = 2.0 * PI * interleaved_gradient_noise(vec2<f32>(global_invocation_id.xy));
+
+  var occlusion = 0.0;
+  for (var i: u32 = 0u; i < SAMPLE_COUNT; i++) {
+    // Cosine weighted directions spiralling around the normal, spread more densely close to the
+    // surface.
+    let phi = f32(i) * GOLDEN_ANGLE + rotation;
+    let cos_theta = sqrt(1.0 - (f32(i) + 0.5) / f32(SAMPLE_COUNT));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    let scale = (f32(i) + 1.0) / f32(SAMPLE_COUNT);
+    let tangent_direction = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+    let sample_position = position
+      + tbn * tangent_direction * settings.radius * mix(0.1, 1.0, scale * scale);
+
+    let sample_clip_position = settings.projection * vec4<f32>(sample_position, 1.0);
+    let sample_uv = sample_clip_position.xy / sample_clip_position.w * vec2<f32>(0.5, -0.5) + 0.5;
+    if any(sample_uv < vec2<f32>(0.0)) || any(sample_uv >= vec2<f32>(1.0)) {
+      continue;
+    }
+
+    let sample_pixel = vec2<i32>(sample_uv * vec2<f32>(size));
+    let scene_z = view_position(sample_pixel, load_depth(sample_pixel)).z;
+
+    // Surfaces far in front of the sample, outside of the hemisphere, do not occlude it.
+    let range_check = smoothstep(0.0, 1.0, settings.radius / abs(position.z - scene_z));
+    occlusion += select(0.0, range_check, scene_z >= sample_position.z + settings.bias);
+  }
+
+  let visibility = pow(1.0 - occlusion / f32(SAMPLE_COUNT), settings.power);
+  textureStore(ambient_occlusion, pixel, vec4<f32>(visibility));
+}
+
+// Averages the neighbourhood of each pixel, ignoring neighbours at a different depth so that the
+// occlusion does not bleed across edges.
+@compute @workgroup_size(8, 8, 1)
+fn cs_blur(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+  let size = textureDimensions(depth_buffer);
+  if any(global_invocation_id.xy >= size) {
+    return;
+  }
+
+  let pixel = vec2<i32>(global_invocation_id.xy);
+  let center_z = view_position(pixel, load_depth(pixel)).z;
+
+  var visibility = 0.0;
+  var weight_sum = 0.0;
+  for (var y = -BLUR_RADIUS; y <= BLUR_RADIUS; y++) {
+    for (var x = -BLUR_RADIUS; x <= BLUR_RADIUS; x++) {
+      let sample_pixel = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), vec2<i32>(size) - 1);
+      let sample_z = view_position(sample_pixel, load_depth(sample_pixel)).z;
+      let weight = saturate(1.0 - abs(sample_z - center_z) / settings.radius);
+      visibility += textureLoad(raw_ambient_occlusion, sample_pixel, 0).r * weight;
+      weight_sum += weight;
+    }
+  }
+
+  // The center always has full weight.
+  textureStore(ambient_occlusion, pixel, vec4<f32>(visibility / weight_sum));
+}
+
+fn load_depth(pixel: vec2<i32>) -> f32 {
+  return textureLoad(depth_buffer, pixel, 0).r;
+}
+
+fn view_position(pixel: vec2<i32>, depth: f32) -> vec3<f32> {
+  let uv = (vec2<f32>(pixel) + 0.5) / vec2<f32>(textureDimensions(depth_buffer));
+  let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+  let position = settings.inverse_projection * ndc;
+  return position.xyz / position.w;
+}
+
+// Builds the normal from the neighbours on each axis closest in depth, so that it does not bend
+// around edges.
+fn reconstruct_normal(pixel: vec2<i32>, position: vec3<f32>) -> vec3<f32> {
+  let max_pixel = vec2<i32>(textureDimensions(depth_buffer)) - 1;
+  let left_pixel = max(pixel - vec2<i32>(1, 0), vec2<i32>(0));
+  let right_pixel = min(pixel + vec2<i32>(1, 0), max_pixel);
+  let up_pixel = max(pixel - vec2<i32>(0, 1), vec2<i32>(0));
+  let down_pixel = min(pixel + vec2<i32>(0, 1), max_pixel);
+
+  let left = view_position(left_pixel, load_depth(left_pixel));
+  let right = view_position(right_pixel, load_depth(right_pixel));
+  let up = view_position(up_pixel, load_depth(up_pixel));
+  let down = view_position(down_pixel, load_depth(down_pixel));
+
+  let horizontal = select(right - position, position - left, abs(left.z - position.z) < abs(right.z - position.z));
+  let vertical = select(down - position, position - up, abs(up.z - position.z) < abs(down.z - position.z));
+
+  // Pixel rows go down the screen, while the view space y axis goes up.
+  return normalize(cross(vertical, horizontal));
+}
+
+fn tangent_frame(normal: vec3<f32>) -> mat3x3<f32> {
+  let up = select(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(1.0, 0.0, 0.0), abs(normal.z) > 0.999);
+  let tangent = normalize(cross(up, normal));
+  let bitangent = cross(normal, tangent);
+  return mat3x3<f32>(tangent, bitangent, normal);
+}
+
+// Jimenez, "Next Generation Post Processing in Call of Duty: Advanced Warfare".
+fn interleaved_gradient_noise(position: vec2<f32>) -> f32 {
+  return fract(52.9829189 * fract(dot(position, vec2<f32>(0.06711056, 0.00583715))));
+}
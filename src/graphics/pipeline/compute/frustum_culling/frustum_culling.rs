@@ -72,6 +72,16 @@ impl FrustumCulling {
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
@@ -110,6 +120,7 @@ impl FrustumCulling {
         compute_pass.set_bind_group(0, bind_group_frustum_culling, &[]);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_bind_group_frustum_culling(
         &self,
         device: &wgpu::Device,
@@ -119,6 +130,7 @@ impl FrustumCulling {
         binding_resource_indirect_instances_buffer: wgpu::BindingResource,
         binding_resource_frustum_buffer: wgpu::BindingResource,
         binding_resource_instance_count_buffer: wgpu::BindingResource,
+        binding_resource_instance_transforms_buffer: wgpu::BindingResource,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("bind_group_frustum_culling"),
@@ -148,6 +160,10 @@ impl FrustumCulling {
                     binding: 5,
                     resource: binding_resource_instance_count_buffer,
                 },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: binding_resource_instance_transforms_buffer,
+                },
             ],
         })
     }
@@ -155,23 +171,42 @@ impl FrustumCulling {
 
 pub fn create_instance_culling_information_buffer(
     device: &wgpu::Device,
-    instance_culling_information: &[InstanceCullingInformation],
+    instance_capacity: usize,
 ) -> wgpu::Buffer {
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("instance_culling_information_buffer"),
-        contents: bytemuck::cast_slice(&instance_culling_information),
+        size: (instance_capacity * std::mem::size_of::<InstanceCullingInformation>())
+            as wgpu::BufferAddress,
         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
     })
 }
 
 pub fn create_indirect_instances_buffer(
     device: &wgpu::Device,
-    indirect_instances: &[u32],
+    instance_capacity: usize,
 ) -> wgpu::Buffer {
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("indirect_instances_buffer"),
-        contents: bytemuck::cast_slice(indirect_instances),
+        size: (instance_capacity * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+pub fn create_indirect_draw_commands_buffer(
+    device: &wgpu::Device,
+    indirect_draw_command_count: usize,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("indirect_draw_commands_buffer"),
+        size: (indirect_draw_command_count.max(1)
+            * std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>())
+            as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_DST
+            | wgpu::BufferUsages::INDIRECT,
+        mapped_at_creation: false,
     })
 }
 
@@ -198,4 +233,5 @@ pub fn create_instance_count_buffer(device: &wgpu::Device, instance_count: u32)
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceCullingInformation {
     pub batch_id: u32,
+    pub mesh_id: u32,
 }
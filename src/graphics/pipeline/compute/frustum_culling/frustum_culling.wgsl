@@ -5,6 +5,14 @@ struct BoundingBox {
 
 struct InstanceCullingInformation {
   batch_id: u32,
+  mesh_id: u32,
+}
+
+struct InstanceTransform {
+  matrix_col_0: vec4<f32>,
+  matrix_col_1: vec4<f32>,
+  matrix_col_2: vec4<f32>,
+  matrix_col_3: vec4<f32>,
 }
 
 struct DrawIndexedIndirectArgs {
@@ -42,6 +50,8 @@ var<storage, read_write> instance_buffer: array<u32>;
 var<uniform> frustum: Frustum;
 @group(0) @binding(5)
 var<uniform> instance_count: u32;
+@group(0) @binding(6)
+var<storage, read> instance_transforms: array<InstanceTransform>;
 
 @compute @workgroup_size(64) fn cs_main (
   @builtin(global_invocation_id) id: vec3<u32>
@@ -54,10 +64,24 @@ var<uniform> instance_count: u32;
 
   let instance = instance_culling_information[instance_id];
 
-  let mesh_id = instance.batch_id;
-  let bounding_box = bounding_boxes[mesh_id];
+  let bounding_box = bounding_boxes[instance.mesh_id];
+
+  let transform = mat4x4<f32>(
+    instance_transforms[instance_id].matrix_col_0,
+    instance_transforms[instance_id].matrix_col_1,
+    instance_transforms[instance_id].matrix_col_2,
+    instance_transforms[instance_id].matrix_col_3,
+  );
 
-  if true || intersects_frustum(bounding_box.min, bounding_box.max) {
+  // Object space AABB to world space AABB, enclosing the transformed box.
+  let center = (transform * vec4<f32>((bounding_box.min + bounding_box.max) * 0.5, 1.0)).xyz;
+  let extents = mat3x3<f32>(
+    abs(transform[0].xyz),
+    abs(transform[1].xyz),
+    abs(transform[2].xyz),
+  ) * ((bounding_box.max - bounding_box.min) * 0.5);
+
+  if intersects_frustum(center - extents, center + extents) {
     let batch_instance_id = atomicAdd(&indirect_draw_commands[instance.batch_id].instance_count, 1u);
     let buffer_instance_id = indirect_draw_commands[instance.batch_id].first_instance + batch_instance_id;
     instance_buffer[buffer_instance_id] = instance_id;
@@ -73,7 +97,7 @@ fn intersects_frustum(bounding_box_min: vec3<f32>, bounding_box_max: vec3<f32>)
     && protrudes_plane(bounding_box_min, bounding_box_max, frustum.near_plane)
     && protrudes_plane(bounding_box_min, bounding_box_max, frustum.far_plane)
     // Extra check to cull large objects outside the frustum but still protruding all frustum planes.
-    && bounding_box_contains_frustum(bounding_box_min, bounding_box_max);
+    && !frustum_outside_bounding_box(bounding_box_min, bounding_box_max);
 }
 
 fn protrudes_plane(bounding_box_min: vec3<f32>, bounding_box_max: vec3<f32>, plane: Plane) -> bool {
@@ -86,16 +110,15 @@ fn protrudes_plane(bounding_box_min: vec3<f32>, bounding_box_max: vec3<f32>, pla
   return dot(plane.normal, p) + plane.distance >= 0.0;
 }
 
-fn bounding_box_contains_frustum(bounding_box_min: vec3<f32>, bounding_box_max: vec3<f32>) -> bool {
+fn frustum_outside_bounding_box(bounding_box_min: vec3<f32>, bounding_box_max: vec3<f32>) -> bool {
+  // Counts, per axis, the frustum corners lying on either side of the bounding box.
+  var below = vec3<u32>(0u);
+  var above = vec3<u32>(0u);
   for (var i = 0; i < 8; i = i + 1) {
     let corner = frustum.corners[i];
-
-    if (corner.x < bounding_box_min.x || corner.x > bounding_box_max.x ||
-        corner.y < bounding_box_min.y || corner.y > bounding_box_max.y ||
-        corner.z < bounding_box_min.z || corner.z > bounding_box_max.z) {
-      return false;
-    }
+    below += vec3<u32>(corner < bounding_box_min);
+    above += vec3<u32>(corner > bounding_box_max);
   }
 
-  return true;
+  return any(below == vec3<u32>(8u)) || any(above == vec3<u32>(8u));
 }
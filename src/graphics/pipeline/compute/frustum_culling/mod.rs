@@ -1,2 +1,6 @@
+#[allow(
+    clippy::module_inception,
+    reason = "each pipeline lives in a file named after its directory"
+)]
 mod frustum_culling;
 pub use frustum_culling::*;
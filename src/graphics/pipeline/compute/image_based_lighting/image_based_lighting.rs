@@ -0,0 +1,364 @@
+use wgpu::util::DeviceExt;
+
+pub const ENVIRONMENT_SIZE: u32 = 256;
+pub const IRRADIANCE_SIZE: u32 = 32;
+pub const PREFILTERED_SPECULAR_SIZE: u32 = 128;
+pub const PREFILTERED_SPECULAR_MIP_LEVEL_COUNT: u32 = 5;
+pub const BRDF_LUT_SIZE: u32 = 256;
+
+const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const WORKGROUP_SIZE: u32 = 8;
+
+pub struct ImageBasedLighting {
+    compute_pipeline_downsample: wgpu::ComputePipeline,
+    compute_pipeline_irradiance: wgpu::ComputePipeline,
+    compute_pipeline_prefilter: wgpu::ComputePipeline,
+    compute_pipeline_brdf_lut: wgpu::ComputePipeline,
+    bind_group_layout_image_based_lighting: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+/// Lighting derived from an environment cubemap, sampled by the PBR pipeline.
+pub struct EnvironmentMaps {
+    pub irradiance: wgpu::Texture,
+    pub prefiltered_specular: wgpu::Texture,
+    pub brdf_lut: wgpu::Texture,
+}
+
+impl ImageBasedLighting {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout_image_based_lighting =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout_image_based_lighting"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: TEXTURE_FORMAT,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader_image_based_lighting"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("image_based_lighting.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("pipeline_layout_image_based_lighting"),
+            bind_group_layouts: &[&bind_group_layout_image_based_lighting],
+            push_constant_ranges: &[],
+        });
+
+        let create_compute_pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        };
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sampler_image_based_lighting"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            compute_pipeline_downsample: create_compute_pipeline(
+                "compute_pipeline_descriptor_downsample",
+                "cs_downsample",
+            ),
+            compute_pipeline_irradiance: create_compute_pipeline(
+                "compute_pipeline_descriptor_irradiance",
+                "cs_irradiance",
+            ),
+            compute_pipeline_prefilter: create_compute_pipeline(
+                "compute_pipeline_descriptor_prefilter",
+                "cs_prefilter",
+            ),
+            compute_pipeline_brdf_lut: create_compute_pipeline(
+                "compute_pipeline_descriptor_brdf_lut",
+                "cs_brdf_lut",
+            ),
+            bind_group_layout_image_based_lighting,
+            sampler,
+        }
+    }
+
+    /// Builds the irradiance cubemap, the prefiltered specular mip chain and the BRDF LUT from an
+    /// environment cubemap. The work is submitted immediately.
+    pub fn compute(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        environment_cubemap: &wgpu::TextureView,
+    ) -> EnvironmentMaps {
+        // The environment is first copied into a mip chain, so that the convolutions can read from
+        // lower resolutions instead of undersampling the full resolution cubemap.
+        let environment_mip_level_count = ENVIRONMENT_SIZE.ilog2() + 1;
+        let environment = create_cubemap_texture(
+            device,
+            "texture_environment",
+            ENVIRONMENT_SIZE,
+            environment_mip_level_count,
+        );
+        let irradiance = create_cubemap_texture(device, "texture_irradiance", IRRADIANCE_SIZE, 1);
+        let prefiltered_specular = create_cubemap_texture(
+            device,
+            "texture_prefiltered_specular",
+            PREFILTERED_SPECULAR_SIZE,
+            PREFILTERED_SPECULAR_MIP_LEVEL_COUNT,
+        );
+        let brdf_lut = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("texture_brdf_lut"),
+            size: wgpu::Extent3d {
+                width: BRDF_LUT_SIZE,
+                height: BRDF_LUT_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("image_based_lighting_encoder"),
+        });
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("image_based_lighting_compute_pass"),
+                timestamp_writes: None,
+            });
+
+            for mip_level in 0..environment_mip_level_count {
+                let previous_mip_level_view;
+                let source = if mip_level == 0 {
+                    environment_cubemap
+                } else {
+                    previous_mip_level_view =
+                        create_cubemap_view(&environment, mip_level - 1, Some(1));
+                    &previous_mip_level_view
+                };
+                let bind_group = self.create_bind_group(
+                    device,
+                    source,
+                    &create_storage_view(&environment, mip_level),
+                    0.0,
+                );
+                dispatch(
+                    &mut compute_pass,
+                    &self.compute_pipeline_downsample,
+                    &bind_group,
+                    ENVIRONMENT_SIZE >> mip_level,
+                    6,
+                );
+            }
+
+            let environment_view = create_cubemap_view(&environment, 0, None);
+
+            let bind_group = self.create_bind_group(
+                device,
+                &environment_view,
+                &create_storage_view(&irradiance, 0),
+                0.0,
+            );
+            dispatch(
+                &mut compute_pass,
+                &self.compute_pipeline_irradiance,
+                &bind_group,
+                IRRADIANCE_SIZE,
+                6,
+            );
+
+            for mip_level in 0..PREFILTERED_SPECULAR_MIP_LEVEL_COUNT {
+                let roughness =
+                    mip_level as f32 / (PREFILTERED_SPECULAR_MIP_LEVEL_COUNT - 1) as f32;
+                let bind_group = self.create_bind_group(
+                    device,
+                    &environment_view,
+                    &create_storage_view(&prefiltered_specular, mip_level),
+                    roughness,
+                );
+                dispatch(
+                    &mut compute_pass,
+                    &self.compute_pipeline_prefilter,
+                    &bind_group,
+                    PREFILTERED_SPECULAR_SIZE >> mip_level,
+                    6,
+                );
+            }
+
+            // The BRDF LUT does not depend on the environment, which is bound only to satisfy the
+            // shared bind group layout.
+            let bind_group = self.create_bind_group(
+                device,
+                &environment_view,
+                &create_storage_view(&brdf_lut, 0),
+                0.0,
+            );
+            dispatch(
+                &mut compute_pass,
+                &self.compute_pipeline_brdf_lut,
+                &bind_group,
+                BRDF_LUT_SIZE,
+                1,
+            );
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        EnvironmentMaps {
+            irradiance,
+            prefiltered_specular,
+            brdf_lut,
+        }
+    }
+
+    fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+        destination: &wgpu::TextureView,
+        roughness: f32,
+    ) -> wgpu::BindGroup {
+        let parameters_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("image_based_lighting_parameters_buffer"),
+            contents: bytemuck::cast_slice(&[Parameters {
+                roughness,
+                _padding: [0.0; 3],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group_image_based_lighting"),
+            layout: &self.bind_group_layout_image_based_lighting,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(destination),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: parameters_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
+
+fn dispatch(
+    compute_pass: &mut wgpu::ComputePass,
+    compute_pipeline: &wgpu::ComputePipeline,
+    bind_group: &wgpu::BindGroup,
+    size: u32,
+    layer_count: u32,
+) {
+    compute_pass.set_pipeline(compute_pipeline);
+    compute_pass.set_bind_group(0, bind_group, &[]);
+    compute_pass.dispatch_workgroups(
+        size.max(1).div_ceil(WORKGROUP_SIZE),
+        size.max(1).div_ceil(WORKGROUP_SIZE),
+        layer_count,
+    );
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Parameters {
+    roughness: f32,
+    _padding: [f32; 3],
+}
+
+fn create_cubemap_texture(
+    device: &wgpu::Device,
+    label: &str,
+    size: u32,
+    mip_level_count: u32,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: TEXTURE_FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
+        view_formats: &[],
+    })
+}
+
+pub fn create_cubemap_view(
+    texture: &wgpu::Texture,
+    base_mip_level: u32,
+    mip_level_count: Option<u32>,
+) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        base_mip_level,
+        mip_level_count,
+        ..Default::default()
+    })
+}
+
+fn create_storage_view(texture: &wgpu::Texture, mip_level: u32) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        base_mip_level: mip_level,
+        mip_level_count: Some(1),
+        ..Default::default()
+    })
+}
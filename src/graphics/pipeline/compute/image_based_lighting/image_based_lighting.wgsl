@@ -0,0 +1,207 @@
+struct Parameters {
+  roughness: f32,
+}
+
+@group(0) @binding(0)
+var source: texture_cube<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var destination: texture_storage_2d_array<rgba16float, write>;
+@group(0) @binding(3)
+var<uniform> parameters: Parameters;
+
+const PI: f32 = 3.14159265359;
+
+// The irradiance is integrated from a low resolution mip level of the environment, as it only
+// holds low frequencies.
+const IRRADIANCE_SOURCE_LOD: f32 = 3.0;
+const IRRADIANCE_PHI_SAMPLE_COUNT: u32 = 64u;
+const IRRADIANCE_THETA_SAMPLE_COUNT: u32 = 16u;
+const PREFILTER_SAMPLE_COUNT: u32 = 256u;
+const BRDF_LUT_SAMPLE_COUNT: u32 = 256u;
+
+// Copies the source cubemap into the destination mip level, averaging four bilinear samples per
+// destination texel.
+@compute @workgroup_size(8, 8, 1)
+fn cs_downsample(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+  let size = textureDimensions(destination);
+  if any(global_invocation_id.xy >= size) {
+    return;
+  }
+
+  var color = vec3<f32>(0.0);
+  for (var i: u32 = 0u; i < 4u; i++) {
+    let offset = vec2<f32>(f32(i & 1u), f32(i >> 1u)) * 0.5 + 0.25;
+    let direction = cube_direction(global_invocation_id.z, vec2<f32>(global_invocation_id.xy) + offset, size);
+    color += textureSampleLevel(source, source_sampler, direction, 0.0).rgb;
+  }
+
+  textureStore(destination, global_invocation_id.xy, global_invocation_id.z, vec4<f32>(color / 4.0, 1.0));
+}
+
+// Convolves the environment with a cosine lobe over the hemisphere around each direction.
+@compute @workgroup_size(8, 8, 1)
+fn cs_irradiance(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+  let size = textureDimensions(destination);
+  if any(global_invocation_id.xy >= size) {
+    return;
+  }
+
+  let normal = cube_direction(global_invocation_id.z, vec2<f32>(global_invocation_id.xy) + 0.5, size);
+  let tangent_frame = tangent_frame(normal);
+
+  var irradiance = vec3<f32>(0.0);
+  for (var i: u32 = 0u; i < IRRADIANCE_PHI_SAMPLE_COUNT; i++) {
+    let phi = 2.0 * PI * (f32(i) + 0.5) / f32(IRRADIANCE_PHI_SAMPLE_COUNT);
+    for (var j: u32 = 0u; j < IRRADIANCE_THETA_SAMPLE_COUNT; j++) {
+      let theta = 0.5 * PI * (f32(j) + 0.5) / f32(IRRADIANCE_THETA_SAMPLE_COUNT);
+      let tangent_direction = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+      let direction = tangent_frame * tangent_direction;
+      irradiance += textureSampleLevel(source, source_sampler, direction, IRRADIANCE_SOURCE_LOD).rgb
+        * cos(theta) * sin(theta);
+    }
+  }
+  irradiance = PI * irradiance / f32(IRRADIANCE_PHI_SAMPLE_COUNT * IRRADIANCE_THETA_SAMPLE_COUNT);
+
+  textureStore(destination, global_invocation_id.xy, global_invocation_id.z, vec4<f32>(irradiance, 1.0));
+}
+
+// Convolves the environment with the GGX lobe of the given roughness, assuming that the view
+// direction equals the normal.
+@compute @workgroup_size(8, 8, 1)
+fn cs_prefilter(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+  let size = textureDimensions(destination);
+  if any(global_invocation_id.xy >= size) {
+    return;
+  }
+
+  let normal = cube_direction(global_invocation_id.z, vec2<f32>(global_invocation_id.xy) + 0.5, size);
+  let view_direction = normal;
+  let alpha = parameters.roughness * parameters.roughness;
+
+  let source_size = f32(textureDimensions(source).x);
+  let texel_solid_angle = 4.0 * PI / (6.0 * source_size * source_size);
+
+  var color = vec3<f32>(0.0);
+  var total_weight = 0.0;
+  for (var i: u32 = 0u; i < PREFILTER_SAMPLE_COUNT; i++) {
+    let halfway_vector = importance_sample_ggx(hammersley(i, PREFILTER_SAMPLE_COUNT), normal, alpha);
+    let light_direction = normalize(2.0 * dot(view_direction, halfway_vector) * halfway_vector - view_direction);
+
+    let n_dot_l = dot(normal, light_direction);
+    if n_dot_l > 0.0 {
+      // Samples with a low probability cover a larger solid angle, so they are read from a lower
+      // resolution mip level to avoid aliasing.
+      let n_dot_h = max(dot(normal, halfway_vector), 0.0);
+      let h_dot_v = max(dot(halfway_vector, view_direction), 0.0);
+      let pdf = distribution_ggx(n_dot_h, alpha) * n_dot_h / (4.0 * h_dot_v) + 0.0001;
+      let sample_solid_angle = 1.0 / (f32(PREFILTER_SAMPLE_COUNT) * pdf + 0.0001);
+      let lod = select(0.5 * log2(sample_solid_angle / texel_solid_angle), 0.0, parameters.roughness == 0.0);
+
+      color += textureSampleLevel(source, source_sampler, light_direction, max(lod, 0.0)).rgb * n_dot_l;
+      total_weight += n_dot_l;
+    }
+  }
+
+  textureStore(destination, global_invocation_id.xy, global_invocation_id.z, vec4<f32>(color / total_weight, 1.0));
+}
+
+// Integrates the scale (red) and bias (green) applied to F0 by the split-sum approximation of the
+// specular BRDF, indexed by n_dot_v horizontally and roughness vertically.
+@compute @workgroup_size(8, 8, 1)
+fn cs_brdf_lut(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+  let size = textureDimensions(destination);
+  if any(global_invocation_id.xy >= size) {
+    return;
+  }
+
+  let n_dot_v = (f32(global_invocation_id.x) + 0.5) / f32(size.x);
+  let roughness = (f32(global_invocation_id.y) + 0.5) / f32(size.y);
+  let alpha = roughness * roughness;
+
+  let normal = vec3<f32>(0.0, 0.0, 1.0);
+  let view_direction = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+
+  var scale = 0.0;
+  var bias = 0.0;
+  for (var i: u32 = 0u; i < BRDF_LUT_SAMPLE_COUNT; i++) {
+    let halfway_vector = importance_sample_ggx(hammersley(i, BRDF_LUT_SAMPLE_COUNT), normal, alpha);
+    let light_direction = normalize(2.0 * dot(view_direction, halfway_vector) * halfway_vector - view_direction);
+
+    let n_dot_l = max(light_direction.z, 0.0);
+    let n_dot_h = max(halfway_vector.z, 0.0);
+    let v_dot_h = max(dot(view_direction, halfway_vector), 0.0);
+
+    if n_dot_l > 0.0 {
+      let g = geometry_smith_ibl(n_dot_v, n_dot_l, roughness);
+      let g_visibility = g * v_dot_h / (n_dot_h * n_dot_v);
+      let fresnel = pow(1.0 - v_dot_h, 5.0);
+
+      scale += (1.0 - fresnel) * g_visibility;
+      bias += fresnel * g_visibility;
+    }
+  }
+
+  let brdf = vec2<f32>(scale, bias) / f32(BRDF_LUT_SAMPLE_COUNT);
+  textureStore(destination, global_invocation_id.xy, 0u, vec4<f32>(brdf, 0.0, 1.0));
+}
+
+// Returns the direction through the center of the given texel of a cubemap face.
+fn cube_direction(face: u32, position: vec2<f32>, size: vec2<u32>) -> vec3<f32> {
+  let st = position / vec2<f32>(size) * 2.0 - 1.0;
+
+  switch face {
+    case 0u: {
+      return normalize(vec3<f32>(1.0, -st.y, -st.x));
+    }
+    case 1u: {
+      return normalize(vec3<f32>(-1.0, -st.y, st.x));
+    }
+    case 2u: {
+      return normalize(vec3<f32>(st.x, 1.0, st.y));
+    }
+    case 3u: {
+      return normalize(vec3<f32>(st.x, -1.0, -st.y));
+    }
+    case 4u: {
+      return normalize(vec3<f32>(st.x, -st.y, 1.0));
+    }
+    case 5u, default: {
+      return normalize(vec3<f32>(-st.x, -st.y, -1.0));
+    }
+  }
+}
+
+fn tangent_frame(normal: vec3<f32>) -> mat3x3<f32> {
+  let up = select(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(1.0, 0.0, 0.0), abs(normal.z) > 0.999);
+  let tangent = normalize(cross(up, normal));
+  let bitangent = cross(normal, tangent);
+  return mat3x3<f32>(tangent, bitangent, normal);
+}
+
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+  return vec2<f32>(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+fn importance_sample_ggx(xi: vec2<f32>, normal: vec3<f32>, alpha: f32) -> vec3<f32> {
+  let phi = 2.0 * PI * xi.x;
+  let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+  let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+  let tangent_direction = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+  return normalize(tangent_frame(normal) * tangent_direction);
+}
+
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+  let alpha_squared = max(alpha * alpha, 0.000001);
+  let denominator = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+  return alpha_squared / (PI * denominator * denominator);
+}
+
+// Smith geometry function with the Schlick-GGX approximation, remapped for image-based lighting.
+fn geometry_smith_ibl(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+  let k = roughness * roughness / 2.0;
+  let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+  let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+  return g_v * g_l;
+}
@@ -1,2 +1,8 @@
+pub mod ambient_occlusion;
+pub use ambient_occlusion::AmbientOcclusion;
+
 pub mod frustum_culling;
 pub use frustum_culling::FrustumCulling;
+
+pub mod image_based_lighting;
+pub use image_based_lighting::ImageBasedLighting;
@@ -0,0 +1,190 @@
+use crate::graphics;
+
+pub const BLOOM_MIP_COUNT: u32 = 6;
+
+/// Blurs the HDR frame by downsampling it into the mips of the bloom texture, then upsampling and
+/// accumulating them back into the first mip.
+pub struct Bloom {
+    render_pipeline_downsample_first: wgpu::RenderPipeline,
+    render_pipeline_downsample: wgpu::RenderPipeline,
+    render_pipeline_upsample: wgpu::RenderPipeline,
+    bind_group_layout_bloom: wgpu::BindGroupLayout,
+}
+
+impl Bloom {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout_bloom =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout_bloom"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader_bloom"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("bloom.wgsl").into()),
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("render_pipeline_layout_bloom"),
+                bind_group_layouts: &[&bind_group_layout_bloom],
+                push_constant_ranges: &[],
+            });
+
+        let create_render_pipeline = |label, entry_point, blend| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: graphics::gpu::HDR_TEXTURE_FORMAT,
+                        blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                depth_stencil: None,
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let render_pipeline_downsample_first = create_render_pipeline(
+            "render_pipeline_bloom_downsample_first",
+            "fs_downsample_first",
+            None,
+        );
+        let render_pipeline_downsample =
+            create_render_pipeline("render_pipeline_bloom_downsample", "fs_downsample", None);
+        // Each upsampled mip is added to the downsampled content of the next larger one.
+        let additive_blending = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let render_pipeline_upsample = create_render_pipeline(
+            "render_pipeline_bloom_upsample",
+            "fs_upsample",
+            Some(wgpu::BlendState {
+                color: additive_blending,
+                alpha: additive_blending,
+            }),
+        );
+
+        Self {
+            render_pipeline_downsample_first,
+            render_pipeline_downsample,
+            render_pipeline_upsample,
+            bind_group_layout_bloom,
+        }
+    }
+
+    /// Prepares the downsampling into the bloom mip at `mip_level`. The first mip is read from the
+    /// HDR frame and averaged to suppress fireflies.
+    pub fn prepare_downsample(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        bind_group_bloom: &wgpu::BindGroup,
+        mip_level: u32,
+    ) {
+        if mip_level == 0 {
+            render_pass.set_pipeline(&self.render_pipeline_downsample_first);
+        } else {
+            render_pass.set_pipeline(&self.render_pipeline_downsample);
+        }
+        render_pass.set_bind_group(0, bind_group_bloom, &[]);
+    }
+
+    pub fn prepare_upsample(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        bind_group_bloom: &wgpu::BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.render_pipeline_upsample);
+        render_pass.set_bind_group(0, bind_group_bloom, &[]);
+    }
+
+    /// Draws a single triangle covering the whole target.
+    pub fn draw(render_pass: &mut wgpu::RenderPass) {
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn create_bind_group_bloom(
+        &self,
+        device: &wgpu::Device,
+        binding_resource_source_texture: wgpu::BindingResource,
+        binding_resource_sampler: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group_bloom"),
+            layout: &self.bind_group_layout_bloom,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: binding_resource_source_texture,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: binding_resource_sampler,
+                },
+            ],
+        })
+    }
+}
+
+/// Creates the bloom texture at half the frame size, with one mip per blur level.
+pub fn create_bloom_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+    let width = (width / 2).max(1);
+    let height = (height / 2).max(1);
+    let max_mip_level_count = u32::BITS - width.min(height).leading_zeros();
+
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("bloom_texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: BLOOM_MIP_COUNT.min(max_mip_level_count),
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: graphics::gpu::HDR_TEXTURE_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
@@ -0,0 +1,110 @@
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+  // A triangle covering the whole clip space square.
+  let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+  var vertex_output: VertexOutput;
+  vertex_output.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+  vertex_output.uv = uv;
+  return vertex_output;
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+fn sample_source(uv: vec2<f32>, offset: vec2<f32>) -> vec3<f32> {
+  let texel_size = 1.0 / vec2<f32>(textureDimensions(source_texture));
+  return textureSampleLevel(source_texture, source_sampler, uv + offset * texel_size, 0.0).rgb;
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+  return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Weights each group of samples down by its brightness, so single very bright texels do not
+// flicker.
+fn karis_weight(group: vec3<f32>) -> f32 {
+  return 1.0 / (1.0 + luminance(group));
+}
+
+// 13 bilinear taps forming four overlapping 2x2 boxes around the center, and one more in the
+// middle, as presented by Jimenez in "Next Generation Post Processing in Call of Duty: Advanced
+// Warfare".
+struct DownsampleTaps {
+  a: vec3<f32>, b: vec3<f32>, c: vec3<f32>,
+  d: vec3<f32>, e: vec3<f32>, f: vec3<f32>,
+  g: vec3<f32>, h: vec3<f32>, i: vec3<f32>,
+  j: vec3<f32>, k: vec3<f32>, l: vec3<f32>, m: vec3<f32>,
+};
+
+fn downsample_taps(uv: vec2<f32>) -> DownsampleTaps {
+  var taps: DownsampleTaps;
+  taps.a = sample_source(uv, vec2<f32>(-2.0, -2.0));
+  taps.b = sample_source(uv, vec2<f32>(0.0, -2.0));
+  taps.c = sample_source(uv, vec2<f32>(2.0, -2.0));
+  taps.d = sample_source(uv, vec2<f32>(-2.0, 0.0));
+  taps.e = sample_source(uv, vec2<f32>(0.0, 0.0));
+  taps.f = sample_source(uv, vec2<f32>(2.0, 0.0));
+  taps.g = sample_source(uv, vec2<f32>(-2.0, 2.0));
+  taps.h = sample_source(uv, vec2<f32>(0.0, 2.0));
+  taps.i = sample_source(uv, vec2<f32>(2.0, 2.0));
+  taps.j = sample_source(uv, vec2<f32>(-1.0, -1.0));
+  taps.k = sample_source(uv, vec2<f32>(1.0, -1.0));
+  taps.l = sample_source(uv, vec2<f32>(-1.0, 1.0));
+  taps.m = sample_source(uv, vec2<f32>(1.0, 1.0));
+  return taps;
+}
+
+@fragment
+fn fs_downsample_first(vertex_output: VertexOutput) -> @location(0) vec4<f32> {
+  let t = downsample_taps(vertex_output.uv);
+  var groups = array<vec3<f32>, 5>(
+    (t.j + t.k + t.l + t.m) * 0.25,
+    (t.a + t.b + t.d + t.e) * 0.25,
+    (t.b + t.c + t.e + t.f) * 0.25,
+    (t.d + t.e + t.g + t.h) * 0.25,
+    (t.e + t.f + t.h + t.i) * 0.25,
+  );
+  var group_weights = array<f32, 5>(0.5, 0.125, 0.125, 0.125, 0.125);
+
+  var color = vec3<f32>(0.0);
+  var weight_sum = 0.0;
+  for (var i = 0u; i < 5u; i++) {
+    let weight = group_weights[i] * karis_weight(groups[i]);
+    color += groups[i] * weight;
+    weight_sum += weight;
+  }
+  return vec4<f32>(color / weight_sum, 1.0);
+}
+
+@fragment
+fn fs_downsample(vertex_output: VertexOutput) -> @location(0) vec4<f32> {
+  let t = downsample_taps(vertex_output.uv);
+  let color = t.e * 0.125
+    + (t.a + t.c + t.g + t.i) * 0.03125
+    + (t.b + t.d + t.f + t.h) * 0.0625
+    + (t.j + t.k + t.l + t.m) * 0.125;
+  return vec4<f32>(color, 1.0);
+}
+
+// 3x3 tent filter over the smaller mip.
+@fragment
+fn fs_upsample(vertex_output: VertexOutput) -> @location(0) vec4<f32> {
+  let uv = vertex_output.uv;
+  let color = sample_source(uv, vec2<f32>(0.0, 0.0)) * 4.0
+    + (sample_source(uv, vec2<f32>(0.0, -1.0))
+      + sample_source(uv, vec2<f32>(-1.0, 0.0))
+      + sample_source(uv, vec2<f32>(1.0, 0.0))
+      + sample_source(uv, vec2<f32>(0.0, 1.0))) * 2.0
+    + sample_source(uv, vec2<f32>(-1.0, -1.0))
+    + sample_source(uv, vec2<f32>(1.0, -1.0))
+    + sample_source(uv, vec2<f32>(-1.0, 1.0))
+    + sample_source(uv, vec2<f32>(1.0, 1.0));
+  return vec4<f32>(color / 16.0, 1.0);
+}
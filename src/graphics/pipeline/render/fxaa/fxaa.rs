@@ -0,0 +1,114 @@
+pub struct Fxaa {
+    render_pipeline: wgpu::RenderPipeline,
+    bind_group_layout_fxaa: wgpu::BindGroupLayout,
+}
+
+impl Fxaa {
+    pub fn new(device: &wgpu::Device, texture_format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout_fxaa =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout_fxaa"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader_fxaa"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("fxaa.wgsl").into()),
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("render_pipeline_layout_fxaa"),
+                bind_group_layouts: &[&bind_group_layout_fxaa],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("render_pipeline_fxaa"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: texture_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            depth_stencil: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            render_pipeline,
+            bind_group_layout_fxaa,
+        }
+    }
+
+    pub fn prepare(&self, render_pass: &mut wgpu::RenderPass, bind_group_fxaa: &wgpu::BindGroup) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, bind_group_fxaa, &[]);
+    }
+
+    /// Draws a single triangle covering the whole target.
+    pub fn draw(render_pass: &mut wgpu::RenderPass) {
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn create_bind_group_fxaa(
+        &self,
+        device: &wgpu::Device,
+        binding_resource_ldr_buffer: wgpu::BindingResource,
+        binding_resource_sampler: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group_fxaa"),
+            layout: &self.bind_group_layout_fxaa,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: binding_resource_ldr_buffer,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: binding_resource_sampler,
+                },
+            ],
+        })
+    }
+}
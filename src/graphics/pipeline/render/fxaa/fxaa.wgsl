@@ -0,0 +1,87 @@
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+  // A triangle covering the whole clip space square.
+  let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+  var vertex_output: VertexOutput;
+  vertex_output.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+  vertex_output.uv = uv;
+  return vertex_output;
+}
+
+@group(0) @binding(0)
+var ldr_buffer: texture_2d<f32>;
+@group(0) @binding(1)
+var ldr_sampler: sampler;
+
+const FXAA_REDUCE_MIN: f32 = 1.0 / 128.0;
+const FXAA_REDUCE_MUL: f32 = 1.0 / 8.0;
+const FXAA_SPAN_MAX: f32 = 8.0;
+
+fn sample_ldr(uv: vec2<f32>) -> vec3<f32> {
+  return textureSampleLevel(ldr_buffer, ldr_sampler, uv, 0.0).rgb;
+}
+
+// Edges are detected on perceptual luma. The buffer is sRGB, so samples are linear and the square
+// root approximates the encoding.
+fn luma(color: vec3<f32>) -> f32 {
+  return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+// Blurs along the local edge direction, found from the luma of the four diagonal neighbors.
+@fragment
+fn fs_main(vertex_output: VertexOutput) -> @location(0) vec4<f32> {
+  let uv = vertex_output.uv;
+  let texel_size = 1.0 / vec2<f32>(textureDimensions(ldr_buffer));
+
+  let color_center = textureSampleLevel(ldr_buffer, ldr_sampler, uv, 0.0);
+  let luma_north_west = luma(sample_ldr(uv + vec2<f32>(-1.0, -1.0) * texel_size));
+  let luma_north_east = luma(sample_ldr(uv + vec2<f32>(1.0, -1.0) * texel_size));
+  let luma_south_west = luma(sample_ldr(uv + vec2<f32>(-1.0, 1.0) * texel_size));
+  let luma_south_east = luma(sample_ldr(uv + vec2<f32>(1.0, 1.0) * texel_size));
+  let luma_center = luma(color_center.rgb);
+
+  let luma_min = min(
+    luma_center,
+    min(min(luma_north_west, luma_north_east), min(luma_south_west, luma_south_east)),
+  );
+  let luma_max = max(
+    luma_center,
+    max(max(luma_north_west, luma_north_east), max(luma_south_west, luma_south_east)),
+  );
+
+  var direction = vec2<f32>(
+    (luma_south_west + luma_south_east) - (luma_north_west + luma_north_east),
+    (luma_north_west + luma_south_west) - (luma_north_east + luma_south_east),
+  );
+  let direction_reduce = max(
+    (luma_north_west + luma_north_east + luma_south_west + luma_south_east) * 0.25
+      * FXAA_REDUCE_MUL,
+    FXAA_REDUCE_MIN,
+  );
+  let inverse_direction_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+  direction = clamp(
+    direction * inverse_direction_min,
+    vec2<f32>(-FXAA_SPAN_MAX),
+    vec2<f32>(FXAA_SPAN_MAX),
+  ) * texel_size;
+
+  let color_inner = 0.5 * (
+    sample_ldr(uv + direction * (1.0 / 3.0 - 0.5))
+      + sample_ldr(uv + direction * (2.0 / 3.0 - 0.5))
+  );
+  let color_outer = color_inner * 0.5 + 0.25 * (
+    sample_ldr(uv - direction * 0.5) + sample_ldr(uv + direction * 0.5)
+  );
+
+  // The wider blur is rejected when it reaches past the local luma range, i.e. across another edge.
+  let luma_outer = luma(color_outer);
+  if luma_outer < luma_min || luma_outer > luma_max {
+    return vec4<f32>(color_inner, color_center.a);
+  }
+  return vec4<f32>(color_outer, color_center.a);
+}
@@ -3,3 +3,15 @@ pub use pbr::Pbr;
 
 pub mod skybox;
 pub use skybox::Skybox;
+
+pub mod shadow;
+pub use shadow::Shadow;
+
+pub mod tonemapping;
+pub use tonemapping::Tonemapping;
+
+pub mod bloom;
+pub use bloom::Bloom;
+
+pub mod fxaa;
+pub use fxaa::Fxaa;
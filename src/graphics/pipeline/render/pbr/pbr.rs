@@ -4,9 +4,18 @@ use wgpu::util::DeviceExt;
 
 pub struct Pbr {
     render_pipeline: wgpu::RenderPipeline,
+    render_pipeline_double_sided: wgpu::RenderPipeline,
+    render_pipeline_transparent: wgpu::RenderPipeline,
+    render_pipeline_transparent_double_sided: wgpu::RenderPipeline,
+    render_pipeline_depth_prepass: wgpu::RenderPipeline,
+    render_pipeline_depth_prepass_double_sided: wgpu::RenderPipeline,
+    render_pipeline_shadow_masked: wgpu::RenderPipeline,
     bind_group_layout_variable: wgpu::BindGroupLayout,
+    bind_group_layout_shadow_masked: wgpu::BindGroupLayout,
     bind_group_layout_bindless: wgpu::BindGroupLayout,
     bind_group_layout_lights: wgpu::BindGroupLayout,
+    bind_group_layout_environment: wgpu::BindGroupLayout,
+    texture_binding_array: bool,
 }
 
 impl Pbr {
@@ -14,7 +23,18 @@ impl Pbr {
         device: &wgpu::Device,
         texture_format: wgpu::TextureFormat,
         msaa_sample_count: u32,
-    ) -> Self {
+        texture_bindings: &TextureBindings,
+    ) -> Result<Self, PbrError> {
+        let sampled_texture_count =
+            texture_bindings.texture_count() + LIGHTING_SAMPLED_TEXTURE_COUNT;
+        let max_sampled_texture_count = device.limits().max_sampled_textures_per_shader_stage;
+        if sampled_texture_count > max_sampled_texture_count {
+            return Err(PbrError::TooManySampledTextures {
+                sampled_texture_count,
+                max_sampled_texture_count,
+            });
+        }
+
         let bind_group_layout_variable =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("bind_group_layout_variable"),
@@ -62,15 +82,121 @@ impl Pbr {
                 ],
             });
 
+        // Shaped like the variable bind group, with the shadow pass views bound as the camera one
+        // view at a time.
+        let bind_group_layout_shadow_masked =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout_shadow_masked"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: wgpu::BufferSize::new(
+                                graphics::pipeline::render::shadow::SHADOW_PASS_VIEW_SIZE,
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let bind_group_layout_bindless_entries = [
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ]
+        .into_iter()
+        .chain(match texture_bindings {
+            TextureBindings::BindingArray { texture_count } => {
+                vec![wgpu::BindGroupLayoutEntry {
+                    binding: TEXTURES_FIRST_BINDING,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: Some(*texture_count),
+                }]
+            }
+            TextureBindings::TextureArrays(texture_arrays) => texture_arrays
+                .iter()
+                .zip(TEXTURES_FIRST_BINDING..)
+                .map(|(_, binding)| wgpu::BindGroupLayoutEntry {
+                    binding,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
         let bind_group_layout_bindless =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("bind_group_layout_bindless"),
+                entries: &bind_group_layout_bindless_entries,
+            });
+
+        let bind_group_layout_lights =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout_lights"),
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            ty: wgpu::BufferBindingType::Uniform,
                             has_dynamic_offset: false,
                             min_binding_size: None,
                         },
@@ -79,60 +205,60 @@ impl Pbr {
                     wgpu::BindGroupLayoutEntry {
                         binding: 1,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
-                            multisampled: false,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
                         binding: 2,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
-                            multisampled: false,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
                         binding: 3,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
-                            multisampled: false,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
                         binding: 4,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
-                            multisampled: false,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
                         binding: 5,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
-                            multisampled: false,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
                         binding: 6,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
-                            multisampled: false,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
                         count: None,
                     },
@@ -140,8 +266,8 @@ impl Pbr {
                         binding: 7,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::D2,
                             multisampled: false,
                         },
                         count: None,
@@ -149,99 +275,69 @@ impl Pbr {
                     wgpu::BindGroupLayoutEntry {
                         binding: 8,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
-                            multisampled: false,
-                        },
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
                         binding: 9,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::CubeArray,
                             multisampled: false,
                         },
                         count: None,
                     },
+                ],
+            });
+
+        let bind_group_layout_environment =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout_environment"),
+                entries: &[
                     wgpu::BindGroupLayoutEntry {
-                        binding: 10,
+                        binding: 0,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
                             multisampled: false,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
-                        binding: 11,
+                        binding: 1,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
                             multisampled: false,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
-                        binding: 12,
+                        binding: 2,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Texture {
                             sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            view_dimension: wgpu::TextureViewDimension::D2,
                             multisampled: false,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
-                        binding: 13,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 14,
+                        binding: 3,
                         visibility: wgpu::ShaderStages::FRAGMENT,
                         ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
-                ],
-            });
-
-        let bind_group_layout_lights =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("bind_group_layout_lights"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: true },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
                     wgpu::BindGroupLayoutEntry {
-                        binding: 2,
+                        binding: 4,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
                         },
                         count: None,
                     },
@@ -250,7 +346,7 @@ impl Pbr {
 
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("shader_pbr"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("pbr.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(create_shader_source(texture_bindings).into()),
         });
 
         let render_pipeline_layout =
@@ -260,62 +356,215 @@ impl Pbr {
                     &bind_group_layout_variable,
                     &bind_group_layout_bindless,
                     &bind_group_layout_lights,
+                    &bind_group_layout_environment,
                 ],
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("render_pipeline_pbr"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[graphics::Vertex::buffer_layout()],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: texture_format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            multisample: wgpu::MultisampleState {
-                count: msaa_sample_count,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
+        let render_pipeline_layout_depth_prepass =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("render_pipeline_layout_pbr_depth_prepass"),
+                bind_group_layouts: &[&bind_group_layout_variable, &bind_group_layout_bindless],
+                push_constant_ranges: &[],
+            });
+
+        // The variants differ by their color target, depth state and culling. Double-sided ones
+        // rasterize back faces.
+        let create_render_pipeline = |label: &str,
+                                      layout: &wgpu::PipelineLayout,
+                                      fragment_entry_point: &str,
+                                      color_target: Option<wgpu::ColorTargetState>,
+                                      depth_write_enabled: bool,
+                                      depth_compare: wgpu::CompareFunction,
+                                      double_sided: bool| {
+            let targets = color_target.map(Some).into_iter().collect::<Vec<_>>();
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[graphics::Vertex::buffer_layout()],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(fragment_entry_point),
+                    targets: &targets,
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: if double_sided {
+                        None
+                    } else {
+                        Some(wgpu::Face::Back)
+                    },
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                multisample: wgpu::MultisampleState {
+                    count: msaa_sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let opaque_target = wgpu::ColorTargetState {
+            format: texture_format,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        };
+        let transparent_target = wgpu::ColorTargetState {
+            format: texture_format,
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            write_mask: wgpu::ColorWrites::ALL,
+        };
+
+        // The depth prepass has already written the depth of opaque surfaces, which the main pass
+        // tests against with `LessEqual`.
+        let render_pipeline = create_render_pipeline(
+            "render_pipeline_pbr",
+            &render_pipeline_layout,
+            "fs_main",
+            Some(opaque_target.clone()),
+            true,
+            wgpu::CompareFunction::LessEqual,
+            false,
+        );
+        let render_pipeline_double_sided = create_render_pipeline(
+            "render_pipeline_pbr_double_sided",
+            &render_pipeline_layout,
+            "fs_main",
+            Some(opaque_target),
+            true,
+            wgpu::CompareFunction::LessEqual,
+            true,
+        );
+
+        // Alpha blended surfaces are sorted back to front, and do not hide each other.
+        let render_pipeline_transparent = create_render_pipeline(
+            "render_pipeline_pbr_transparent",
+            &render_pipeline_layout,
+            "fs_main",
+            Some(transparent_target.clone()),
+            false,
+            wgpu::CompareFunction::LessEqual,
+            false,
+        );
+        let render_pipeline_transparent_double_sided = create_render_pipeline(
+            "render_pipeline_pbr_transparent_double_sided",
+            &render_pipeline_layout,
+            "fs_main",
+            Some(transparent_target),
+            false,
+            wgpu::CompareFunction::LessEqual,
+            true,
+        );
+
+        // Writes only the depth of opaque surfaces, which screen-space ambient occlusion reads
+        // before the main pass.
+        let render_pipeline_depth_prepass = create_render_pipeline(
+            "render_pipeline_pbr_depth_prepass",
+            &render_pipeline_layout_depth_prepass,
+            "fs_depth_prepass",
+            None,
+            true,
+            wgpu::CompareFunction::Less,
+            false,
+        );
+        let render_pipeline_depth_prepass_double_sided = create_render_pipeline(
+            "render_pipeline_pbr_depth_prepass_double_sided",
+            &render_pipeline_layout_depth_prepass,
+            "fs_depth_prepass",
+            None,
+            true,
+            wgpu::CompareFunction::Less,
+            true,
+        );
+
+        let render_pipeline_layout_shadow_masked =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("render_pipeline_layout_pbr_shadow_masked"),
+                bind_group_layouts: &[
+                    &bind_group_layout_shadow_masked,
+                    &bind_group_layout_bindless,
+                ],
+                push_constant_ranges: &[],
+            });
 
-        Self {
+        // Draws alpha masked shadow casters into the shadow maps, with the same depth state as the
+        // shadow pipeline drawing the opaque ones.
+        let render_pipeline_shadow_masked =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("render_pipeline_pbr_shadow_masked"),
+                layout: Some(&render_pipeline_layout_shadow_masked),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[graphics::Vertex::buffer_layout()],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_shadow_masked"),
+                    targets: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: graphics::pipeline::render::shadow::SHADOW_DEPTH_BIAS,
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        Ok(Self {
             render_pipeline,
+            render_pipeline_double_sided,
+            render_pipeline_transparent,
+            render_pipeline_transparent_double_sided,
+            render_pipeline_depth_prepass,
+            render_pipeline_depth_prepass_double_sided,
+            render_pipeline_shadow_masked,
             bind_group_layout_variable,
+            bind_group_layout_shadow_masked,
             bind_group_layout_bindless,
             bind_group_layout_lights,
-        }
+            bind_group_layout_environment,
+            texture_binding_array: matches!(texture_bindings, TextureBindings::BindingArray { .. }),
+        })
     }
 
+    /// Sets the opaque single-sided pipeline, see `set_pipeline` to draw other materials.
+    #[allow(clippy::too_many_arguments)]
     pub fn prepare(
         &self,
         render_pass: &mut wgpu::RenderPass,
@@ -324,6 +573,7 @@ impl Pbr {
         bind_group_variable: &wgpu::BindGroup,
         bind_group_bindless: &wgpu::BindGroup,
         bind_group_lights: &wgpu::BindGroup,
+        bind_group_environment: &wgpu::BindGroup,
     ) {
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_vertex_buffer(0, vertex_buffer);
@@ -331,6 +581,74 @@ impl Pbr {
         render_pass.set_bind_group(0, bind_group_variable, &[]);
         render_pass.set_bind_group(1, bind_group_bindless, &[]);
         render_pass.set_bind_group(2, bind_group_lights, &[]);
+        render_pass.set_bind_group(3, bind_group_environment, &[]);
+    }
+
+    pub fn prepare_depth_prepass(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        vertex_buffer: wgpu::BufferSlice,
+        index_buffer: wgpu::BufferSlice,
+        bind_group_variable: &wgpu::BindGroup,
+        bind_group_bindless: &wgpu::BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.render_pipeline_depth_prepass);
+        render_pass.set_vertex_buffer(0, vertex_buffer);
+        render_pass.set_index_buffer(index_buffer, wgpu::IndexFormat::Uint32);
+        render_pass.set_bind_group(0, bind_group_variable, &[]);
+        render_pass.set_bind_group(1, bind_group_bindless, &[]);
+    }
+
+    /// Switches to the pipeline drawing materials with the given alpha blending and sidedness,
+    /// keeping the buffers and bind groups set by `prepare`.
+    pub fn set_pipeline(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        alpha_blended: bool,
+        double_sided: bool,
+    ) {
+        render_pass.set_pipeline(match (alpha_blended, double_sided) {
+            (false, false) => &self.render_pipeline,
+            (false, true) => &self.render_pipeline_double_sided,
+            (true, false) => &self.render_pipeline_transparent,
+            (true, true) => &self.render_pipeline_transparent_double_sided,
+        });
+    }
+
+    /// Switches the depth prepass pipeline, keeping the buffers and bind groups set by
+    /// `prepare_depth_prepass`.
+    pub fn set_depth_prepass_pipeline(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        double_sided: bool,
+    ) {
+        render_pass.set_pipeline(if double_sided {
+            &self.render_pipeline_depth_prepass_double_sided
+        } else {
+            &self.render_pipeline_depth_prepass
+        });
+    }
+
+    /// Selects the shadow view at `view_index` in the shadow pass views buffer, like
+    /// `Shadow::prepare` does for opaque shadow casters.
+    pub fn prepare_shadow_masked(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        vertex_buffer: wgpu::BufferSlice,
+        index_buffer: wgpu::BufferSlice,
+        bind_group_shadow_masked: &wgpu::BindGroup,
+        bind_group_bindless: &wgpu::BindGroup,
+        view_index: u32,
+    ) {
+        render_pass.set_pipeline(&self.render_pipeline_shadow_masked);
+        render_pass.set_vertex_buffer(0, vertex_buffer);
+        render_pass.set_index_buffer(index_buffer, wgpu::IndexFormat::Uint32);
+        render_pass.set_bind_group(
+            0,
+            bind_group_shadow_masked,
+            &[view_index * graphics::pipeline::render::shadow::SHADOW_PASS_VIEW_SIZE as u32],
+        );
+        render_pass.set_bind_group(1, bind_group_bindless, &[]);
     }
 
     pub fn create_bind_group_variable(
@@ -365,128 +683,184 @@ impl Pbr {
         })
     }
 
+    pub fn create_bind_group_shadow_masked(
+        &self,
+        device: &wgpu::Device,
+        shadow_pass_views_buffer: &wgpu::Buffer,
+        binding_resource_instance_transforms_buffer: wgpu::BindingResource,
+        binding_resource_indirect_instances_buffer: wgpu::BindingResource,
+        binding_resource_instance_materials_buffer: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group_shadow_masked"),
+            layout: &self.bind_group_layout_shadow_masked,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: shadow_pass_views_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(
+                            graphics::pipeline::render::shadow::SHADOW_PASS_VIEW_SIZE,
+                        ),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: binding_resource_instance_transforms_buffer,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: binding_resource_indirect_instances_buffer,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: binding_resource_instance_materials_buffer,
+                },
+            ],
+        })
+    }
+
     pub fn create_bind_group_bindless(
         &self,
         device: &wgpu::Device,
         binding_resource_material_buffer: wgpu::BindingResource,
-        texture_array_views: TextureArrays<wgpu::TextureView>,
-        binding_resource_texture_array_sampler_base_color: wgpu::BindingResource,
-        binding_resource_texture_array_sampler_normal: wgpu::BindingResource,
+        texture_views: &[wgpu::TextureView],
+        binding_resource_material_sampler: wgpu::BindingResource,
     ) -> wgpu::BindGroup {
+        let texture_view_array = texture_views.iter().collect::<Vec<_>>();
+        let texture_entries = if self.texture_binding_array {
+            vec![wgpu::BindGroupEntry {
+                binding: TEXTURES_FIRST_BINDING,
+                resource: wgpu::BindingResource::TextureViewArray(&texture_view_array),
+            }]
+        } else {
+            texture_views
+                .iter()
+                .zip(TEXTURES_FIRST_BINDING..)
+                .map(|(texture_view, binding)| wgpu::BindGroupEntry {
+                    binding,
+                    resource: wgpu::BindingResource::TextureView(texture_view),
+                })
+                .collect()
+        };
+
+        let entries = [
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: binding_resource_material_buffer,
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: binding_resource_material_sampler,
+            },
+        ]
+        .into_iter()
+        .chain(texture_entries)
+        .collect::<Vec<_>>();
+
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("bind_group_bindless"),
             layout: &self.bind_group_layout_bindless,
+            entries: &entries,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_bind_group_lights(
+        &self,
+        device: &wgpu::Device,
+        resource_binding_ambient_light_buffer: wgpu::BindingResource,
+        resource_binding_point_lights_buffer: wgpu::BindingResource,
+        resource_binding_lights_length_buffer: wgpu::BindingResource,
+        resource_binding_directional_lights_buffer: wgpu::BindingResource,
+        resource_binding_spot_lights_buffer: wgpu::BindingResource,
+        resource_binding_shadow_views_buffer: wgpu::BindingResource,
+        resource_binding_shadow_cascades_buffer: wgpu::BindingResource,
+        resource_binding_shadow_atlas: wgpu::BindingResource,
+        resource_binding_shadow_sampler: wgpu::BindingResource,
+        resource_binding_point_shadow_maps: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group_lights"),
+            layout: &self.bind_group_layout_lights,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: binding_resource_material_buffer,
+                    resource: resource_binding_ambient_light_buffer,
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rg_bc5_unorm_512,
-                    ),
+                    resource: resource_binding_point_lights_buffer,
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rg_bc5_unorm_1024,
-                    ),
+                    resource: resource_binding_lights_length_buffer,
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rg_bc5_unorm_2048,
-                    ),
+                    resource: resource_binding_directional_lights_buffer,
                 },
                 wgpu::BindGroupEntry {
                     binding: 4,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rg_bc5_unorm_4096,
-                    ),
+                    resource: resource_binding_spot_lights_buffer,
                 },
                 wgpu::BindGroupEntry {
                     binding: 5,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rgb_bc7_unorm_512,
-                    ),
+                    resource: resource_binding_shadow_views_buffer,
                 },
                 wgpu::BindGroupEntry {
                     binding: 6,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rgb_bc7_unorm_1024,
-                    ),
+                    resource: resource_binding_shadow_cascades_buffer,
                 },
                 wgpu::BindGroupEntry {
                     binding: 7,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rgb_bc7_unorm_2048,
-                    ),
+                    resource: resource_binding_shadow_atlas,
                 },
                 wgpu::BindGroupEntry {
                     binding: 8,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rgb_bc7_unorm_4096,
-                    ),
+                    resource: resource_binding_shadow_sampler,
                 },
                 wgpu::BindGroupEntry {
                     binding: 9,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rgba_bc7_srgb_512,
-                    ),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 10,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rgba_bc7_srgb_1024,
-                    ),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 11,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rgba_bc7_srgb_2048,
-                    ),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 12,
-                    resource: wgpu::BindingResource::TextureView(
-                        &texture_array_views.rgba_bc7_srgb_4096,
-                    ),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 13,
-                    resource: binding_resource_texture_array_sampler_base_color,
-                },
-                wgpu::BindGroupEntry {
-                    binding: 14,
-                    resource: binding_resource_texture_array_sampler_normal,
+                    resource: resource_binding_point_shadow_maps,
                 },
             ],
         })
     }
 
-    pub fn create_bind_group_lights(
+    pub fn create_bind_group_environment(
         &self,
         device: &wgpu::Device,
-        resource_binding_ambient_light_buffer: wgpu::BindingResource,
-        resource_binding_point_lights_buffer: wgpu::BindingResource,
-        resource_binding_point_lights_length_buffer: wgpu::BindingResource,
+        binding_resource_irradiance_cubemap: wgpu::BindingResource,
+        binding_resource_prefiltered_specular_cubemap: wgpu::BindingResource,
+        binding_resource_brdf_lut: wgpu::BindingResource,
+        binding_resource_environment_sampler: wgpu::BindingResource,
+        binding_resource_ambient_occlusion: wgpu::BindingResource,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("bind_group_lights"),
-            layout: &self.bind_group_layout_lights,
+            label: Some("bind_group_environment"),
+            layout: &self.bind_group_layout_environment,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: resource_binding_ambient_light_buffer,
+                    resource: binding_resource_irradiance_cubemap,
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: resource_binding_point_lights_buffer,
+                    resource: binding_resource_prefiltered_specular_cubemap,
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: resource_binding_point_lights_length_buffer,
+                    resource: binding_resource_brdf_lut,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: binding_resource_environment_sampler,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: binding_resource_ambient_occlusion,
                 },
             ],
         })
@@ -514,23 +888,25 @@ pub fn create_material_buffer(
 
 pub fn create_instance_transforms_buffer(
     device: &wgpu::Device,
-    instance_transforms: Vec<[f32; 16]>,
+    instance_capacity: usize,
 ) -> wgpu::Buffer {
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("instance_transforms_buffer"),
-        contents: bytemuck::cast_slice(&instance_transforms),
+        size: (instance_capacity * std::mem::size_of::<[f32; 16]>()) as wgpu::BufferAddress,
         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
     })
 }
 
 pub fn create_instance_materials_buffer(
     device: &wgpu::Device,
-    instance_materials: Vec<u32>,
+    instance_capacity: usize,
 ) -> wgpu::Buffer {
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("instance_materials_buffer"),
-        contents: bytemuck::cast_slice(&instance_materials),
+        size: (instance_capacity * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
     })
 }
 
@@ -545,24 +921,54 @@ pub fn create_ambient_light_buffer(
     })
 }
 
+// Storage buffers cannot be empty, so light buffers always hold at least one light.
+
 pub fn create_point_lights_buffer(
     device: &wgpu::Device,
-    point_lights: &[PointLight],
+    point_light_capacity: usize,
 ) -> wgpu::Buffer {
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+    device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("point_lights_buffer"),
-        contents: bytemuck::cast_slice(point_lights),
+        size: (point_light_capacity.max(1) * std::mem::size_of::<PointLight>())
+            as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+pub fn create_directional_lights_buffer(
+    device: &wgpu::Device,
+    directional_light_capacity: usize,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("directional_lights_buffer"),
+        size: (directional_light_capacity.max(1) * std::mem::size_of::<DirectionalLight>())
+            as wgpu::BufferAddress,
         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
     })
 }
 
-pub fn create_point_lights_length_buffer(
+pub fn create_spot_lights_buffer(
     device: &wgpu::Device,
-    point_lights_length: u32,
+    spot_light_capacity: usize,
+) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("spot_lights_buffer"),
+        size: (spot_light_capacity.max(1) * std::mem::size_of::<SpotLight>())
+            as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+pub fn create_lights_length_buffer(
+    device: &wgpu::Device,
+    lights_length: LightsLength,
 ) -> wgpu::Buffer {
     device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("point_lights_length_buffer"),
-        contents: bytemuck::cast_slice(&[point_lights_length]),
+        label: Some("lights_length_buffer"),
+        contents: bytemuck::cast_slice(&[lights_length]),
         usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
     })
 }
@@ -576,27 +982,34 @@ pub fn create_texture_array(
     let texture = device.create_texture(&wgpu::TextureDescriptor {
         label,
         size: wgpu::Extent3d {
-            width: texture_map.dimension,
-            height: texture_map.dimension,
-            depth_or_array_layers: (texture_map.map.len() as u32).max(1),
+            width: texture_map.class.width,
+            height: texture_map.class.height,
+            depth_or_array_layers: texture_array_layer_count(texture_map.map.len() as u32),
         },
-        mip_level_count: texture_map.mip_level_count,
+        mip_level_count: texture_map.class.mip_level_count,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
-        format: texture_map.format,
+        format: texture_map.class.format,
         usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
         view_formats: &[],
     });
 
-    // Holds true for BC5 and BC7.
+    // Holds true for BC5, BC6H and BC7.
     const BYTES_PER_BLOCK: u32 = 16;
     const BLOCK_SIZE: u32 = 4;
 
+    let mip_level_count = texture_map.class.mip_level_count;
     for layer_index in 0..texture_map.count() {
-        for mip_level_index in 0..texture_map.mip_level_count {
-            let mip_level_dimension = (texture_map.dimension >> mip_level_index).max(BLOCK_SIZE);
+        for mip_level_index in 0..mip_level_count {
+            // Mip levels smaller than a block still take a whole block.
+            let mip_level_width = (texture_map.class.width >> mip_level_index)
+                .max(1)
+                .next_multiple_of(BLOCK_SIZE);
+            let mip_level_height = (texture_map.class.height >> mip_level_index)
+                .max(1)
+                .next_multiple_of(BLOCK_SIZE);
             let (mip_offset, mip_len) = texture_map.mip_levels
-                [layer_index * texture_map.mip_level_count as usize + mip_level_index as usize];
+                [layer_index * mip_level_count as usize + mip_level_index as usize];
             let mip_level = &texture_map.data[mip_offset..(mip_offset + mip_len)];
 
             queue.write_texture(
@@ -613,12 +1026,12 @@ pub fn create_texture_array(
                 mip_level,
                 wgpu::ImageDataLayout {
                     offset: 0,
-                    bytes_per_row: Some(BYTES_PER_BLOCK * mip_level_dimension / BLOCK_SIZE),
-                    rows_per_image: Some(mip_level_dimension / BLOCK_SIZE),
+                    bytes_per_row: Some(BYTES_PER_BLOCK * mip_level_width / BLOCK_SIZE),
+                    rows_per_image: Some(mip_level_height / BLOCK_SIZE),
                 },
                 wgpu::Extent3d {
-                    width: mip_level_dimension,
-                    height: mip_level_dimension,
+                    width: mip_level_width,
+                    height: mip_level_height,
                     depth_or_array_layers: 1,
                 },
             );
@@ -628,126 +1041,38 @@ pub fn create_texture_array(
     texture
 }
 
-pub struct TextureArrays<T> {
-    pub rg_bc5_unorm_512: T,
-    pub rg_bc5_unorm_1024: T,
-    pub rg_bc5_unorm_2048: T,
-    pub rg_bc5_unorm_4096: T,
-    pub rgb_bc7_unorm_512: T,
-    pub rgb_bc7_unorm_1024: T,
-    pub rgb_bc7_unorm_2048: T,
-    pub rgb_bc7_unorm_4096: T,
-    pub rgba_bc7_srgb_512: T,
-    pub rgba_bc7_srgb_1024: T,
-    pub rgba_bc7_srgb_2048: T,
-    pub rgba_bc7_srgb_4096: T,
+/// OpenGL infers how a texture is viewed from its layer count, so texture arrays never have a
+/// single layer or a multiple of six, which would be taken for a 2D texture or cubemaps.
+fn texture_array_layer_count(texture_count: u32) -> u32 {
+    let layer_count = texture_count.max(2);
+    if layer_count.is_multiple_of(6) {
+        layer_count + 1
+    } else {
+        layer_count
+    }
 }
 
 pub fn create_texture_arrays_init(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
-    texture_maps: &TextureArrays<asset::TextureMap>,
-) -> TextureArrays<wgpu::Texture> {
-    let texture_array_rg_bc5_unorm_512 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rg_bc5_unorm_512"),
-        &texture_maps.rg_bc5_unorm_512,
-    );
-
-    let texture_array_rg_bc5_unorm_1024 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rg_bc5_unorm_1024"),
-        &texture_maps.rg_bc5_unorm_1024,
-    );
-
-    let texture_array_rg_bc5_unorm_2048 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rg_bc5_unorm_2048"),
-        &texture_maps.rg_bc5_unorm_2048,
-    );
-
-    let texture_array_rg_bc5_unorm_4096 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rg_bc5_unorm_4096"),
-        &texture_maps.rg_bc5_unorm_4096,
-    );
-
-    let texture_array_rgb_bc7_unorm_512 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rgb_bc7_unorm_512"),
-        &texture_maps.rgb_bc7_unorm_512,
-    );
-
-    let texture_array_rgb_bc7_unorm_1024 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rgb_bc7_unorm_1024"),
-        &texture_maps.rgb_bc7_unorm_1024,
-    );
-
-    let texture_array_rgb_bc7_unorm_2048 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rgb_bc7_unorm_2048"),
-        &texture_maps.rgb_bc7_unorm_2048,
-    );
-
-    let texture_array_rgb_bc7_unorm_4096 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rgb_bc7_unorm_4096"),
-        &texture_maps.rgb_bc7_unorm_4096,
-    );
-
-    let texture_array_rgba_bc7_srgb_512 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rgba_bc7_srgb_512"),
-        &texture_maps.rgba_bc7_srgb_512,
-    );
-
-    let texture_array_rgba_bc7_srgb_1024 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rgba_bc7_srgb_1024"),
-        &texture_maps.rgba_bc7_srgb_1024,
-    );
-
-    let texture_array_rgba_bc7_srgb_2048 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rgba_bc7_srgb_2048"),
-        &texture_maps.rgba_bc7_srgb_2048,
-    );
-
-    let texture_array_rgba_bc7_srgb_4096 = create_texture_array(
-        device,
-        queue,
-        Some("2d_texture_array_rgba_bc7_srgb_4096"),
-        &texture_maps.rgba_bc7_srgb_4096,
-    );
+    texture_arrays: &asset::TextureArrays,
+    bound_texture_arrays: &[asset::TextureArray],
+) -> Vec<wgpu::Texture> {
+    let textures = bound_texture_arrays
+        .iter()
+        .map(|&texture_array| {
+            create_texture_array(
+                device,
+                queue,
+                Some(&format!("2d_{}", texture_array_name(texture_array))),
+                texture_arrays.get(texture_array),
+            )
+        })
+        .collect();
 
     queue.submit([]);
 
-    TextureArrays {
-        rg_bc5_unorm_512: texture_array_rg_bc5_unorm_512,
-        rg_bc5_unorm_1024: texture_array_rg_bc5_unorm_1024,
-        rg_bc5_unorm_2048: texture_array_rg_bc5_unorm_2048,
-        rg_bc5_unorm_4096: texture_array_rg_bc5_unorm_4096,
-        rgb_bc7_unorm_512: texture_array_rgb_bc7_unorm_512,
-        rgb_bc7_unorm_1024: texture_array_rgb_bc7_unorm_1024,
-        rgb_bc7_unorm_2048: texture_array_rgb_bc7_unorm_2048,
-        rgb_bc7_unorm_4096: texture_array_rgb_bc7_unorm_4096,
-        rgba_bc7_srgb_512: texture_array_rgba_bc7_srgb_512,
-        rgba_bc7_srgb_1024: texture_array_rgba_bc7_srgb_1024,
-        rgba_bc7_srgb_2048: texture_array_rgba_bc7_srgb_2048,
-        rgba_bc7_srgb_4096: texture_array_rgba_bc7_srgb_4096,
-    }
+    textures
 }
 
 pub fn create_texture_array_view(
@@ -768,77 +1093,184 @@ pub fn create_texture_array_view(
 }
 
 pub fn create_texture_array_views(
-    texture_arrays: TextureArrays<wgpu::Texture>,
-) -> TextureArrays<wgpu::TextureView> {
-    TextureArrays {
-        rg_bc5_unorm_512: create_texture_array_view(
-            Some("texture_view_texture_array_rg_bc5_unorm_512"),
-            &texture_arrays.rg_bc5_unorm_512,
-            wgpu::TextureFormat::Bc5RgUnorm,
-        ),
-        rg_bc5_unorm_1024: create_texture_array_view(
-            Some("texture_view_texture_array_rg_bc5_unorm_1024"),
-            &texture_arrays.rg_bc5_unorm_1024,
-            wgpu::TextureFormat::Bc5RgUnorm,
-        ),
-        rg_bc5_unorm_2048: create_texture_array_view(
-            Some("texture_view_texture_array_rg_bc5_unorm_2048"),
-            &texture_arrays.rg_bc5_unorm_2048,
-            wgpu::TextureFormat::Bc5RgUnorm,
-        ),
-        rg_bc5_unorm_4096: create_texture_array_view(
-            Some("texture_view_texture_array_rg_bc5_unorm_4096"),
-            &texture_arrays.rg_bc5_unorm_4096,
-            wgpu::TextureFormat::Bc5RgUnorm,
-        ),
-        rgb_bc7_unorm_512: create_texture_array_view(
-            Some("texture_view_texture_array_rgb_bc7_unorm_512"),
-            &texture_arrays.rgb_bc7_unorm_512,
-            wgpu::TextureFormat::Bc7RgbaUnorm,
-        ),
-        rgb_bc7_unorm_1024: create_texture_array_view(
-            Some("texture_view_texture_array_rgb_bc7_unorm_1024"),
-            &texture_arrays.rgb_bc7_unorm_1024,
-            wgpu::TextureFormat::Bc7RgbaUnorm,
-        ),
-        rgb_bc7_unorm_2048: create_texture_array_view(
-            Some("texture_view_texture_array_rgb_bc7_unorm_2048"),
-            &texture_arrays.rgb_bc7_unorm_2048,
-            wgpu::TextureFormat::Bc7RgbaUnorm,
-        ),
-        rgb_bc7_unorm_4096: create_texture_array_view(
-            Some("texture_view_texture_array_rgb_bc7_unorm_4096"),
-            &texture_arrays.rgb_bc7_unorm_4096,
-            wgpu::TextureFormat::Bc7RgbaUnorm,
-        ),
-        rgba_bc7_srgb_512: create_texture_array_view(
-            Some("texture_view_texture_array_rgba_bc7_srgb_512"),
-            &texture_arrays.rgba_bc7_srgb_512,
-            wgpu::TextureFormat::Bc7RgbaUnormSrgb,
-        ),
-        rgba_bc7_srgb_1024: create_texture_array_view(
-            Some("texture_view_texture_array_rgba_bc7_srgb_1024"),
-            &texture_arrays.rgba_bc7_srgb_1024,
-            wgpu::TextureFormat::Bc7RgbaUnormSrgb,
-        ),
-        rgba_bc7_srgb_2048: create_texture_array_view(
-            Some("texture_view_texture_array_rgba_bc7_srgb_2048"),
-            &texture_arrays.rgba_bc7_srgb_2048,
-            wgpu::TextureFormat::Bc7RgbaUnormSrgb,
-        ),
-        rgba_bc7_srgb_4096: create_texture_array_view(
-            Some("texture_view_texture_array_rgba_bc7_srgb_4096"),
-            &texture_arrays.rgba_bc7_srgb_4096,
-            wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+    bound_texture_arrays: &[asset::TextureArray],
+    textures: Vec<wgpu::Texture>,
+) -> Vec<wgpu::TextureView> {
+    bound_texture_arrays
+        .iter()
+        .zip(textures)
+        .map(|(&texture_array, texture)| {
+            create_texture_array_view(
+                Some(&format!(
+                    "texture_view_{}",
+                    texture_array_name(texture_array)
+                )),
+                &texture,
+                texture_array.class().format,
+            )
+        })
+        .collect()
+}
+
+pub fn create_texture_layer_view(
+    label: Option<&str>,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    layer: u32,
+) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label,
+        format: Some(format),
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        aspect: wgpu::TextureAspect::All,
+        base_mip_level: 0,
+        mip_level_count: None,
+        base_array_layer: layer,
+        array_layer_count: Some(1),
+    })
+}
+
+/// Uploads the texture arrays and creates the views bound for materials: one per texture for a
+/// binding array, in the order of their texture indices, or one per bound texture array.
+pub fn create_texture_views_init(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture_arrays: &asset::TextureArrays,
+    texture_bindings: &TextureBindings,
+) -> Vec<wgpu::TextureView> {
+    match texture_bindings {
+        TextureBindings::BindingArray { .. } => {
+            let textures = create_texture_arrays_init(
+                device,
+                queue,
+                texture_arrays,
+                &texture_arrays.texture_arrays().collect::<Vec<_>>(),
+            );
+
+            texture_arrays
+                .textures
+                .iter()
+                .map(|&(texture_array, texture_id)| {
+                    create_texture_layer_view(
+                        Some(&format!(
+                            "texture_view_{}_{texture_id}",
+                            texture_array_name(texture_array)
+                        )),
+                        &textures[texture_array.id() as usize],
+                        texture_array.class().format,
+                        texture_id,
+                    )
+                })
+                .collect()
+        }
+        TextureBindings::TextureArrays(bound_texture_arrays) => create_texture_array_views(
+            bound_texture_arrays,
+            create_texture_arrays_init(device, queue, texture_arrays, bound_texture_arrays),
         ),
     }
 }
 
+#[derive(Debug)]
+pub enum PbrError {
+    TooManySampledTextures {
+        sampled_texture_count: u32,
+        max_sampled_texture_count: u32,
+    },
+}
+
+impl std::fmt::Display for PbrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManySampledTextures {
+                sampled_texture_count,
+                max_sampled_texture_count,
+            } => write!(
+                f,
+                "{sampled_texture_count} sampled textures exceed the device limit of {max_sampled_texture_count} per shader stage"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PbrError {}
+
+/// How material textures are bound to the shader.
+#[derive(Clone, Debug)]
+pub enum TextureBindings {
+    // Every texture in one binding array, indexed by texture index.
+    BindingArray { texture_count: std::num::NonZeroU32 },
+    // One binding per texture array, picked by texture array id.
+    TextureArrays(Vec<asset::TextureArray>),
+}
+
+impl TextureBindings {
+    /// Number of textures the fragment stage samples for the materials.
+    pub fn texture_count(&self) -> u32 {
+        match self {
+            Self::BindingArray { texture_count } => texture_count.get(),
+            Self::TextureArrays(texture_arrays) => texture_arrays.len() as u32,
+        }
+    }
+}
+
+// Textures sampled by the lights and environment bind groups, which count towards the same
+// per-stage limit as the material textures.
+const LIGHTING_SAMPLED_TEXTURE_COUNT: u32 = 6;
+
+// Binding of the first texture in the bindless bind group, after the material buffer and the
+// sampler.
+const TEXTURES_FIRST_BINDING: u32 = 2;
+
+// The body of `sample_texture` in the shader file, replaced when generating the shader.
+const SAMPLE_TEXTURE_PLACEHOLDER: &str =
+    "  // SAMPLE_TEXTURE\n  return vec4<f32>(0.0, 0.0, 0.0, 1.0);\n";
+
+fn texture_array_name(texture_array: asset::TextureArray) -> String {
+    format!("texture_array_{}", texture_array.id())
+}
+
+/// Declares the texture bindings in the shader, and samples them by texture reference.
+fn create_shader_source(texture_bindings: &TextureBindings) -> String {
+    let (bindings, sample_texture) = match texture_bindings {
+        TextureBindings::BindingArray { .. } => (
+            format!(
+                "@group(1) @binding({TEXTURES_FIRST_BINDING})\nvar textures: binding_array<texture_2d<f32>>;\n"
+            ),
+            "  return textureSample(textures[texture_reference.texture_index], material_sampler, tex_coords);\n"
+                .to_string(),
+        ),
+        TextureBindings::TextureArrays(texture_arrays) => {
+            let mut bindings = String::new();
+            let mut cases = String::new();
+            for (&texture_array, binding) in texture_arrays.iter().zip(TEXTURES_FIRST_BINDING..) {
+                let name = texture_array_name(texture_array);
+                bindings.push_str(&format!(
+                    "@group(1) @binding({binding})\nvar {name}: texture_2d_array<f32>;\n"
+                ));
+                cases.push_str(&format!(
+                    "    case {}u: {{\n      return textureSample({name}, material_sampler, tex_coords, texture_reference.texture_id);\n    }}\n",
+                    texture_array.id()
+                ));
+            }
+            (
+                bindings,
+                format!(
+                    "  switch texture_reference.texture_array_id {{\n{cases}    default: {{}}\n  }}\n  return vec4<f32>(0.0, 0.0, 0.0, 1.0);\n"
+                ),
+            )
+        }
+    };
+
+    include_str!("pbr.wgsl")
+        .replace("// TEXTURE_BINDINGS\n", &bindings)
+        .replace(SAMPLE_TEXTURE_PLACEHOLDER, &sample_texture)
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraMatrix {
-    pub position: [f32; 4],
     pub view_projection: [f32; 16],
+    pub position: [f32; 4],
 }
 
 #[repr(C)]
@@ -855,4 +1287,38 @@ pub struct PointLight {
     pub strength: f32,
     pub position: [f32; 3],
     pub range: f32,
+    pub shadow_index: u32, // Cube in the point shadow maps, or `shadow::NO_SHADOW`.
+    pub _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DirectionalLight {
+    pub color: [f32; 3],
+    pub strength: f32,
+    pub direction: [f32; 3],
+    pub shadow_index: u32, // First of the cascade shadow views, or `shadow::NO_SHADOW`.
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SpotLight {
+    pub color: [f32; 3],
+    pub strength: f32,
+    pub position: [f32; 3],
+    pub range: f32,
+    pub direction: [f32; 3],
+    pub inner_cone_cos: f32,
+    pub outer_cone_cos: f32,
+    pub shadow_index: u32, // Shadow view, or `shadow::NO_SHADOW`.
+    pub _padding: [f32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightsLength {
+    pub point_lights: u32,
+    pub directional_lights: u32,
+    pub spot_lights: u32,
+    pub _padding: u32,
 }
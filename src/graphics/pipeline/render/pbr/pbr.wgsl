@@ -1,28 +1,44 @@
+// Shadow passes bind their view in place of the camera, which starts with the same matrix.
 struct Camera {
-  position: vec3<f32>,
   view_projection: mat4x4<f32>,
+  position: vec3<f32>,
 }
 
 struct Material {
-  base_color_rgba: vec4<f32>,
-  normal_solid: vec3<f32>,
+  base_color_factor: vec4<f32>, // The solid color when there is no texture.
+  emissive_factor: vec3<f32>, // The solid color when there is no texture.
+  normal_scale: f32,
   base_color_texture: TextureReference,
   normal_texture: TextureReference,
-  occlusion_solid: f32,
+  occlusion_strength: f32, // The solid occlusion when there is no texture.
   occlusion_texture: TextureReference,
   occlusion_texture_channel: u32,
-  roughness_solid: f32,
+  roughness_factor: f32,
   roughness_texture: TextureReference,
   roughness_texture_channel: u32,
-  metallic_solid: f32,
+  metallic_factor: f32,
   metallic_texture: TextureReference,
   metallic_texture_channel: u32,
   bitmask: u32,
+  emissive_texture: TextureReference,
+  alpha_cutoff: f32,
 }
 
+const ALPHA_MASK_FLAG: u32 = 1u << 6u;
+const ALPHA_BLEND_FLAG: u32 = 1u << 7u;
+
 struct TextureReference {
   texture_array_id: u32,
   texture_id: u32,
+  texture_index: u32, // Among all textures, as bound in binding arrays.
+  tex_coords_set: u32,
+  tex_coords_transform: array<f32, 6>, // Columns of a 3x2 matrix.
+}
+
+// Both sets of texture coordinates of a fragment, each texture picking its own.
+struct TexCoords {
+  set_0: vec2<f32>,
+  set_1: vec2<f32>,
 }
 
 struct InstanceMaterial {
@@ -47,20 +63,23 @@ var<storage, read> instance_materials: array<InstanceMaterial>;
 
 struct Vertex {
   @location(0) position: vec3<f32>,
-  @location(1) tex_coords: vec2<f32>,
-  @location(2) normal: vec3<f32>,
-  @location(3) tangent: vec3<f32>,
-  @location(4) bitangent: vec3<f32>,
+  @location(1) tex_coords_0: vec2<f32>,
+  @location(2) tex_coords_1: vec2<f32>,
+  @location(3) normal: vec3<f32>,
+  @location(4) tangent: vec3<f32>,
+  @location(5) bitangent: vec3<f32>,
 }
 
 struct VertexOutput {
-  @builtin(position) clip_position: vec4<f32>,
+  // Invariant so that the depth prepass and the main pass compute the same depth.
+  @builtin(position) @invariant clip_position: vec4<f32>,
   @location(0) object_index: u32,
-  @location(1) tex_coords: vec2<f32>,
-  @location(2) world_position: vec3<f32>,
-  @location(3) normal: vec3<f32>,
-  @location(4) tangent: vec3<f32>,
-  @location(5) bitangent: vec3<f32>,
+  @location(1) tex_coords_0: vec2<f32>,
+  @location(2) tex_coords_1: vec2<f32>,
+  @location(3) world_position: vec3<f32>,
+  @location(4) normal: vec3<f32>,
+  @location(5) tangent: vec3<f32>,
+  @location(6) bitangent: vec3<f32>,
 }
 
 @vertex
@@ -79,51 +98,39 @@ fn vs_main(
 
   let world_position = transform * vec4<f32>(vertex.position, 1.0);
 
+  // Tangents follow the upper 3x3 of the transform, normals its inverse transpose. The cofactor
+  // matrix is the inverse transpose scaled by the determinant, whose sign keeps mirrored normals
+  // facing outwards.
+  let linear = mat3x3<f32>(transform[0].xyz, transform[1].xyz, transform[2].xyz);
+  let cofactor = mat3x3<f32>(
+    cross(linear[1], linear[2]),
+    cross(linear[2], linear[0]),
+    cross(linear[0], linear[1]),
+  );
+  let determinant_sign = select(1.0, -1.0, dot(linear[0], cofactor[0]) < 0.0);
+
   //let camera_space_position = world_position.xyz - camera.position;
 
   var vertex_output: VertexOutput;
   //vertex_output.clip_position = camera.view_projection * vec4<f32>(camera_space_position, 1.0);
   vertex_output.clip_position = camera.view_projection * world_position;
   vertex_output.object_index = object_index;
-  vertex_output.tex_coords = vertex.tex_coords;
+  vertex_output.tex_coords_0 = vertex.tex_coords_0;
+  vertex_output.tex_coords_1 = vertex.tex_coords_1;
   vertex_output.world_position = world_position.xyz;
-  vertex_output.normal = vertex.normal;
-  vertex_output.tangent = vertex.tangent;
-  vertex_output.bitangent = vertex.bitangent;
+  vertex_output.normal = normalize(determinant_sign * (cofactor * vertex.normal));
+  vertex_output.tangent = normalize(linear * vertex.tangent);
+  vertex_output.bitangent = normalize(linear * vertex.bitangent);
 
   return vertex_output;
 }
 
 @group(1) @binding(0)
 var<storage, read> materials: array<Material>;
+// A single sampler, as OpenGL binds each texture with one sampler.
 @group(1) @binding(1)
-var texture_array_rg_bc5_unorm_512: texture_2d_array<f32>;
-@group(1) @binding(2)
-var texture_array_rg_bc5_unorm_1024: texture_2d_array<f32>;
-@group(1) @binding(3)
-var texture_array_rg_bc5_unorm_2048: texture_2d_array<f32>;
-@group(1) @binding(4)
-var texture_array_rg_bc5_unorm_4096: texture_2d_array<f32>;
-@group(1) @binding(5)
-var texture_array_rgb_bc7_unorm_512: texture_2d_array<f32>;
-@group(1) @binding(6)
-var texture_array_rgb_bc7_unorm_1024: texture_2d_array<f32>;
-@group(1) @binding(7)
-var texture_array_rgb_bc7_unorm_2048: texture_2d_array<f32>;
-@group(1) @binding(8)
-var texture_array_rgb_bc7_unorm_4096: texture_2d_array<f32>;
-@group(1) @binding(9)
-var texture_array_rgba_bc7_srgb_512: texture_2d_array<f32>;
-@group(1) @binding(10)
-var texture_array_rgba_bc7_srgb_1024: texture_2d_array<f32>;
-@group(1) @binding(11)
-var texture_array_rgba_bc7_srgb_2048: texture_2d_array<f32>;
-@group(1) @binding(12)
-var texture_array_rgba_bc7_srgb_4096: texture_2d_array<f32>;
-@group(1) @binding(13)
-var base_color_sampler: sampler;
-@group(1) @binding(14)
-var normal_sampler: sampler;
+var material_sampler: sampler;
+// TEXTURE_BINDINGS
 
 struct AmbientLight {
   color: vec3<f32>,
@@ -135,6 +142,46 @@ struct PointLight {
   strength: f32,
   position: vec3<f32>,
   range: f32,
+  shadow_index: u32, // Cube in the point shadow maps.
+}
+
+struct DirectionalLight {
+  color: vec3<f32>,
+  strength: f32,
+  direction: vec3<f32>,
+  shadow_index: u32, // First of the cascade shadow views.
+}
+
+struct SpotLight {
+  color: vec3<f32>,
+  strength: f32,
+  position: vec3<f32>,
+  range: f32,
+  direction: vec3<f32>,
+  inner_cone_cos: f32,
+  outer_cone_cos: f32,
+  shadow_index: u32,
+}
+
+struct ShadowView {
+  view_projection: mat4x4<f32>,
+  atlas_rect: vec4<f32>, // Offset and size in atlas texture coordinates.
+  texel_size: f32, // World space size of a shadow texel, per unit of clip space w.
+}
+
+struct ShadowCascades {
+  camera_forward: vec3<f32>,
+  split_depths: vec4<f32>, // Far view depth of each cascade.
+}
+
+const NO_SHADOW: u32 = 0xffffffffu;
+const CASCADE_COUNT: u32 = 4u;
+const POINT_SHADOW_NEAR: f32 = 0.05;
+
+struct LightsLength {
+  point_lights: u32,
+  directional_lights: u32,
+  spot_lights: u32,
 }
 
 @group(2) @binding(0)
@@ -142,182 +189,472 @@ var<uniform> ambient_light: AmbientLight;
 @group(2) @binding(1)
 var<storage, read> point_lights: array<PointLight>;
 @group(2) @binding(2)
-var<uniform> point_lights_length: u32;
+var<uniform> lights_length: LightsLength;
+@group(2) @binding(3)
+var<storage, read> directional_lights: array<DirectionalLight>;
+@group(2) @binding(4)
+var<storage, read> spot_lights: array<SpotLight>;
+@group(2) @binding(5)
+var<storage, read> shadow_views: array<ShadowView>;
+@group(2) @binding(6)
+var<uniform> shadow_cascades: ShadowCascades;
+@group(2) @binding(7)
+var shadow_atlas: texture_depth_2d;
+@group(2) @binding(8)
+var shadow_sampler: sampler_comparison;
+@group(2) @binding(9)
+var point_shadow_maps: texture_depth_cube_array;
+
+@group(3) @binding(0)
+var irradiance_map: texture_cube<f32>;
+@group(3) @binding(1)
+var prefiltered_specular_map: texture_cube<f32>;
+@group(3) @binding(2)
+var brdf_lut: texture_2d<f32>;
+@group(3) @binding(3)
+var environment_sampler: sampler;
+@group(3) @binding(4)
+var screen_space_ambient_occlusion: texture_2d<f32>;
+
+// Alpha blended surfaces are not drawn in the depth prepass. Masked ones are cut out so that they
+// do not occlude what is behind them.
+@fragment
+fn fs_depth_prepass(vertex_output: VertexOutput) {
+  let material_id = instance_materials[vertex_output.object_index].material_id;
+  let material = materials[material_id];
+  let tex_coords = TexCoords(vertex_output.tex_coords_0, vertex_output.tex_coords_1);
 
+  if is_masked_out(material, base_color(material, tex_coords).a) {
+    discard;
+  }
+}
+
+// Shadow casters are drawn without a fragment stage, except alpha masked ones, which are cut out
+// where light passes through them. Alpha blended surfaces cast no shadow.
 @fragment
-fn fs_main(vertex_output: VertexOutput) -> @location(0) vec4<f32> {
+fn fs_shadow_masked(vertex_output: VertexOutput) {
   let material_id = instance_materials[vertex_output.object_index].material_id;
   let material = materials[material_id];
+  let tex_coords = TexCoords(vertex_output.tex_coords_0, vertex_output.tex_coords_1);
 
-  var color = vec3<f32>(0.0, 0.0, 0.0);
+  if base_color(material, tex_coords).a < material.alpha_cutoff {
+    discard;
+  }
+}
 
-  let object_color: vec4<f32> = base_color(material, vertex_output.tex_coords);
-  let ambient_occlusion: f32 = occlusion(material, vertex_output.tex_coords);
-  let roughness: f32 = roughness(material, vertex_output.tex_coords);
-  let metalness: f32 = metallic(material, vertex_output.tex_coords);
+@fragment
+fn fs_main(
+  vertex_output: VertexOutput,
+  @builtin(front_facing) front_facing: bool,
+) -> @location(0) vec4<f32> {
+  let material_id = instance_materials[vertex_output.object_index].material_id;
+  let material = materials[material_id];
+  let tex_coords = TexCoords(vertex_output.tex_coords_0, vertex_output.tex_coords_1);
 
-  let ambient_color = ambient_light.strength * ambient_light.color * object_color.xyz * ambient_occlusion;
-  color += ambient_color;
+  var color = vec3<f32>(0.0, 0.0, 0.0);
+
+  let object_color: vec4<f32> = base_color(material, tex_coords);
+  if is_masked_out(material, object_color.a) {
+    discard;
+  }
+  let alpha_blended = (material.bitmask & ALPHA_BLEND_FLAG) != 0u;
+  // Alpha blended surfaces are missing from the depth prepass, so the screen-space ambient
+  // occlusion at their pixels belongs to what is behind them.
+  let screen_space_occlusion = select(
+    textureLoad(screen_space_ambient_occlusion, vec2<i32>(vertex_output.clip_position.xy), 0).r,
+    1.0,
+    alpha_blended,
+  );
+  let ambient_occlusion: f32 = occlusion(material, tex_coords) * screen_space_occlusion;
+  let roughness: f32 = roughness(material, tex_coords);
+  let metalness: f32 = metallic(material, tex_coords);
 
   let tbn = mat3x3<f32>(vertex_output.tangent, vertex_output.bitangent, vertex_output.normal);
-  let object_normal: vec3<f32> = normal(material, vertex_output.tex_coords, tbn);
+  // Back faces are only rasterized for double-sided materials, and are lit from their side.
+  let object_normal: vec3<f32> = normal(material, tex_coords, tbn)
+    * select(-1.0, 1.0, front_facing);
 
   let view_direction = normalize(camera.position - vertex_output.world_position);
 
-  for (var i: u32 = 0; i < point_lights_length; i++) {
+  // The ambient light tints and scales the lighting coming from the environment.
+  color += ambient_light.strength * ambient_light.color * ambient_occlusion * image_based_lighting(
+    object_normal,
+    view_direction,
+    object_color.xyz,
+    roughness,
+    metalness,
+  );
+
+  for (var i: u32 = 0; i < lights_length.point_lights; i++) {
     let point_light = point_lights[i];
     let point_light_direction = point_light.position - vertex_output.world_position;
     let distance = length(point_light_direction);
-    let attenuation = max(0.0, 1.0 - pow(distance / point_light.range, 2.0));
-    let point_light_direction_normalized = normalize(point_light_direction);
-
-    let diffuse_factor = max(dot(object_normal, point_light_direction_normalized), 0.0);
-    let diffuse_color = attenuation * diffuse_factor * point_light.strength * point_light.color * object_color.xyz;
-    color += diffuse_color;
-
-    let halfway_vector = normalize(point_light_direction_normalized + view_direction);
-    let specular_factor = max(dot(object_normal, halfway_vector), 0.0);
-    let specular_intensity = pow(specular_factor, (1.0 - roughness) * 128.0);
-    var specular_color: vec3<f32>;
-    if (metalness > 0.5) {
-      // For metals, specular color is the color of the material.
-      specular_color = object_color.xyz * specular_intensity;
-    } else {
-      // For non-metals, specular color is typically white.
-      specular_color = specular_intensity * vec3<f32>(1.0, 1.0, 1.0);
+    let attenuation = distance_attenuation(distance, point_light.range)
+      * point_light_shadow(point_light, vertex_output.world_position, object_normal);
+
+    color += shade(
+      normalize(point_light_direction),
+      attenuation * point_light.strength * point_light.color,
+      object_normal,
+      view_direction,
+      object_color.xyz,
+      roughness,
+      metalness,
+    );
+  }
+
+  for (var i: u32 = 0; i < lights_length.directional_lights; i++) {
+    let directional_light = directional_lights[i];
+    let shadow = directional_light_shadow(
+      directional_light.shadow_index,
+      vertex_output.world_position,
+      object_normal,
+    );
+
+    color += shade(
+      -directional_light.direction,
+      shadow * directional_light.strength * directional_light.color,
+      object_normal,
+      view_direction,
+      object_color.xyz,
+      roughness,
+      metalness,
+    );
+  }
+
+  for (var i: u32 = 0; i < lights_length.spot_lights; i++) {
+    let spot_light = spot_lights[i];
+    let spot_light_direction = spot_light.position - vertex_output.world_position;
+    let distance = length(spot_light_direction);
+    let spot_light_direction_normalized = normalize(spot_light_direction);
+    let cone_cos = dot(-spot_light_direction_normalized, spot_light.direction);
+    let attenuation = distance_attenuation(distance, spot_light.range)
+      * smoothstep(spot_light.outer_cone_cos, spot_light.inner_cone_cos, cone_cos)
+      * shadow_visibility(spot_light.shadow_index, vertex_output.world_position, object_normal);
+
+    color += shade(
+      spot_light_direction_normalized,
+      attenuation * spot_light.strength * spot_light.color,
+      object_normal,
+      view_direction,
+      object_color.xyz,
+      roughness,
+      metalness,
+    );
+  }
+
+  color += emissive(material, tex_coords);
+
+  return vec4<f32>(color, select(1.0, object_color.a, alpha_blended));
+}
+
+fn is_masked_out(material: Material, alpha: f32) -> bool {
+  return (material.bitmask & ALPHA_MASK_FLAG) != 0u && alpha < material.alpha_cutoff;
+}
+
+const PI: f32 = 3.14159265359;
+
+// Inverse-square falloff, windowed so that it reaches zero at the light range.
+fn distance_attenuation(distance: f32, range: f32) -> f32 {
+  let window = saturate(1.0 - pow(distance / range, 4.0));
+  return window * window / max(distance * distance, 0.0001);
+}
+
+// Selects the cascade covering the fragment, based on its view depth. Fragments beyond the last
+// cascade are lit.
+fn directional_light_shadow(shadow_index: u32, world_position: vec3<f32>, object_normal: vec3<f32>) -> f32 {
+  if shadow_index == NO_SHADOW {
+    return 1.0;
+  }
+
+  let view_depth = dot(world_position - camera.position, shadow_cascades.camera_forward);
+  for (var cascade: u32 = 0; cascade < CASCADE_COUNT; cascade++) {
+    if view_depth < shadow_cascades.split_depths[cascade] {
+      return shadow_visibility(shadow_index + cascade, world_position, object_normal);
+    }
+  }
+
+  return 1.0;
+}
+
+// Fraction of the light reaching the fragment, filtered with 3x3 percentage-closer filtering.
+fn shadow_visibility(shadow_index: u32, world_position: vec3<f32>, object_normal: vec3<f32>) -> f32 {
+  if shadow_index == NO_SHADOW {
+    return 1.0;
+  }
+
+  let shadow_view = shadow_views[shadow_index];
+
+  // Offsets the position along the normal by about a shadow texel, which grows with the distance
+  // to the light for perspective views.
+  let unbiased_clip_position = shadow_view.view_projection * vec4<f32>(world_position, 1.0);
+  let normal_offset = object_normal * 1.5 * shadow_view.texel_size * unbiased_clip_position.w;
+  let clip_position = shadow_view.view_projection * vec4<f32>(world_position + normal_offset, 1.0);
+  let ndc = clip_position.xyz / clip_position.w;
+
+  if any(abs(ndc.xy) > vec2<f32>(1.0)) || ndc.z < 0.0 || ndc.z > 1.0 {
+    return 1.0;
+  }
+
+  let tile_coords = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+  let atlas_coords = shadow_view.atlas_rect.xy + tile_coords * shadow_view.atlas_rect.zw;
+
+  // Samples are kept half a texel inside the tile, so that they never read a neighbouring tile.
+  let texel = 1.0 / vec2<f32>(textureDimensions(shadow_atlas));
+  let tile_min = shadow_view.atlas_rect.xy + 0.5 * texel;
+  let tile_max = shadow_view.atlas_rect.xy + shadow_view.atlas_rect.zw - 0.5 * texel;
+
+  var visibility = 0.0;
+  for (var y = -1; y <= 1; y++) {
+    for (var x = -1; x <= 1; x++) {
+      let coords = clamp(atlas_coords + vec2<f32>(f32(x), f32(y)) * texel, tile_min, tile_max);
+      visibility += textureSampleCompareLevel(shadow_atlas, shadow_sampler, coords, ndc.z);
     }
-    specular_color = specular_color * attenuation * point_light.strength * point_light.color;
-    color += specular_color;
   }
 
-  return vec4<f32>(color, object_color.w);
+  return visibility / 9.0;
 }
 
-fn base_color(material: Material, tex_coords: vec2<f32>) -> vec4<f32> {
+fn point_light_shadow(point_light: PointLight, world_position: vec3<f32>, object_normal: vec3<f32>) -> f32 {
+  if point_light.shadow_index == NO_SHADOW {
+    return 1.0;
+  }
+
+  let light_to_fragment = world_position - point_light.position;
+
+  // Offsets the position along the normal by about a shadow texel at the fragment distance.
+  let texel_size = 2.0 * length(light_to_fragment) / f32(textureDimensions(point_shadow_maps).x);
+  let offset_light_to_fragment = light_to_fragment + object_normal * 1.5 * texel_size;
+
+  // The cube face rendering the fragment is the one facing its major axis, where the view depth
+  // is the distance along that axis.
+  let view_depth = max(
+    max(abs(offset_light_to_fragment.x), abs(offset_light_to_fragment.y)),
+    abs(offset_light_to_fragment.z),
+  );
+  let far = max(point_light.range, POINT_SHADOW_NEAR * 2.0);
+  let depth = far * (view_depth - POINT_SHADOW_NEAR) / (view_depth * (far - POINT_SHADOW_NEAR));
+
+  if depth > 1.0 {
+    return 1.0;
+  }
+
+  // The linear comparison sampler filters between the four nearest texels.
+  return textureSampleCompareLevel(
+    point_shadow_maps,
+    shadow_sampler,
+    offset_light_to_fragment,
+    point_light.shadow_index,
+    depth,
+  );
+}
+
+// Cook-Torrance metallic-roughness BRDF, returning the light reflected towards the viewer by a light
+// of the given radiance arriving from `light_direction`.
+fn shade(
+  light_direction: vec3<f32>,
+  radiance: vec3<f32>,
+  object_normal: vec3<f32>,
+  view_direction: vec3<f32>,
+  object_color: vec3<f32>,
+  roughness: f32,
+  metalness: f32,
+) -> vec3<f32> {
+  let n_dot_l = max(dot(object_normal, light_direction), 0.0);
+  if n_dot_l <= 0.0 {
+    return vec3<f32>(0.0);
+  }
+
+  let halfway_vector = normalize(light_direction + view_direction);
+  let n_dot_v = max(dot(object_normal, view_direction), 0.0001);
+  let n_dot_h = max(dot(object_normal, halfway_vector), 0.0);
+  let v_dot_h = max(dot(view_direction, halfway_vector), 0.0);
+
+  // Dielectrics reflect about 4% at normal incidence, metals reflect their base color.
+  let f0 = mix(vec3<f32>(0.04), object_color, metalness);
+
+  let alpha = max(roughness * roughness, 0.002);
+  let d = distribution_ggx(n_dot_h, alpha);
+  let g = geometry_smith(n_dot_v, n_dot_l, roughness);
+  let f = fresnel_schlick(v_dot_h, f0);
+
+  let specular = d * g * f / (4.0 * n_dot_v * n_dot_l);
+
+  // Light reflected by the specular lobe does not enter the surface, and metals absorb the
+  // refracted light.
+  let diffuse = (vec3<f32>(1.0) - f) * (1.0 - metalness) * object_color / PI;
+
+  return (diffuse + specular) * radiance * n_dot_l;
+}
+
+// Trowbridge-Reitz GGX normal distribution function.
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+  let alpha_squared = alpha * alpha;
+  let denominator = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+  return alpha_squared / (PI * denominator * denominator);
+}
+
+// Smith geometry function with the Schlick-GGX approximation, remapped for direct lighting.
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+  let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+  let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+  let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+  return g_v * g_l;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+  return f0 + (vec3<f32>(1.0) - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Split-sum approximation of the environment lighting, using the irradiance and prefiltered
+// specular cubemaps and the BRDF integration lookup table.
+fn image_based_lighting(
+  object_normal: vec3<f32>,
+  view_direction: vec3<f32>,
+  object_color: vec3<f32>,
+  roughness: f32,
+  metalness: f32,
+) -> vec3<f32> {
+  // The environment cubemaps are laid out like the skybox, which flips the z axis.
+  let cubemap_flip = vec3<f32>(1.0, 1.0, -1.0);
+
+  let n_dot_v = max(dot(object_normal, view_direction), 0.0001);
+  let f0 = mix(vec3<f32>(0.04), object_color, metalness);
+  let f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+
+  let irradiance = textureSample(irradiance_map, environment_sampler, object_normal * cubemap_flip).rgb;
+  let diffuse = (vec3<f32>(1.0) - f) * (1.0 - metalness) * irradiance * object_color;
+
+  let reflection = reflect(-view_direction, object_normal);
+  let max_lod = f32(textureNumLevels(prefiltered_specular_map) - 1u);
+  let prefiltered = textureSampleLevel(
+    prefiltered_specular_map,
+    environment_sampler,
+    reflection * cubemap_flip,
+    roughness * max_lod,
+  ).rgb;
+  let brdf = textureSampleLevel(brdf_lut, environment_sampler, vec2<f32>(n_dot_v, roughness), 0.0).rg;
+  let specular = prefiltered * (f * brdf.x + brdf.y);
+
+  return diffuse + specular;
+}
+
+// Fresnel-Schlick with the reflectance at grazing angles damped for rough surfaces.
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+  return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+fn base_color(material: Material, tex_coords: TexCoords) -> vec4<f32> {
   const BASE_COLOR_FLAG: u32 = 1u << 1u;
 
   if (material.bitmask & BASE_COLOR_FLAG) != 0u {
-    return sample_texture_2d_array(
-      material.base_color_texture.texture_array_id,
-      material.base_color_texture.texture_id,
-      base_color_sampler,
-      tex_coords
-    );
+    return sample_texture(
+      material.base_color_texture,
+      texture_tex_coords(material.base_color_texture, tex_coords)
+    ) * material.base_color_factor;
   } else {
-    return material.base_color_rgba;
+    return material.base_color_factor;
   }
 }
 
-fn normal(material: Material, tex_coords: vec2<f32>, tbn: mat3x3<f32>) -> vec3<f32> {
+fn normal(material: Material, tex_coords: TexCoords, tbn: mat3x3<f32>) -> vec3<f32> {
   const NORMAL_FLAG: u32 = 1u << 2u;
 
   if (material.bitmask & NORMAL_FLAG) != 0u {
-    let sampled_texture = sample_texture_2d_array(
-      material.normal_texture.texture_array_id,
-      material.normal_texture.texture_id,
-      normal_sampler,
-      tex_coords
+    let sampled_texture = sample_texture(
+      material.normal_texture,
+      texture_tex_coords(material.normal_texture, tex_coords)
     );
 
+    // Z is reconstructed before scaling, as only X and Y are stored.
     let object_normal_xy = sampled_texture.xy * 2.0 - 1.0;
-    let object_normal_z = sqrt(1.0 - dot(object_normal_xy, object_normal_xy));
-    let object_normal = normalize(tbn * vec3<f32>(object_normal_xy, object_normal_z));
+    let object_normal_z = sqrt(saturate(1.0 - dot(object_normal_xy, object_normal_xy)));
+    let object_normal = normalize(
+      tbn * vec3<f32>(object_normal_xy * material.normal_scale, object_normal_z)
+    );
 
     return object_normal;
   } else {
-    return material.normal_solid;
+    return normalize(tbn[2]);
   }
 }
 
-fn occlusion(material: Material, tex_coords: vec2<f32>) -> f32 {
+fn occlusion(material: Material, tex_coords: TexCoords) -> f32 {
   const OCCLUSION_FLAG: u32 = 1u << 3u;
 
   if (material.bitmask & OCCLUSION_FLAG) != 0u {
-    let sampled_texture = sample_texture_2d_array(
-      material.occlusion_texture.texture_array_id,
-      material.occlusion_texture.texture_id,
-      base_color_sampler,
-      tex_coords
+    let sampled_texture = sample_texture(
+      material.occlusion_texture,
+      texture_tex_coords(material.occlusion_texture, tex_coords)
     );
-    return sample_texture_channel(sampled_texture, material.occlusion_texture_channel);
+    let sampled_occlusion = sample_texture_channel(sampled_texture, material.occlusion_texture_channel);
+    return mix(1.0, sampled_occlusion, material.occlusion_strength);
   } else {
-    return material.occlusion_solid;
+    return material.occlusion_strength;
   }
 }
 
-fn roughness(material: Material, tex_coords: vec2<f32>) -> f32 {
+fn roughness(material: Material, tex_coords: TexCoords) -> f32 {
   const ROUGHNESS_FLAG: u32 = 1u << 4u;
 
   if (material.bitmask & ROUGHNESS_FLAG) != 0u {
-    let sampled_texture = sample_texture_2d_array(
-      material.roughness_texture.texture_array_id,
-      material.roughness_texture.texture_id,
-      base_color_sampler,
-      tex_coords
+    let sampled_texture = sample_texture(
+      material.roughness_texture,
+      texture_tex_coords(material.roughness_texture, tex_coords)
     );
-    return sample_texture_channel(sampled_texture, material.roughness_texture_channel);
+    return sample_texture_channel(sampled_texture, material.roughness_texture_channel)
+      * material.roughness_factor;
   } else {
-    return material.roughness_solid;
+    return material.roughness_factor;
   }
 }
 
-fn metallic(material: Material, tex_coords: vec2<f32>) -> f32 {
+fn metallic(material: Material, tex_coords: TexCoords) -> f32 {
   const METALLIC_FLAG: u32 = 1u << 5u;
 
   if (material.bitmask & METALLIC_FLAG) != 0u {
-    let sampled_texture = sample_texture_2d_array(
-      material.metallic_texture.texture_array_id,
-      material.metallic_texture.texture_id,
-      base_color_sampler,
-      tex_coords
+    let sampled_texture = sample_texture(
+      material.metallic_texture,
+      texture_tex_coords(material.metallic_texture, tex_coords)
     );
-    return sample_texture_channel(sampled_texture, material.metallic_texture_channel);
+    return sample_texture_channel(sampled_texture, material.metallic_texture_channel)
+      * material.metallic_factor;
   } else {
-    return material.metallic_solid;
+    return material.metallic_factor;
   }
 }
 
-fn sample_texture_2d_array(texture_array_id: u32, texture_id: u32, s: sampler, tex_coords: vec2<f32>) -> vec4<f32> {
-  switch texture_array_id {
-    case 0u, default: {
-      return textureSample(texture_array_rg_bc5_unorm_512, s, tex_coords, texture_id);
-    }
-    case 1u: {
-      return textureSample(texture_array_rg_bc5_unorm_1024, s, tex_coords, texture_id);
-    }
-    case 2u: {
-      return textureSample(texture_array_rg_bc5_unorm_2048, s, tex_coords, texture_id);
-    }
-    case 3u: {
-      return textureSample(texture_array_rg_bc5_unorm_4096, s, tex_coords, texture_id);
-    }
-    case 4u: {
-      return textureSample(texture_array_rgb_bc7_unorm_512, s, tex_coords, texture_id);
-    }
-    case 5u: {
-      return textureSample(texture_array_rgb_bc7_unorm_1024, s, tex_coords, texture_id);
-    }
-    case 6u: {
-      return textureSample(texture_array_rgb_bc7_unorm_2048, s, tex_coords, texture_id);
-    }
-    case 7u: {
-      return textureSample(texture_array_rgb_bc7_unorm_4096, s, tex_coords, texture_id);
-    }
-    case 8u: {
-      return textureSample(texture_array_rgba_bc7_srgb_512, s, tex_coords, texture_id);
-    }
-    case 9u: {
-      return textureSample(texture_array_rgba_bc7_srgb_1024, s, tex_coords, texture_id);
-    }
-    case 10u: {
-      return textureSample(texture_array_rgba_bc7_srgb_2048, s, tex_coords, texture_id);
-    }
-    case 11u: {
-      return textureSample(texture_array_rgba_bc7_srgb_4096, s, tex_coords, texture_id);
-    }
+fn emissive(material: Material, tex_coords: TexCoords) -> vec3<f32> {
+  const EMISSIVE_FLAG: u32 = 1u << 9u;
+
+  if (material.bitmask & EMISSIVE_FLAG) != 0u {
+    return sample_texture(
+      material.emissive_texture,
+      texture_tex_coords(material.emissive_texture, tex_coords)
+    ).rgb * material.emissive_factor;
+  } else {
+    return material.emissive_factor;
   }
 }
 
+// Picks the set of texture coordinates of the texture and applies its transform.
+fn texture_tex_coords(texture_reference: TextureReference, tex_coords: TexCoords) -> vec2<f32> {
+  let transform = texture_reference.tex_coords_transform;
+  let matrix = mat3x2<f32>(
+    transform[0], transform[1],
+    transform[2], transform[3],
+    transform[4], transform[5],
+  );
+  let set_tex_coords = select(tex_coords.set_0, tex_coords.set_1, texture_reference.tex_coords_set == 1u);
+  return matrix * vec3<f32>(set_tex_coords, 1.0);
+}
+
+// Samples a binding array or a texture array, as generated with the texture bindings.
+fn sample_texture(texture_reference: TextureReference, tex_coords: vec2<f32>) -> vec4<f32> {
+  // SAMPLE_TEXTURE
+  return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+}
+
 fn sample_texture_channel(sampled_texture: vec4<f32>, channel: u32) -> f32 {
   switch channel {
     case 0u, default: {
@@ -0,0 +1,6 @@
+#[allow(
+    clippy::module_inception,
+    reason = "each pipeline lives in a file named after its directory"
+)]
+mod shadow;
+pub use shadow::*;
@@ -0,0 +1,274 @@
+use crate::graphics;
+
+pub const SHADOW_ATLAS_SIZE: u32 = 4096;
+pub const SHADOW_TILE_SIZE: u32 = 1024;
+pub const SHADOW_TILE_COUNT: usize =
+    ((SHADOW_ATLAS_SIZE / SHADOW_TILE_SIZE) * (SHADOW_ATLAS_SIZE / SHADOW_TILE_SIZE)) as usize;
+pub const CASCADE_COUNT: usize = 4;
+pub const POINT_SHADOW_MAP_SIZE: u32 = 512;
+pub const MAX_POINT_LIGHT_SHADOWS: usize = 4;
+pub const POINT_SHADOW_FACE_COUNT: usize = MAX_POINT_LIGHT_SHADOWS * 6;
+pub const POINT_SHADOW_NEAR: f32 = 0.05;
+/// Marks a light without a shadow map.
+pub const NO_SHADOW: u32 = u32::MAX;
+
+pub const SHADOW_PASS_VIEW_SIZE: u64 = std::mem::size_of::<ShadowPassView>() as u64;
+/// Pushes the stored depth away from the light to avoid shadow acne.
+pub const SHADOW_DEPTH_BIAS: wgpu::DepthBiasState = wgpu::DepthBiasState {
+    constant: 2,
+    slope_scale: 2.0,
+    clamp: 0.0,
+};
+
+pub struct Shadow {
+    render_pipeline: wgpu::RenderPipeline,
+    bind_group_layout_shadow: wgpu::BindGroupLayout,
+}
+
+impl Shadow {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout_shadow =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout_shadow"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: wgpu::BufferSize::new(SHADOW_PASS_VIEW_SIZE),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader_shadow"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shadow.wgsl").into()),
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("render_pipeline_layout_shadow"),
+                bind_group_layouts: &[&bind_group_layout_shadow],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("render_pipeline_shadow"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[graphics::Vertex::buffer_layout()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: None,
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: SHADOW_DEPTH_BIAS,
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // Cube face views are mirrored, which flips the winding order, so both faces are
+                // drawn.
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            render_pipeline,
+            bind_group_layout_shadow,
+        }
+    }
+
+    /// Selects the shadow view at `view_index` in the shadow pass views buffer.
+    pub fn prepare(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        vertex_buffer: wgpu::BufferSlice,
+        index_buffer: wgpu::BufferSlice,
+        bind_group_shadow: &wgpu::BindGroup,
+        view_index: u32,
+    ) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, vertex_buffer);
+        render_pass.set_index_buffer(index_buffer, wgpu::IndexFormat::Uint32);
+        render_pass.set_bind_group(
+            0,
+            bind_group_shadow,
+            &[view_index * SHADOW_PASS_VIEW_SIZE as u32],
+        );
+    }
+
+    pub fn create_bind_group_shadow(
+        &self,
+        device: &wgpu::Device,
+        shadow_pass_views_buffer: &wgpu::Buffer,
+        binding_resource_instance_transforms_buffer: wgpu::BindingResource,
+        binding_resource_indirect_instances_buffer: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group_shadow"),
+            layout: &self.bind_group_layout_shadow,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    // Bound one view at a time, selected with a dynamic offset.
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: shadow_pass_views_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(SHADOW_PASS_VIEW_SIZE),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: binding_resource_instance_transforms_buffer,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: binding_resource_indirect_instances_buffer,
+                },
+            ],
+        })
+    }
+}
+
+pub fn create_shadow_atlas(device: &wgpu::Device) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("shadow_atlas"),
+        size: wgpu::Extent3d {
+            width: SHADOW_ATLAS_SIZE,
+            height: SHADOW_ATLAS_SIZE,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Depth32Float,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+pub fn create_point_shadow_maps(device: &wgpu::Device) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("point_shadow_maps"),
+        size: wgpu::Extent3d {
+            width: POINT_SHADOW_MAP_SIZE,
+            height: POINT_SHADOW_MAP_SIZE,
+            depth_or_array_layers: POINT_SHADOW_FACE_COUNT as u32,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Depth32Float,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+pub fn create_shadow_sampler(device: &wgpu::Device) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("shadow_sampler"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        compare: Some(wgpu::CompareFunction::LessEqual),
+        ..Default::default()
+    })
+}
+
+// Every atlas tile and point shadow face holds at most one view, so the view buffers are never
+// reallocated.
+
+/// Holds the atlas tile views, followed by the point shadow face views.
+pub fn create_shadow_pass_views_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("shadow_pass_views_buffer"),
+        size: ((SHADOW_TILE_COUNT + POINT_SHADOW_FACE_COUNT)
+            * std::mem::size_of::<ShadowPassView>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+pub fn create_shadow_views_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("shadow_views_buffer"),
+        size: (SHADOW_TILE_COUNT * std::mem::size_of::<ShadowView>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+pub fn create_shadow_cascades_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("shadow_cascades_buffer"),
+        size: std::mem::size_of::<ShadowCascades>() as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// View rendered by the shadow pass, padded to the minimum uniform buffer offset alignment. It
+/// starts like the PBR camera, which alpha masked shadow casters are drawn with.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowPassView {
+    pub view_projection: [f32; 16],
+    pub _padding: [f32; 48],
+}
+
+/// Shadow view as sampled by the PBR shader.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowView {
+    pub view_projection: [f32; 16],
+    pub atlas_rect: [f32; 4], // Offset and size in atlas texture coordinates.
+    // World space size of a shadow texel, per unit of clip space w.
+    pub texel_size: f32,
+    pub _padding: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowCascades {
+    pub camera_forward: [f32; 3],
+    pub _padding: f32,
+    pub split_depths: [f32; CASCADE_COUNT], // Far view depth of each cascade.
+}
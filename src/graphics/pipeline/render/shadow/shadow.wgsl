@@ -0,0 +1,34 @@
+struct ShadowPassView {
+  view_projection: mat4x4<f32>,
+}
+
+struct InstanceTransform {
+  matrix_col_0: vec4<f32>,
+  matrix_col_1: vec4<f32>,
+  matrix_col_2: vec4<f32>,
+  matrix_col_3: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> shadow_pass_view: ShadowPassView;
+@group(0) @binding(1)
+var<storage, read> instance_transforms: array<InstanceTransform>;
+@group(0) @binding(2)
+var<storage, read> indirect_instances: array<u32>;
+
+@vertex
+fn vs_main(
+  @location(0) position: vec3<f32>,
+  @builtin(instance_index) instance_index: u32,
+) -> @builtin(position) vec4<f32> {
+  let object_index = indirect_instances[instance_index];
+
+  let transform = mat4x4<f32>(
+    instance_transforms[object_index].matrix_col_0,
+    instance_transforms[object_index].matrix_col_1,
+    instance_transforms[object_index].matrix_col_2,
+    instance_transforms[object_index].matrix_col_3,
+  );
+
+  return shadow_pass_view.view_projection * transform * vec4<f32>(position, 1.0);
+}
@@ -1,2 +1,6 @@
+#[allow(
+    clippy::module_inception,
+    reason = "each pipeline lives in a file named after its directory"
+)]
 pub mod skybox;
 pub use skybox::*;
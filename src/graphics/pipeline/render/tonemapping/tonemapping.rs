@@ -0,0 +1,249 @@
+use crate::asset;
+use crate::ecs;
+use wgpu::util::DeviceExt;
+
+pub struct Tonemapping {
+    render_pipeline: wgpu::RenderPipeline,
+    bind_group_layout_tonemapping: wgpu::BindGroupLayout,
+}
+
+impl Tonemapping {
+    pub fn new(device: &wgpu::Device, texture_format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout_tonemapping =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("bind_group_layout_tonemapping"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader_tonemapping"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("tonemapping.wgsl").into()),
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("render_pipeline_layout_tonemapping"),
+                bind_group_layouts: &[&bind_group_layout_tonemapping],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("render_pipeline_tonemapping"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: texture_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            depth_stencil: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            render_pipeline,
+            bind_group_layout_tonemapping,
+        }
+    }
+
+    pub fn prepare(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        bind_group_tonemapping: &wgpu::BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, bind_group_tonemapping, &[]);
+    }
+
+    /// Draws a single triangle covering the whole target.
+    pub fn draw(render_pass: &mut wgpu::RenderPass) {
+        render_pass.draw(0..3, 0..1);
+    }
+
+    pub fn create_bind_group_tonemapping(
+        &self,
+        device: &wgpu::Device,
+        binding_resource_hdr_buffer: wgpu::BindingResource,
+        binding_resource_tonemapping_buffer: wgpu::BindingResource,
+        binding_resource_bloom_texture: wgpu::BindingResource,
+        binding_resource_color_grading_lut: wgpu::BindingResource,
+        binding_resource_sampler: wgpu::BindingResource,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bind_group_tonemapping"),
+            layout: &self.bind_group_layout_tonemapping,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: binding_resource_hdr_buffer,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: binding_resource_tonemapping_buffer,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: binding_resource_bloom_texture,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: binding_resource_color_grading_lut,
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: binding_resource_sampler,
+                },
+            ],
+        })
+    }
+}
+
+pub fn create_tonemapping_buffer(
+    device: &wgpu::Device,
+    tonemapping_settings: &TonemappingSettings,
+) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("tonemapping_buffer"),
+        contents: bytemuck::cast_slice(&[*tonemapping_settings]),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+pub fn create_color_grading_lut_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    color_grading_lut: &asset::ColorGradingLut,
+) -> wgpu::Texture {
+    let texels = color_grading_lut
+        .colors
+        .iter()
+        .flat_map(|color| {
+            let [r, g, b] = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+            [r, g, b, u8::MAX]
+        })
+        .collect::<Vec<_>>();
+
+    device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("color_grading_lut"),
+            size: wgpu::Extent3d {
+                width: color_grading_lut.size,
+                height: color_grading_lut.size,
+                depth_or_array_layers: color_grading_lut.size,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        &texels,
+    )
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TonemappingSettings {
+    pub exposure: f32, // In stops.
+    pub tonemapper: u32,
+    pub bloom_intensity: f32,
+    pub vignette_intensity: f32,
+    pub color_grading: u32,
+    pub _padding: [u32; 3],
+}
+
+impl TonemappingSettings {
+    /// Disabled effects are given no weight.
+    pub fn new(
+        camera: &ecs::resource::Camera,
+        post_processing: &ecs::resource::PostProcessing,
+    ) -> Self {
+        Self {
+            exposure: camera.exposure,
+            tonemapper: camera.tonemapper as u32,
+            bloom_intensity: if post_processing.bloom {
+                post_processing.bloom_intensity
+            } else {
+                0.0
+            },
+            vignette_intensity: if post_processing.vignette {
+                post_processing.vignette_intensity
+            } else {
+                0.0
+            },
+            color_grading: post_processing.color_grading as u32,
+            _padding: [0; 3],
+        }
+    }
+}
@@ -0,0 +1,139 @@
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+  // A triangle covering the whole clip space square.
+  let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+  return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+struct TonemappingSettings {
+  exposure: f32,
+  tonemapper: u32,
+  bloom_intensity: f32,
+  vignette_intensity: f32,
+  color_grading: u32,
+};
+
+const TONEMAPPER_ACES: u32 = 0u;
+const TONEMAPPER_AGX: u32 = 1u;
+const TONEMAPPER_REINHARD: u32 = 2u;
+
+@group(0) @binding(0)
+var hdr_buffer: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> tonemapping_settings: TonemappingSettings;
+@group(0) @binding(2)
+var bloom_texture: texture_2d<f32>;
+@group(0) @binding(3)
+var color_grading_lut: texture_3d<f32>;
+@group(0) @binding(4)
+var linear_sampler: sampler;
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+  let uv = position.xy / vec2<f32>(textureDimensions(hdr_buffer));
+  let hdr_color = textureLoad(hdr_buffer, vec2<i32>(position.xy), 0);
+  let bloom = textureSampleLevel(bloom_texture, linear_sampler, uv, 0.0).rgb;
+  let scene_color = mix(hdr_color.rgb, bloom, tonemapping_settings.bloom_intensity);
+  let color = max(scene_color, vec3<f32>(0.0)) * exp2(tonemapping_settings.exposure);
+
+  var mapped: vec3<f32>;
+  switch tonemapping_settings.tonemapper {
+    case TONEMAPPER_AGX: {
+      mapped = agx(color);
+    }
+    case TONEMAPPER_REINHARD: {
+      mapped = reinhard(color);
+    }
+    default: {
+      mapped = aces(color);
+    }
+  }
+
+  if tonemapping_settings.color_grading != 0u {
+    mapped = color_grade(mapped);
+  }
+
+  // Darkens the corners, reaching full strength at the corners of the frame.
+  let distance_to_center = length(uv - 0.5) * sqrt(2.0);
+  mapped *= 1.0 - tonemapping_settings.vignette_intensity * smoothstep(0.4, 1.0, distance_to_center);
+
+  // The surface is sRGB, so the output stays linear.
+  return vec4<f32>(mapped, hdr_color.a);
+}
+
+// The lookup table maps sRGB encoded colors. Samples are offset by half a texel so the outermost
+// entries are reached exactly.
+fn color_grade(color: vec3<f32>) -> vec3<f32> {
+  let size = f32(textureDimensions(color_grading_lut).x);
+  let coordinates = (linear_to_srgb(saturate(color)) * (size - 1.0) + 0.5) / size;
+  let graded = textureSampleLevel(color_grading_lut, linear_sampler, coordinates, 0.0).rgb;
+  return srgb_to_linear(graded);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+  return select(
+    1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055,
+    color * 12.92,
+    color <= vec3<f32>(0.0031308),
+  );
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+  return select(
+    pow((color + 0.055) / 1.055, vec3<f32>(2.4)),
+    color / 12.92,
+    color <= vec3<f32>(0.04045),
+  );
+}
+
+// Stephen Hill's fit of the ACES reference rendering and sRGB output transforms. The matrices are
+// written row by row and applied with a vector on the left.
+const ACES_INPUT = mat3x3<f32>(
+  0.59719, 0.35458, 0.04823,
+  0.07600, 0.90834, 0.01566,
+  0.02840, 0.13383, 0.83777,
+);
+const ACES_OUTPUT = mat3x3<f32>(
+  1.60475, -0.53108, -0.07367,
+  -0.10208, 1.10813, -0.00605,
+  -0.00327, -0.07276, 1.07602,
+);
+
+fn aces(color: vec3<f32>) -> vec3<f32> {
+  let v = color * ACES_INPUT;
+  let a = v * (v + 0.0245786) - 0.000090537;
+  let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+  return clamp((a / b) * ACES_OUTPUT, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Minimal AgX with the default look, using a polynomial fit of the sigmoid.
+const AGX_INSET = mat3x3<f32>(
+  0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+  0.0784335999999992, 0.878468636469772, 0.0784336,
+  0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+);
+const AGX_OUTSET = mat3x3<f32>(
+  1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+  -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+  -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+);
+const AGX_MIN_EV: f32 = -12.47393;
+const AGX_MAX_EV: f32 = 4.026069;
+
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+  let x2 = x * x;
+  let x4 = x2 * x2;
+  return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+    - 0.00232;
+}
+
+fn agx(color: vec3<f32>) -> vec3<f32> {
+  let log_color = clamp(log2(max(AGX_INSET * color, vec3<f32>(1e-10))), vec3<f32>(AGX_MIN_EV), vec3<f32>(AGX_MAX_EV));
+  let encoded = agx_contrast((log_color - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV));
+  // The sigmoid targets a 2.2 display gamma.
+  return pow(clamp(AGX_OUTSET * encoded, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(2.2));
+}
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+  return color / (1.0 + color);
+}
@@ -9,5 +9,46 @@ fn main() {
     // Removing this makes wgpu fail silently.
     env_logger::init();
 
-    app::App::run();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("--headless") => {
+            let Some((width, height, frame_count)) = parse_headless_args(&args[1..]) else {
+                eprintln!("{HEADLESS_USAGE}");
+                std::process::exit(2);
+            };
+            let output_directory = args.get(3).map_or("captures", String::as_str);
+
+            app::App::run_headless(
+                width,
+                height,
+                frame_count,
+                std::path::Path::new(output_directory),
+            );
+        }
+        _ => app::App::run(),
+    }
+}
+
+const HEADLESS_USAGE: &str =
+    "usage: merlin --headless [WIDTHxHEIGHT] [FRAME_COUNT] [OUTPUT_DIRECTORY]";
+
+/// Parses the optional size and frame count following `--headless`, returning `None` if either is
+/// malformed or the size is empty.
+fn parse_headless_args(args: &[String]) -> Option<(u32, u32, u32)> {
+    let (width, height) = match args.first() {
+        Some(size) => {
+            let (width, height) = size.split_once('x')?;
+            (width.parse().ok()?, height.parse().ok()?)
+        }
+        None => (1280, 720),
+    };
+    let frame_count = match args.get(1) {
+        Some(frame_count) => frame_count.parse().ok()?,
+        None => 1,
+    };
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    Some((width, height, frame_count))
 }
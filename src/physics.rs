@@ -65,6 +65,10 @@ impl Physics {
 pub struct RigidBody(pub rapier3d::dynamics::RigidBodyHandle);
 
 #[derive(bevy_ecs::component::Component, Debug)]
+#[allow(
+    dead_code,
+    reason = "ties static colliders to their entity, nothing reads them back yet"
+)]
 pub struct Collider(pub rapier3d::geometry::ColliderHandle);
 
 #[derive(bevy_ecs::system::Resource, Debug)]
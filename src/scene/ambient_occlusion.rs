@@ -0,0 +1,56 @@
+use crate::graphics;
+
+use graphics::pipeline::compute::{ambient_occlusion, AmbientOcclusion};
+
+/// Screen-space ambient occlusion targets, sized to the surface, and the bind groups reading the
+/// depth buffer. Rebuilt along with the depth buffer whenever the surface is resized.
+#[derive(bevy_ecs::system::Resource)]
+pub struct AmbientOcclusionTargets {
+    pub width: u32,
+    pub height: u32,
+    // Blurred occlusion, read by the PBR pipeline.
+    pub ambient_occlusion_view: wgpu::TextureView,
+    pub bind_group_ambient_occlusion: wgpu::BindGroup,
+    pub bind_group_ambient_occlusion_blur: wgpu::BindGroup,
+}
+
+impl AmbientOcclusionTargets {
+    pub fn new(
+        gpu: &graphics::Gpu,
+        ambient_occlusion_pipeline: &AmbientOcclusion,
+        ambient_occlusion_buffer: &wgpu::Buffer,
+        depth_buffer: &wgpu::TextureView,
+    ) -> Self {
+        let device = &gpu.device;
+        let (width, height) = (gpu.config.width, gpu.config.height);
+
+        let raw_ambient_occlusion_view =
+            ambient_occlusion::create_ambient_occlusion_texture(device, width, height);
+        let ambient_occlusion_view =
+            ambient_occlusion::create_ambient_occlusion_texture(device, width, height);
+
+        let bind_group_ambient_occlusion = ambient_occlusion_pipeline
+            .create_bind_group_ambient_occlusion(
+                device,
+                wgpu::BindingResource::TextureView(depth_buffer),
+                ambient_occlusion_buffer.as_entire_binding(),
+                wgpu::BindingResource::TextureView(&raw_ambient_occlusion_view),
+            );
+        let bind_group_ambient_occlusion_blur = ambient_occlusion_pipeline
+            .create_bind_group_ambient_occlusion_blur(
+                device,
+                wgpu::BindingResource::TextureView(depth_buffer),
+                ambient_occlusion_buffer.as_entire_binding(),
+                wgpu::BindingResource::TextureView(&ambient_occlusion_view),
+                wgpu::BindingResource::TextureView(&raw_ambient_occlusion_view),
+            );
+
+        Self {
+            width,
+            height,
+            ambient_occlusion_view,
+            bind_group_ambient_occlusion,
+            bind_group_ambient_occlusion_blur,
+        }
+    }
+}
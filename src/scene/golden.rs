@@ -0,0 +1,251 @@
+//! Golden-image regression tests. Each case renders the flight helmet scene offscreen with a
+//! deterministic camera on a software adapter, and compares the last frame against a reference
+//! image in `tests/golden`. Failing cases, including missing references, write the rendered frame
+//! and a diff image to `target/golden`. Run with `MERLIN_BLESS_GOLDEN=1` to write the reference
+//! images from the rendered frames instead.
+//!
+//! The rendering cases need a software adapter, such as Mesa's llvmpipe, lavapipe or WARP, so they
+//! are ignored unless requested with `cargo test -- --ignored`.
+
+use super::Scene;
+use crate::ecs;
+use crate::graphics;
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+const DELTA_TIME: std::time::Duration = std::time::Duration::from_millis(16);
+const PIXEL_TOLERANCE: u8 = 8;
+
+struct GoldenCase {
+    name: &'static str,
+    camera: ecs::resource::Camera,
+    frame_count: u32,
+}
+
+fn run(case: GoldenCase) {
+    let gpu = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(graphics::Gpu::new_headless(WIDTH, HEIGHT, true))
+        .unwrap_or_else(|error| panic!("golden test `{}` cannot render: {error}", case.name));
+
+    let mut scene = Scene::setup_deterministic(
+        gpu,
+        Some(ecs::resource::Camera {
+            aspect_ratio: WIDTH as f32 / HEIGHT as f32,
+            ..case.camera
+        }),
+        DELTA_TIME,
+    );
+
+    let mut capture = None;
+    for _ in 0..case.frame_count {
+        capture = scene.capture_frame();
+    }
+    let actual = capture.unwrap();
+
+    let reference_path = golden_directory().join(format!("{}.png", case.name));
+    if std::env::var_os("MERLIN_BLESS_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_directory()).unwrap();
+        actual.save_png(&reference_path).unwrap();
+        eprintln!("wrote reference image {}", reference_path.display());
+        return;
+    }
+
+    // A missing reference is an empty image, which differs from every frame.
+    let reference = if reference_path.exists() {
+        load_png(&reference_path)
+    } else {
+        graphics::Capture {
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        }
+    };
+    let Some(diff) = diff(&actual, &reference) else {
+        return;
+    };
+
+    std::fs::create_dir_all(output_directory()).unwrap();
+    let actual_path = output_directory().join(format!("{}.png", case.name));
+    let diff_path = output_directory().join(format!("{}.diff.png", case.name));
+    actual.save_png(&actual_path).unwrap();
+    diff.save_png(&diff_path).unwrap();
+
+    let mismatch = if reference_path.exists() {
+        "does not match"
+    } else {
+        "has no reference image at"
+    };
+    panic!(
+        "`{}` {mismatch} {}, see {} and {}",
+        case.name,
+        reference_path.display(),
+        actual_path.display(),
+        diff_path.display(),
+    );
+}
+
+/// Returns an image highlighting the pixels that differ by more than the tolerance, or `None` if
+/// the images match.
+fn diff(actual: &graphics::Capture, reference: &graphics::Capture) -> Option<graphics::Capture> {
+    if (actual.width, actual.height) != (reference.width, reference.height) {
+        // Every pixel is considered different.
+        return Some(graphics::Capture {
+            width: actual.width,
+            height: actual.height,
+            pixels: [u8::MAX, 0, 0, u8::MAX].repeat((actual.width * actual.height) as usize),
+        });
+    }
+
+    let mut mismatched = false;
+    let pixels = actual
+        .pixels
+        .chunks_exact(4)
+        .zip(reference.pixels.chunks_exact(4))
+        .flat_map(|(actual, reference)| {
+            let difference = actual
+                .iter()
+                .zip(reference)
+                .map(|(a, b)| a.abs_diff(*b))
+                .max()
+                .unwrap();
+
+            if difference > PIXEL_TOLERANCE {
+                mismatched = true;
+                [u8::MAX, 0, 0, u8::MAX]
+            } else {
+                // Matching pixels are shown dimmed, so that differences stand out.
+                let luminance =
+                    ((reference[0] as u32 + reference[1] as u32 + reference[2] as u32) / 12) as u8;
+                [luminance, luminance, luminance, u8::MAX]
+            }
+        })
+        .collect();
+
+    mismatched.then_some(graphics::Capture {
+        width: actual.width,
+        height: actual.height,
+        pixels,
+    })
+}
+
+fn load_png(path: &std::path::Path) -> graphics::Capture {
+    let image = image::open(path).unwrap().into_rgba8();
+
+    graphics::Capture {
+        width: image.width(),
+        height: image.height(),
+        pixels: image.into_raw(),
+    }
+}
+
+fn golden_directory() -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+fn output_directory() -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("golden")
+}
+
+#[test]
+#[ignore = "needs a software adapter, run with `cargo test -- --ignored`"]
+fn renders_helmets_with_pbr_and_skybox() {
+    run(GoldenCase {
+        name: "pbr_skybox",
+        camera: ecs::resource::Camera {
+            position: (-1.2, 0.6, 1.2).into(),
+            target: (0.0, 0.3, 0.0).into(),
+            ..Default::default()
+        },
+        frame_count: 3,
+    });
+}
+
+#[test]
+#[ignore = "needs a software adapter, run with `cargo test -- --ignored`"]
+fn renders_skybox_when_every_instance_is_culled() {
+    run(GoldenCase {
+        name: "skybox_only",
+        camera: ecs::resource::Camera {
+            position: (0.0, 0.3, 2.0).into(),
+            target: (0.0, 0.3, 5.0).into(),
+            ..Default::default()
+        },
+        frame_count: 3,
+    });
+}
+
+#[test]
+#[ignore = "needs a software adapter, run with `cargo test -- --ignored`"]
+fn keeps_instances_straddling_the_frustum_edges() {
+    // The outer helmets are cut by the left and right planes, so culling them wrongly would leave
+    // visible gaps at the image borders.
+    run(GoldenCase {
+        name: "frustum_culling_edges",
+        camera: ecs::resource::Camera {
+            position: (0.0, 0.3, 1.0).into(),
+            target: (0.0, 0.3, 0.0).into(),
+            ..Default::default()
+        },
+        frame_count: 3,
+    });
+}
+
+#[test]
+#[ignore = "needs a software adapter, run with `cargo test -- --ignored`"]
+fn tonemaps_with_agx_and_raised_exposure() {
+    run(GoldenCase {
+        name: "tonemapping_agx",
+        camera: ecs::resource::Camera {
+            position: (-1.2, 0.6, 1.2).into(),
+            target: (0.0, 0.3, 0.0).into(),
+            exposure: 1.0,
+            tonemapper: ecs::resource::camera::Tonemapper::AgX,
+            ..Default::default()
+        },
+        frame_count: 3,
+    });
+}
+
+#[test]
+#[ignore = "needs a software adapter, run with `cargo test -- --ignored`"]
+fn tonemaps_with_reinhard_and_lowered_exposure() {
+    run(GoldenCase {
+        name: "tonemapping_reinhard",
+        camera: ecs::resource::Camera {
+            position: (-1.2, 0.6, 1.2).into(),
+            target: (0.0, 0.3, 0.0).into(),
+            exposure: -1.0,
+            tonemapper: ecs::resource::camera::Tonemapper::Reinhard,
+            ..Default::default()
+        },
+        frame_count: 3,
+    });
+}
+
+#[test]
+fn diff_highlights_pixels_outside_tolerance() {
+    let reference = graphics::Capture {
+        width: 2,
+        height: 1,
+        pixels: vec![100, 100, 100, 255, 200, 200, 200, 255],
+    };
+
+    let within_tolerance = graphics::Capture {
+        pixels: vec![100 + PIXEL_TOLERANCE, 100, 100, 255, 200, 200, 200, 255],
+        ..reference.clone()
+    };
+    assert!(diff(&within_tolerance, &reference).is_none());
+
+    let outside_tolerance = graphics::Capture {
+        pixels: vec![100, 100, 100, 255, 200, 200, 200 - PIXEL_TOLERANCE - 1, 255],
+        ..reference.clone()
+    };
+    let diff = diff(&outside_tolerance, &reference).unwrap();
+    assert_eq!(diff.pixels[..4], [25, 25, 25, 255]);
+    assert_eq!(diff.pixels[4..], [255, 0, 0, 255]);
+}
@@ -0,0 +1,735 @@
+use crate::asset;
+use crate::graphics;
+
+use bevy_ecs::entity::Entity;
+use std::collections::{HashMap, HashSet};
+
+#[derive(bevy_ecs::system::Resource, Default)]
+pub struct ExtractedInstances {
+    pub changed: Vec<ExtractedInstance>,
+    pub removed: Vec<Entity>,
+}
+
+pub struct ExtractedInstance {
+    pub entity: Entity,
+    pub mesh_id: asset::MeshId,
+    pub material_id: asset::MaterialId,
+    pub transform: [f32; 16],
+}
+
+// Instances are packed densely into slots. Removing an instance moves the last slot into the freed
+// one, so that only the moved slot needs to be uploaded again.
+//
+// Opaque and alpha masked instances are batched per mesh, in groups of single-sided opaque,
+// single-sided masked, double-sided opaque and double-sided masked ones. Alpha blended instances
+// follow with one draw command each, sorted back to front every frame.
+#[derive(bevy_ecs::system::Resource)]
+pub struct InstanceBuffers {
+    slots: HashMap<Entity, usize>,
+    entities: Vec<Entity>,
+    culling_information:
+        Vec<graphics::pipeline::compute::frustum_culling::InstanceCullingInformation>,
+    transforms: Vec<[f32; 16]>,
+    materials: Vec<u32>,
+    dirty_slots: Vec<usize>,
+    mesh_draw_commands: Vec<wgpu::util::DrawIndexedIndirectArgs>,
+    mesh_centers: Vec<glam::Vec3>,
+    materials_alpha_blended: Vec<bool>,
+    materials_alpha_masked: Vec<bool>,
+    materials_double_sided: Vec<bool>,
+    batch_instance_counts: Vec<u32>,
+    transparent_entities: HashSet<Entity>,
+    // Slots of the alpha blended instances, back to front.
+    transparent_slots: Vec<usize>,
+    indirect_draw_commands: Vec<wgpu::util::DrawIndexedIndirectArgs>,
+    capacity: usize,
+    transparent_capacity: usize,
+    // Whether the device draws several indirect commands at once, otherwise they are drawn one by
+    // one.
+    multi_draw_indirect: bool,
+    pub instance_culling_information_buffer: wgpu::Buffer,
+    pub instance_transforms_buffer: wgpu::Buffer,
+    pub instance_materials_buffer: wgpu::Buffer,
+    pub indirect_instances_buffer: wgpu::Buffer,
+    pub indirect_draw_commands_buffer: wgpu::Buffer,
+    pub instance_count_buffer: wgpu::Buffer,
+    // Shadow views have their own indirect draws, see `SHADOW_DRAW_LIST_COUNT`.
+    pub shadow_indirect_instances_buffers: Vec<wgpu::Buffer>,
+    pub shadow_indirect_draw_commands_buffers: Vec<wgpu::Buffer>,
+}
+
+/// The first shadow draw list is shared by the shadow atlas views and holds every instance. The
+/// others are culled against one point shadow cube face each.
+pub const SHADOW_DRAW_LIST_COUNT: usize =
+    1 + graphics::pipeline::render::shadow::POINT_SHADOW_FACE_COUNT;
+
+const OPAQUE_BATCH_GROUP_COUNT: usize = 4;
+
+impl InstanceBuffers {
+    const INITIAL_CAPACITY: usize = 1024;
+    const INITIAL_TRANSPARENT_CAPACITY: usize = 64;
+
+    pub fn new(
+        device: &wgpu::Device,
+        meshes: &[graphics::Mesh],
+        bounding_boxes: &[graphics::BoundingBox],
+        materials: &[graphics::Material],
+    ) -> Self {
+        let capacity = Self::INITIAL_CAPACITY;
+        let transparent_capacity = Self::INITIAL_TRANSPARENT_CAPACITY;
+        let indirect_draw_command_capacity =
+            OPAQUE_BATCH_GROUP_COUNT * meshes.len() + transparent_capacity;
+
+        Self {
+            slots: HashMap::new(),
+            entities: Vec::new(),
+            culling_information: Vec::new(),
+            transforms: Vec::new(),
+            materials: Vec::new(),
+            dirty_slots: Vec::new(),
+            mesh_draw_commands: meshes
+                .iter()
+                .map(|mesh| wgpu::util::DrawIndexedIndirectArgs {
+                    index_count: mesh.index_count,
+                    instance_count: 0,
+                    first_index: mesh.index_offset,
+                    base_vertex: mesh.vertex_offset as i32,
+                    first_instance: 0,
+                })
+                .collect(),
+            mesh_centers: bounding_boxes
+                .iter()
+                .map(|bounding_box| {
+                    (glam::Vec3::from(bounding_box.min) + glam::Vec3::from(bounding_box.max)) * 0.5
+                })
+                .collect(),
+            materials_alpha_blended: materials
+                .iter()
+                .map(graphics::Material::is_alpha_blended)
+                .collect(),
+            materials_alpha_masked: materials
+                .iter()
+                .map(graphics::Material::is_alpha_masked)
+                .collect(),
+            materials_double_sided: materials
+                .iter()
+                .map(graphics::Material::is_double_sided)
+                .collect(),
+            batch_instance_counts: vec![0; OPAQUE_BATCH_GROUP_COUNT * meshes.len()],
+            transparent_entities: HashSet::new(),
+            transparent_slots: Vec::new(),
+            indirect_draw_commands: Vec::new(),
+            multi_draw_indirect: device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT),
+            capacity,
+            transparent_capacity,
+            instance_culling_information_buffer:
+                graphics::pipeline::compute::frustum_culling::create_instance_culling_information_buffer(
+                    device, capacity,
+                ),
+            instance_transforms_buffer:
+                graphics::pipeline::render::pbr::create_instance_transforms_buffer(
+                    device, capacity,
+                ),
+            instance_materials_buffer:
+                graphics::pipeline::render::pbr::create_instance_materials_buffer(device, capacity),
+            indirect_instances_buffer:
+                graphics::pipeline::compute::frustum_culling::create_indirect_instances_buffer(
+                    device, capacity,
+                ),
+            indirect_draw_commands_buffer:
+                graphics::pipeline::compute::frustum_culling::create_indirect_draw_commands_buffer(
+                    device,
+                    indirect_draw_command_capacity,
+                ),
+            instance_count_buffer:
+                graphics::pipeline::compute::frustum_culling::create_instance_count_buffer(
+                    device, 0,
+                ),
+            shadow_indirect_instances_buffers: (0..SHADOW_DRAW_LIST_COUNT)
+                .map(|_| {
+                    graphics::pipeline::compute::frustum_culling::create_indirect_instances_buffer(
+                        device, capacity,
+                    )
+                })
+                .collect(),
+            shadow_indirect_draw_commands_buffers: (0..SHADOW_DRAW_LIST_COUNT)
+                .map(|_| {
+                    graphics::pipeline::compute::frustum_culling::create_indirect_draw_commands_buffer(
+                        device,
+                        indirect_draw_command_capacity,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Draw commands of the opaque and alpha masked instances, either single-sided or
+    /// double-sided.
+    pub fn opaque_draw_commands(&self, double_sided: bool) -> std::ops::Range<u32> {
+        opaque_draw_commands(self.mesh_draw_commands.len() as u32, double_sided)
+    }
+
+    /// Draw commands of the alpha blended instances, back to front, split into runs of the same
+    /// sidedness.
+    pub fn transparent_draw_command_runs(&self) -> Vec<(std::ops::Range<u32>, bool)> {
+        let first_command = (OPAQUE_BATCH_GROUP_COUNT * self.mesh_draw_commands.len()) as u32;
+        let mut runs: Vec<(std::ops::Range<u32>, bool)> = Vec::new();
+
+        for (index, &slot) in self.transparent_slots.iter().enumerate() {
+            let command = first_command + index as u32;
+            let double_sided = self.materials_double_sided[self.materials[slot] as usize];
+            match runs.last_mut() {
+                Some((range, run_double_sided)) if *run_double_sided == double_sided => {
+                    range.end = command + 1;
+                }
+                _ => runs.push((command..command + 1, double_sided)),
+            }
+        }
+
+        runs
+    }
+
+    /// Draws a range of the main draw commands.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, draw_commands: std::ops::Range<u32>) {
+        self.draw_indirect(
+            render_pass,
+            &self.indirect_draw_commands_buffer,
+            draw_commands,
+        );
+    }
+
+    /// Draws either the opaque or the alpha masked commands of a shadow draw list. Alpha blended
+    /// instances cast no shadow.
+    pub fn draw_shadows(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        draw_list: usize,
+        alpha_masked: bool,
+    ) {
+        for draw_commands in
+            shadow_draw_commands(self.mesh_draw_commands.len() as u32, alpha_masked)
+        {
+            self.draw_indirect(
+                render_pass,
+                &self.shadow_indirect_draw_commands_buffers[draw_list],
+                draw_commands,
+            );
+        }
+    }
+
+    fn draw_indirect(
+        &self,
+        render_pass: &mut wgpu::RenderPass,
+        indirect_draw_commands_buffer: &wgpu::Buffer,
+        draw_commands: std::ops::Range<u32>,
+    ) {
+        if draw_commands.is_empty() {
+            return;
+        }
+
+        let stride =
+            std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() as wgpu::BufferAddress;
+        if self.multi_draw_indirect {
+            render_pass.multi_draw_indexed_indirect(
+                indirect_draw_commands_buffer,
+                draw_commands.start as wgpu::BufferAddress * stride,
+                draw_commands.len() as u32,
+            );
+        } else {
+            for draw_command in draw_commands {
+                render_pass.draw_indexed_indirect(
+                    indirect_draw_commands_buffer,
+                    draw_command as wgpu::BufferAddress * stride,
+                );
+            }
+        }
+    }
+
+    /// Applies the instances extracted from the main world and uploads the slots that changed.
+    /// Returns `true` if the instance or draw command buffers were reallocated, in which case the
+    /// bind groups referencing them must be recreated.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        extracted_instances: &ExtractedInstances,
+        camera_position: glam::Vec3,
+    ) -> bool {
+        for entity in &extracted_instances.removed {
+            self.remove(*entity);
+        }
+
+        for extracted_instance in &extracted_instances.changed {
+            self.insert(extracted_instance);
+        }
+
+        self.sort_transparent(camera_position);
+
+        let reallocated = self.reserve(device) | self.reserve_draw_commands(device);
+
+        self.upload_dirty_slots(queue);
+
+        queue.write_buffer(
+            &self.instance_count_buffer,
+            0,
+            bytemuck::cast_slice(&[self.len() as u32]),
+        );
+
+        // Instance counts are reset every frame, as the frustum culling pass accumulates into them.
+        let mesh_count = self.mesh_draw_commands.len();
+        let mut cumulative_count = 0;
+        self.indirect_draw_commands.clear();
+        for (batch_id, &batch_instance_count) in self.batch_instance_counts.iter().enumerate() {
+            self.indirect_draw_commands
+                .push(wgpu::util::DrawIndexedIndirectArgs {
+                    first_instance: cumulative_count,
+                    ..self.mesh_draw_commands[batch_id % mesh_count]
+                });
+            cumulative_count += batch_instance_count;
+        }
+        for &slot in &self.transparent_slots {
+            self.indirect_draw_commands
+                .push(wgpu::util::DrawIndexedIndirectArgs {
+                    first_instance: cumulative_count,
+                    ..self.mesh_draw_commands[self.culling_information[slot].mesh_id as usize]
+                });
+            cumulative_count += 1;
+        }
+        let indirect_draw_commands = self
+            .indirect_draw_commands
+            .iter()
+            .flat_map(|cmd| cmd.as_bytes())
+            .copied()
+            .collect::<Vec<_>>();
+        queue.write_buffer(
+            &self.indirect_draw_commands_buffer,
+            0,
+            &indirect_draw_commands,
+        );
+        for shadow_indirect_draw_commands_buffer in &self.shadow_indirect_draw_commands_buffers {
+            queue.write_buffer(
+                shadow_indirect_draw_commands_buffer,
+                0,
+                &indirect_draw_commands,
+            );
+        }
+
+        reallocated
+    }
+
+    pub fn create_bind_group_variable(
+        &self,
+        device: &wgpu::Device,
+        render_pipeline_pbr: &graphics::pipeline::render::Pbr,
+        camera_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        render_pipeline_pbr.create_bind_group_variable(
+            device,
+            camera_buffer.as_entire_binding(),
+            self.instance_transforms_buffer.as_entire_binding(),
+            self.indirect_instances_buffer.as_entire_binding(),
+            self.instance_materials_buffer.as_entire_binding(),
+        )
+    }
+
+    pub fn create_bind_group_frustum_culling(
+        &self,
+        device: &wgpu::Device,
+        compute_pipeline_frustum_culling: &graphics::pipeline::compute::FrustumCulling,
+        bounding_boxes_buffer: &wgpu::Buffer,
+        frustum_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        compute_pipeline_frustum_culling.create_bind_group_frustum_culling(
+            device,
+            bounding_boxes_buffer.as_entire_binding(),
+            self.instance_culling_information_buffer.as_entire_binding(),
+            self.indirect_draw_commands_buffer.as_entire_binding(),
+            self.indirect_instances_buffer.as_entire_binding(),
+            frustum_buffer.as_entire_binding(),
+            self.instance_count_buffer.as_entire_binding(),
+            self.instance_transforms_buffer.as_entire_binding(),
+        )
+    }
+
+    /// Creates one bind group per shadow draw list, culling the shadow casters against the
+    /// matching frustum in `shadow_frustum_buffers`.
+    pub fn create_bind_groups_shadow_frustum_culling(
+        &self,
+        device: &wgpu::Device,
+        compute_pipeline_frustum_culling: &graphics::pipeline::compute::FrustumCulling,
+        bounding_boxes_buffer: &wgpu::Buffer,
+        shadow_frustum_buffers: &[wgpu::Buffer],
+    ) -> Vec<wgpu::BindGroup> {
+        self.shadow_indirect_draw_commands_buffers
+            .iter()
+            .zip(&self.shadow_indirect_instances_buffers)
+            .zip(shadow_frustum_buffers)
+            .map(
+                |(
+                    (shadow_indirect_draw_commands_buffer, shadow_indirect_instances_buffer),
+                    shadow_frustum_buffer,
+                )| {
+                    compute_pipeline_frustum_culling.create_bind_group_frustum_culling(
+                        device,
+                        bounding_boxes_buffer.as_entire_binding(),
+                        self.instance_culling_information_buffer.as_entire_binding(),
+                        shadow_indirect_draw_commands_buffer.as_entire_binding(),
+                        shadow_indirect_instances_buffer.as_entire_binding(),
+                        shadow_frustum_buffer.as_entire_binding(),
+                        self.instance_count_buffer.as_entire_binding(),
+                        self.instance_transforms_buffer.as_entire_binding(),
+                    )
+                },
+            )
+            .collect()
+    }
+
+    /// Creates one bind group per shadow draw list.
+    pub fn create_bind_groups_shadow(
+        &self,
+        device: &wgpu::Device,
+        render_pipeline_shadow: &graphics::pipeline::render::Shadow,
+        shadow_pass_views_buffer: &wgpu::Buffer,
+    ) -> Vec<wgpu::BindGroup> {
+        self.shadow_indirect_instances_buffers
+            .iter()
+            .map(|shadow_indirect_instances_buffer| {
+                render_pipeline_shadow.create_bind_group_shadow(
+                    device,
+                    shadow_pass_views_buffer,
+                    self.instance_transforms_buffer.as_entire_binding(),
+                    shadow_indirect_instances_buffer.as_entire_binding(),
+                )
+            })
+            .collect()
+    }
+
+    /// Creates one bind group per shadow draw list, for the alpha masked shadow casters.
+    pub fn create_bind_groups_shadow_masked(
+        &self,
+        device: &wgpu::Device,
+        render_pipeline_pbr: &graphics::pipeline::render::Pbr,
+        shadow_pass_views_buffer: &wgpu::Buffer,
+    ) -> Vec<wgpu::BindGroup> {
+        self.shadow_indirect_instances_buffers
+            .iter()
+            .map(|shadow_indirect_instances_buffer| {
+                render_pipeline_pbr.create_bind_group_shadow_masked(
+                    device,
+                    shadow_pass_views_buffer,
+                    self.instance_transforms_buffer.as_entire_binding(),
+                    shadow_indirect_instances_buffer.as_entire_binding(),
+                    self.instance_materials_buffer.as_entire_binding(),
+                )
+            })
+            .collect()
+    }
+
+    // Alpha blended instances are assigned their batch when sorted.
+    fn opaque_batch_id(&self, mesh_id: asset::MeshId, material_id: asset::MaterialId) -> u32 {
+        opaque_batch_id(
+            self.mesh_draw_commands.len() as u32,
+            mesh_id,
+            self.materials_alpha_masked[material_id as usize],
+            self.materials_double_sided[material_id as usize],
+        )
+    }
+
+    fn add_to_batch(&mut self, slot: usize) {
+        let material_id = self.materials[slot];
+        if self.materials_alpha_blended[material_id as usize] {
+            self.transparent_entities.insert(self.entities[slot]);
+        } else {
+            let batch_id =
+                self.opaque_batch_id(self.culling_information[slot].mesh_id, material_id);
+            self.batch_instance_counts[batch_id as usize] += 1;
+        }
+    }
+
+    fn remove_from_batch(&mut self, slot: usize) {
+        let material_id = self.materials[slot];
+        if self.materials_alpha_blended[material_id as usize] {
+            self.transparent_entities.remove(&self.entities[slot]);
+        } else {
+            let batch_id =
+                self.opaque_batch_id(self.culling_information[slot].mesh_id, material_id);
+            self.batch_instance_counts[batch_id as usize] -= 1;
+        }
+    }
+
+    fn insert(&mut self, extracted_instance: &ExtractedInstance) {
+        let culling_information =
+            graphics::pipeline::compute::frustum_culling::InstanceCullingInformation {
+                batch_id: self
+                    .opaque_batch_id(extracted_instance.mesh_id, extracted_instance.material_id),
+                mesh_id: extracted_instance.mesh_id,
+            };
+
+        let slot = if let Some(&slot) = self.slots.get(&extracted_instance.entity) {
+            self.remove_from_batch(slot);
+            self.culling_information[slot] = culling_information;
+            self.transforms[slot] = extracted_instance.transform;
+            self.materials[slot] = extracted_instance.material_id;
+            slot
+        } else {
+            let slot = self.entities.len();
+            self.slots.insert(extracted_instance.entity, slot);
+            self.entities.push(extracted_instance.entity);
+            self.culling_information.push(culling_information);
+            self.transforms.push(extracted_instance.transform);
+            self.materials.push(extracted_instance.material_id);
+            slot
+        };
+
+        self.add_to_batch(slot);
+        self.dirty_slots.push(slot);
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        let Some(&slot) = self.slots.get(&entity) else {
+            return;
+        };
+
+        self.remove_from_batch(slot);
+        self.slots.remove(&entity);
+
+        self.entities.swap_remove(slot);
+        self.culling_information.swap_remove(slot);
+        self.transforms.swap_remove(slot);
+        self.materials.swap_remove(slot);
+
+        if let Some(&moved_entity) = self.entities.get(slot) {
+            self.slots.insert(moved_entity, slot);
+            self.dirty_slots.push(slot);
+        }
+    }
+
+    /// Orders the alpha blended instances from the farthest to the closest to the camera, and
+    /// assigns them the batch of their position.
+    fn sort_transparent(&mut self, camera_position: glam::Vec3) {
+        let mut transparent_slots = self
+            .transparent_entities
+            .iter()
+            .map(|entity| {
+                let slot = self.slots[entity];
+                let center = glam::Mat4::from_cols_array(&self.transforms[slot]).transform_point3(
+                    self.mesh_centers[self.culling_information[slot].mesh_id as usize],
+                );
+                (center.distance_squared(camera_position), slot)
+            })
+            .collect::<Vec<_>>();
+        transparent_slots.sort_unstable_by(|(distance_a, slot_a), (distance_b, slot_b)| {
+            distance_b.total_cmp(distance_a).then(slot_a.cmp(slot_b))
+        });
+
+        let first_batch_id = (OPAQUE_BATCH_GROUP_COUNT * self.mesh_draw_commands.len()) as u32;
+        self.transparent_slots.clear();
+        for (index, (_, slot)) in transparent_slots.into_iter().enumerate() {
+            let batch_id = first_batch_id + index as u32;
+            if self.culling_information[slot].batch_id != batch_id {
+                self.culling_information[slot].batch_id = batch_id;
+                self.dirty_slots.push(slot);
+            }
+            self.transparent_slots.push(slot);
+        }
+    }
+
+    fn reserve_draw_commands(&mut self, device: &wgpu::Device) -> bool {
+        if self.transparent_slots.len() <= self.transparent_capacity {
+            return false;
+        }
+
+        self.transparent_capacity = self.transparent_slots.len().next_power_of_two();
+        let indirect_draw_command_capacity =
+            OPAQUE_BATCH_GROUP_COUNT * self.mesh_draw_commands.len() + self.transparent_capacity;
+
+        self.indirect_draw_commands_buffer =
+            graphics::pipeline::compute::frustum_culling::create_indirect_draw_commands_buffer(
+                device,
+                indirect_draw_command_capacity,
+            );
+        for shadow_indirect_draw_commands_buffer in &mut self.shadow_indirect_draw_commands_buffers
+        {
+            *shadow_indirect_draw_commands_buffer =
+                graphics::pipeline::compute::frustum_culling::create_indirect_draw_commands_buffer(
+                    device,
+                    indirect_draw_command_capacity,
+                );
+        }
+
+        true
+    }
+
+    fn reserve(&mut self, device: &wgpu::Device) -> bool {
+        if self.len() <= self.capacity {
+            return false;
+        }
+
+        self.capacity = self.len().next_power_of_two();
+
+        self.instance_culling_information_buffer =
+            graphics::pipeline::compute::frustum_culling::create_instance_culling_information_buffer(
+                device,
+                self.capacity,
+            );
+        self.instance_transforms_buffer =
+            graphics::pipeline::render::pbr::create_instance_transforms_buffer(
+                device,
+                self.capacity,
+            );
+        self.instance_materials_buffer =
+            graphics::pipeline::render::pbr::create_instance_materials_buffer(
+                device,
+                self.capacity,
+            );
+        self.indirect_instances_buffer =
+            graphics::pipeline::compute::frustum_culling::create_indirect_instances_buffer(
+                device,
+                self.capacity,
+            );
+        for shadow_indirect_instances_buffer in &mut self.shadow_indirect_instances_buffers {
+            *shadow_indirect_instances_buffer =
+                graphics::pipeline::compute::frustum_culling::create_indirect_instances_buffer(
+                    device,
+                    self.capacity,
+                );
+        }
+
+        // The new buffers are empty, so every slot has to be uploaded.
+        self.dirty_slots = (0..self.len()).collect();
+
+        true
+    }
+
+    fn upload_dirty_slots(&mut self, queue: &wgpu::Queue) {
+        self.dirty_slots.retain(|&slot| slot < self.entities.len());
+        self.dirty_slots.sort_unstable();
+        self.dirty_slots.dedup();
+
+        // Contiguous dirty slots are uploaded with a single write per buffer.
+        for range in self
+            .dirty_slots
+            .chunk_by(|&previous, &next| previous + 1 == next)
+            .map(|slots| slots[0]..(slots[slots.len() - 1] + 1))
+        {
+            write_slots(
+                queue,
+                &self.instance_culling_information_buffer,
+                &self.culling_information,
+                range.clone(),
+            );
+            write_slots(
+                queue,
+                &self.instance_transforms_buffer,
+                &self.transforms,
+                range.clone(),
+            );
+            write_slots(
+                queue,
+                &self.instance_materials_buffer,
+                &self.materials,
+                range,
+            );
+        }
+
+        self.dirty_slots.clear();
+    }
+}
+
+fn opaque_batch_id(
+    mesh_count: u32,
+    mesh_id: asset::MeshId,
+    alpha_masked: bool,
+    double_sided: bool,
+) -> u32 {
+    let group = 2 * double_sided as u32 + alpha_masked as u32;
+    group * mesh_count + mesh_id
+}
+
+fn opaque_draw_commands(mesh_count: u32, double_sided: bool) -> std::ops::Range<u32> {
+    if double_sided {
+        2 * mesh_count..4 * mesh_count
+    } else {
+        0..2 * mesh_count
+    }
+}
+
+// The opaque or alpha masked batches of both sidedness, shadows being drawn without culling.
+fn shadow_draw_commands(mesh_count: u32, alpha_masked: bool) -> [std::ops::Range<u32>; 2] {
+    let first_command = alpha_masked as u32 * mesh_count;
+    [
+        first_command..first_command + mesh_count,
+        2 * mesh_count + first_command..3 * mesh_count + first_command,
+    ]
+}
+
+fn write_slots<T: bytemuck::Pod>(
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    data: &[T],
+    range: std::ops::Range<usize>,
+) {
+    queue.write_buffer(
+        buffer,
+        (range.start * std::mem::size_of::<T>()) as wgpu::BufferAddress,
+        bytemuck::cast_slice(&data[range]),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadow_draw_lists_leave_out_alpha_blended_batches() {
+        let mesh_count = 3;
+        let in_ranges = |ranges: &[std::ops::Range<u32>], batch_id| {
+            ranges.iter().any(|range| range.contains(&batch_id))
+        };
+
+        for mesh_id in 0..mesh_count {
+            for alpha_masked in [false, true] {
+                for double_sided in [false, true] {
+                    let batch_id = opaque_batch_id(mesh_count, mesh_id, alpha_masked, double_sided);
+                    assert_eq!(batch_id % mesh_count, mesh_id);
+                    assert!(opaque_draw_commands(mesh_count, double_sided).contains(&batch_id));
+                    assert!(!opaque_draw_commands(mesh_count, !double_sided).contains(&batch_id));
+                    assert!(in_ranges(
+                        &shadow_draw_commands(mesh_count, alpha_masked),
+                        batch_id
+                    ));
+                    assert!(!in_ranges(
+                        &shadow_draw_commands(mesh_count, !alpha_masked),
+                        batch_id
+                    ));
+                }
+            }
+        }
+
+        // Both the main pass and the shadow passes cover every opaque and masked batch once, the
+        // alpha blended draw commands following them.
+        let first_transparent_command = OPAQUE_BATCH_GROUP_COUNT as u32 * mesh_count;
+        let mut main_commands = [false, true]
+            .into_iter()
+            .flat_map(|double_sided| opaque_draw_commands(mesh_count, double_sided))
+            .collect::<Vec<_>>();
+        let mut shadow_commands = [false, true]
+            .into_iter()
+            .flat_map(|alpha_masked| shadow_draw_commands(mesh_count, alpha_masked))
+            .flatten()
+            .collect::<Vec<_>>();
+        main_commands.sort_unstable();
+        shadow_commands.sort_unstable();
+        assert_eq!(
+            main_commands,
+            (0..first_transparent_command).collect::<Vec<_>>()
+        );
+        assert_eq!(shadow_commands, main_commands);
+    }
+}
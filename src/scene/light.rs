@@ -0,0 +1,198 @@
+use super::shadow::ShadowMaps;
+use crate::graphics;
+
+use graphics::pipeline::render::pbr;
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct ExtractedLights {
+    pub ambient_light: pbr::AmbientLight,
+    pub point_lights: Vec<pbr::PointLight>,
+    // Whether each point light casts shadows.
+    pub point_light_shadows: Vec<bool>,
+    pub directional_lights: Vec<pbr::DirectionalLight>,
+    pub spot_lights: Vec<pbr::SpotLight>,
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct LightBuffers {
+    point_light_capacity: usize,
+    directional_light_capacity: usize,
+    spot_light_capacity: usize,
+    pub ambient_light_buffer: wgpu::Buffer,
+    pub point_lights_buffer: wgpu::Buffer,
+    pub lights_length_buffer: wgpu::Buffer,
+    pub directional_lights_buffer: wgpu::Buffer,
+    pub spot_lights_buffer: wgpu::Buffer,
+}
+
+impl LightBuffers {
+    const INITIAL_CAPACITY: usize = 16;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            point_light_capacity: Self::INITIAL_CAPACITY,
+            directional_light_capacity: Self::INITIAL_CAPACITY,
+            spot_light_capacity: Self::INITIAL_CAPACITY,
+            ambient_light_buffer: pbr::create_ambient_light_buffer(
+                device,
+                bytemuck::Zeroable::zeroed(),
+            ),
+            point_lights_buffer: pbr::create_point_lights_buffer(device, Self::INITIAL_CAPACITY),
+            lights_length_buffer: pbr::create_lights_length_buffer(
+                device,
+                bytemuck::Zeroable::zeroed(),
+            ),
+            directional_lights_buffer: pbr::create_directional_lights_buffer(
+                device,
+                Self::INITIAL_CAPACITY,
+            ),
+            spot_lights_buffer: pbr::create_spot_lights_buffer(device, Self::INITIAL_CAPACITY),
+        }
+    }
+
+    /// Uploads the lights extracted from the main world. Returns `true` if a light buffer was
+    /// reallocated, in which case the lights bind group must be recreated.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        extracted_lights: &ExtractedLights,
+    ) -> bool {
+        let mut reallocated = false;
+
+        if extracted_lights.point_lights.len() > self.point_light_capacity {
+            self.point_light_capacity = extracted_lights.point_lights.len().next_power_of_two();
+            self.point_lights_buffer =
+                pbr::create_point_lights_buffer(device, self.point_light_capacity);
+            reallocated = true;
+        }
+
+        if extracted_lights.directional_lights.len() > self.directional_light_capacity {
+            self.directional_light_capacity = extracted_lights
+                .directional_lights
+                .len()
+                .next_power_of_two();
+            self.directional_lights_buffer =
+                pbr::create_directional_lights_buffer(device, self.directional_light_capacity);
+            reallocated = true;
+        }
+
+        if extracted_lights.spot_lights.len() > self.spot_light_capacity {
+            self.spot_light_capacity = extracted_lights.spot_lights.len().next_power_of_two();
+            self.spot_lights_buffer =
+                pbr::create_spot_lights_buffer(device, self.spot_light_capacity);
+            reallocated = true;
+        }
+
+        queue.write_buffer(
+            &self.ambient_light_buffer,
+            0,
+            bytemuck::cast_slice(&[extracted_lights.ambient_light]),
+        );
+        queue.write_buffer(
+            &self.point_lights_buffer,
+            0,
+            bytemuck::cast_slice(&extracted_lights.point_lights),
+        );
+        queue.write_buffer(
+            &self.directional_lights_buffer,
+            0,
+            bytemuck::cast_slice(&extracted_lights.directional_lights),
+        );
+        queue.write_buffer(
+            &self.spot_lights_buffer,
+            0,
+            bytemuck::cast_slice(&extracted_lights.spot_lights),
+        );
+        queue.write_buffer(
+            &self.lights_length_buffer,
+            0,
+            bytemuck::cast_slice(&[pbr::LightsLength {
+                point_lights: extracted_lights.point_lights.len() as u32,
+                directional_lights: extracted_lights.directional_lights.len() as u32,
+                spot_lights: extracted_lights.spot_lights.len() as u32,
+                _padding: 0,
+            }]),
+        );
+
+        reallocated
+    }
+
+    pub fn create_bind_group_lights(
+        &self,
+        device: &wgpu::Device,
+        render_pipeline_pbr: &graphics::pipeline::render::Pbr,
+        shadow_maps: &ShadowMaps,
+    ) -> wgpu::BindGroup {
+        render_pipeline_pbr.create_bind_group_lights(
+            device,
+            self.ambient_light_buffer.as_entire_binding(),
+            self.point_lights_buffer.as_entire_binding(),
+            self.lights_length_buffer.as_entire_binding(),
+            self.directional_lights_buffer.as_entire_binding(),
+            self.spot_lights_buffer.as_entire_binding(),
+            shadow_maps.shadow_views_buffer.as_entire_binding(),
+            shadow_maps.shadow_cascades_buffer.as_entire_binding(),
+            wgpu::BindingResource::TextureView(&shadow_maps.shadow_atlas_view),
+            wgpu::BindingResource::Sampler(&shadow_maps.shadow_sampler),
+            wgpu::BindingResource::TextureView(&shadow_maps.point_shadow_maps_view),
+        )
+    }
+}
+
+/// Views of the image-based lighting maps, kept to recreate the environment bind group when the
+/// screen-space ambient occlusion it also holds is resized.
+#[derive(bevy_ecs::system::Resource)]
+pub struct EnvironmentLighting {
+    pub irradiance_view: wgpu::TextureView,
+    pub prefiltered_specular_view: wgpu::TextureView,
+    pub brdf_lut_view: wgpu::TextureView,
+    pub environment_sampler: wgpu::Sampler,
+}
+
+impl EnvironmentLighting {
+    pub fn new(
+        device: &wgpu::Device,
+        environment_maps: &graphics::pipeline::compute::image_based_lighting::EnvironmentMaps,
+    ) -> Self {
+        Self {
+            irradiance_view: graphics::pipeline::compute::image_based_lighting::create_cubemap_view(
+                &environment_maps.irradiance,
+                0,
+                None,
+            ),
+            prefiltered_specular_view:
+                graphics::pipeline::compute::image_based_lighting::create_cubemap_view(
+                    &environment_maps.prefiltered_specular,
+                    0,
+                    None,
+                ),
+            brdf_lut_view: environment_maps
+                .brdf_lut
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            environment_sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("texture_sampler_environment"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn create_bind_group_environment(
+        &self,
+        device: &wgpu::Device,
+        render_pipeline_pbr: &graphics::pipeline::render::Pbr,
+        ambient_occlusion_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        render_pipeline_pbr.create_bind_group_environment(
+            device,
+            wgpu::BindingResource::TextureView(&self.irradiance_view),
+            wgpu::BindingResource::TextureView(&self.prefiltered_specular_view),
+            wgpu::BindingResource::TextureView(&self.brdf_lut_view),
+            wgpu::BindingResource::Sampler(&self.environment_sampler),
+            wgpu::BindingResource::TextureView(ambient_occlusion_view),
+        )
+    }
+}
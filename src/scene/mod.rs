@@ -1,6 +1,17 @@
+#[allow(
+    clippy::module_inception,
+    reason = "the scene is defined in a file named after its directory"
+)]
 mod scene;
 pub use scene::*;
 
+mod ambient_occlusion;
+#[cfg(test)]
+mod golden;
+mod instance;
+mod light;
+mod post_processing;
 mod rendering;
 mod resource;
+mod shadow;
 mod simulation;
@@ -0,0 +1,115 @@
+use crate::graphics;
+
+use graphics::pipeline::render::{bloom, Bloom, Fxaa, Tonemapping};
+
+/// Intermediate targets of the post-processing chain, sized to the surface, and the bind groups
+/// reading them. Rebuilt whenever the surface is resized.
+#[derive(bevy_ecs::system::Resource)]
+pub struct PostProcessingTargets {
+    // Target of the main pass.
+    pub hdr_buffer: wgpu::TextureView,
+    pub bloom_mip_views: Vec<wgpu::TextureView>,
+    // Reads the HDR buffer, which is downsampled into the first bloom mip.
+    pub bind_group_bloom_hdr: wgpu::BindGroup,
+    // One per bloom mip, reading that mip.
+    pub bind_groups_bloom_mips: Vec<wgpu::BindGroup>,
+    // Tonemapped frame, read by FXAA.
+    pub ldr_buffer: wgpu::TextureView,
+    pub bind_group_tonemapping: wgpu::BindGroup,
+    pub bind_group_fxaa: wgpu::BindGroup,
+}
+
+impl PostProcessingTargets {
+    pub fn new(
+        gpu: &graphics::Gpu,
+        bloom_pipeline: &Bloom,
+        tonemapping_pipeline: &Tonemapping,
+        fxaa_pipeline: &Fxaa,
+        tonemapping_buffer: &wgpu::Buffer,
+        color_grading_lut_view: &wgpu::TextureView,
+    ) -> Self {
+        let device = &gpu.device;
+        let (width, height) = (gpu.config.width, gpu.config.height);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post_processing_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let hdr_buffer = graphics::gpu::create_hdr_buffer(device, width, height);
+
+        let bloom_texture = bloom::create_bloom_texture(device, width, height);
+        let bloom_mip_views = (0..bloom_texture.mip_level_count())
+            .map(|mip_level| {
+                bloom_texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("bloom_mip"),
+                    base_mip_level: mip_level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+        let bind_group_bloom_hdr = bloom_pipeline.create_bind_group_bloom(
+            device,
+            wgpu::BindingResource::TextureView(&hdr_buffer),
+            wgpu::BindingResource::Sampler(&sampler),
+        );
+        let bind_groups_bloom_mips = bloom_mip_views
+            .iter()
+            .map(|bloom_mip_view| {
+                bloom_pipeline.create_bind_group_bloom(
+                    device,
+                    wgpu::BindingResource::TextureView(bloom_mip_view),
+                    wgpu::BindingResource::Sampler(&sampler),
+                )
+            })
+            .collect();
+
+        let ldr_buffer = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("ldr_texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: gpu.config.format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group_tonemapping = tonemapping_pipeline.create_bind_group_tonemapping(
+            device,
+            wgpu::BindingResource::TextureView(&hdr_buffer),
+            tonemapping_buffer.as_entire_binding(),
+            wgpu::BindingResource::TextureView(&bloom_mip_views[0]),
+            wgpu::BindingResource::TextureView(color_grading_lut_view),
+            wgpu::BindingResource::Sampler(&sampler),
+        );
+        let bind_group_fxaa = fxaa_pipeline.create_bind_group_fxaa(
+            device,
+            wgpu::BindingResource::TextureView(&ldr_buffer),
+            wgpu::BindingResource::Sampler(&sampler),
+        );
+
+        Self {
+            hdr_buffer,
+            bloom_mip_views,
+            bind_group_bloom_hdr,
+            bind_groups_bloom_mips,
+            ldr_buffer,
+            bind_group_tonemapping,
+            bind_group_fxaa,
+        }
+    }
+}
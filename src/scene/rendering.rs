@@ -1,3 +1,5 @@
+use super::resource::FrameCapture;
+
 pub struct Renderer;
 
 impl Renderer {
@@ -16,6 +18,7 @@ impl Renderer {
             render_schedule.run(&mut render_world);
 
             render_world.clear_entities();
+            render_world.remove_resource::<FrameCapture>();
 
             let send_result = renderer_to_scene_sender.send(render_world);
             if send_result.is_err() {
@@ -38,42 +41,190 @@ mod schedule {
 }
 
 mod system {
-    use super::super::resource::*;
+    use super::super::{
+        ambient_occlusion::*, instance::*, light::*, post_processing::*, resource::*, shadow::*,
+    };
     use crate::ecs;
     use crate::graphics;
-    use bevy_ecs::change_detection::Res;
-    use bevy_ecs::system::Query;
-    use wgpu::util::DeviceExt;
+    use bevy_ecs::change_detection::{Res, ResMut};
 
+    #[allow(clippy::too_many_arguments)]
     pub fn render(
-        (gpu, camera, meshes): (
+        (gpu, camera, post_processing): (
             Res<graphics::Gpu<'static>>,
             Res<ecs::resource::Camera>,
-            Res<Meshes>,
+            Res<ecs::resource::PostProcessing>,
         ),
         (vertex_buffer, index_buffer, bounding_boxes_buffer): (
             Res<VertexBuffer>,
             Res<IndexBuffer>,
             Res<BoundingBoxesBuffer>,
         ),
-        (bind_group_bindless, bind_group_skybox): (Res<BindGroupBindless>, Res<BindGroupSkybox>),
-        (compute_pipeline_frustum_culling, render_pipeline_pbr, render_pipeline_skybox): (
+        (camera_buffer, frustum_buffer, inverse_view_projection_buffer, tonemapping_buffer): (
+            Res<CameraBuffer>,
+            Res<FrustumBuffer>,
+            Res<InverseViewProjectionBuffer>,
+            Res<TonemappingBuffer>,
+        ),
+        (mut instance_buffers, extracted_instances, mut light_buffers, mut extracted_lights): (
+            ResMut<InstanceBuffers>,
+            Res<ExtractedInstances>,
+            ResMut<LightBuffers>,
+            ResMut<ExtractedLights>,
+        ),
+        (mut bind_group_variable, mut bind_group_frustum_culling, bind_group_bindless): (
+            ResMut<BindGroupVariable>,
+            ResMut<BindGroupFrustumCulling>,
+            Res<BindGroupBindless>,
+        ),
+        (
+            mut bind_group_lights,
+            bind_group_environment,
+            bind_group_inverse_view_projection,
+            bind_group_skybox,
+        ): (
+            ResMut<BindGroupLights>,
+            Res<BindGroupEnvironment>,
+            Res<BindGroupInverseViewProjection>,
+            Res<BindGroupSkybox>,
+        ),
+        (
+            mut shadow_maps,
+            render_pipeline_shadow,
+            mut bind_groups_shadow,
+            mut bind_groups_shadow_frustum_culling,
+        ): (
+            ResMut<ShadowMaps>,
+            Res<RenderPipelineShadow>,
+            ResMut<BindGroupsShadow>,
+            ResMut<BindGroupsShadowFrustumCulling>,
+        ),
+        mut bind_groups_shadow_masked: ResMut<BindGroupsShadowMasked>,
+        (
+            compute_pipeline_frustum_culling,
+            compute_pipeline_ambient_occlusion,
+            render_pipeline_pbr,
+            render_pipeline_skybox,
+        ): (
             Res<ComputePipelineFrustumCulling>,
+            Res<ComputePipelineAmbientOcclusion>,
             Res<RenderPipelinePbr>,
             Res<RenderPipelineSkybox>,
         ),
-        (depth_buffer, msaa_buffer): (Res<DepthBuffer>, Res<MsaaBuffer>),
-        query: Query<(
-            &ecs::component::Mesh,
-            &ecs::component::Material,
-            &ecs::component::GlobalTransform,
-        )>,
+        (
+            render_pipeline_bloom,
+            render_pipeline_tonemapping,
+            render_pipeline_fxaa,
+            post_processing_targets,
+        ): (
+            Res<RenderPipelineBloom>,
+            Res<RenderPipelineTonemapping>,
+            Res<RenderPipelineFxaa>,
+            Res<PostProcessingTargets>,
+        ),
+        (depth_buffer, msaa_buffer, ambient_occlusion_buffer, ambient_occlusion_targets): (
+            Res<DepthBuffer>,
+            Res<MsaaBuffer>,
+            Res<AmbientOcclusionBuffer>,
+            Res<AmbientOcclusionTargets>,
+        ),
+        frame_capture: Option<Res<FrameCapture>>,
     ) {
-        let output = gpu.surface.get_current_texture().unwrap();
-        let output_texture = &output.texture;
+        let frame = gpu.current_frame();
+
+        let view_projection = camera.perspective() * camera.view_matrix();
+
+        gpu.queue.write_buffer(
+            &camera_buffer,
+            0,
+            bytemuck::cast_slice(&[graphics::pipeline::render::pbr::CameraMatrix {
+                view_projection: view_projection.to_cols_array(),
+                position: camera.position.extend(1.0).into(),
+            }]),
+        );
+
+        gpu.queue.write_buffer(
+            &frustum_buffer,
+            0,
+            bytemuck::cast_slice(&[ecs::resource::Frustum::from_view_projection_matrix(
+                &view_projection,
+            )]),
+        );
+
+        gpu.queue.write_buffer(
+            &inverse_view_projection_buffer,
+            0,
+            bytemuck::cast_slice(&view_projection.inverse().to_cols_array()),
+        );
+
+        gpu.queue.write_buffer(
+            &tonemapping_buffer,
+            0,
+            bytemuck::cast_slice(&[
+                graphics::pipeline::render::tonemapping::TonemappingSettings::new(
+                    &camera,
+                    &post_processing,
+                ),
+            ]),
+        );
+
+        gpu.queue.write_buffer(
+            &ambient_occlusion_buffer,
+            0,
+            bytemuck::cast_slice(&[
+                graphics::pipeline::compute::ambient_occlusion::AmbientOcclusionSettings::new(
+                    camera.perspective(),
+                ),
+            ]),
+        );
+
+        let reallocated = instance_buffers.update(
+            &gpu.device,
+            &gpu.queue,
+            &extracted_instances,
+            camera.position,
+        );
+        if reallocated {
+            **bind_group_variable = instance_buffers.create_bind_group_variable(
+                &gpu.device,
+                &render_pipeline_pbr,
+                &camera_buffer,
+            );
+            **bind_group_frustum_culling = instance_buffers.create_bind_group_frustum_culling(
+                &gpu.device,
+                &compute_pipeline_frustum_culling,
+                &bounding_boxes_buffer,
+                &frustum_buffer,
+            );
+            **bind_groups_shadow_frustum_culling = instance_buffers
+                .create_bind_groups_shadow_frustum_culling(
+                    &gpu.device,
+                    &compute_pipeline_frustum_culling,
+                    &bounding_boxes_buffer,
+                    &shadow_maps.shadow_frustum_buffers,
+                );
+            **bind_groups_shadow = instance_buffers.create_bind_groups_shadow(
+                &gpu.device,
+                &render_pipeline_shadow,
+                &shadow_maps.shadow_pass_views_buffer,
+            );
+            **bind_groups_shadow_masked = instance_buffers.create_bind_groups_shadow_masked(
+                &gpu.device,
+                &render_pipeline_pbr,
+                &shadow_maps.shadow_pass_views_buffer,
+            );
+        }
+
+        // Assigns the shadow views of the lights before they are uploaded.
+        shadow_maps.update(&gpu.queue, &camera, &mut extracted_lights);
 
-        let output_texture_view =
-            output_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        if light_buffers.update(&gpu.device, &gpu.queue, &extracted_lights) {
+            **bind_group_lights = light_buffers.create_bind_group_lights(
+                &gpu.device,
+                &render_pipeline_pbr,
+                &shadow_maps,
+            );
+        }
 
         let mut encoder = gpu
             .device
@@ -81,125 +232,120 @@ mod system {
                 label: Some("render_encoder"),
             });
 
-        let view_projection = camera.perspective() * camera.view_matrix();
-
-        let instances = query.iter();
-        let instances_len = instances.len();
-
-        let mut instance_culling_information = Vec::with_capacity(instances_len);
-        let mut instance_transforms = Vec::with_capacity(instances_len);
-        let mut instance_materials = Vec::with_capacity(instances_len);
-        let mut batches_map = std::collections::HashMap::new();
-        let mut batches: Vec<(u32, usize)> = Vec::new();
-        for (mesh, material, global_transform) in instances {
-            instance_transforms.push(glam::Mat4::from(**global_transform).to_cols_array());
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("compute_pass"),
+                timestamp_writes: None,
+            });
 
-            instance_materials.push(material.material_id);
+            compute_pipeline_frustum_culling
+                .prepare(&mut compute_pass, &bind_group_frustum_culling);
 
-            let batch_id = if let Some(&batch_id) = batches_map.get(&mesh.mesh_id) {
-                batches[batch_id as usize].1 += 1;
-                batch_id
-            } else {
-                let batch_id = batches_map.len() as u32;
-                batches_map.insert(mesh.mesh_id, batch_id);
-                batches.push((mesh.mesh_id, 1));
-                batch_id
-            };
+            compute_pass.dispatch_workgroups(instance_buffers.len().div_ceil(64) as u32, 1, 1);
 
-            instance_culling_information.push(
-                graphics::pipeline::compute::frustum_culling::InstanceCullingInformation {
-                    batch_id,
-                },
-            );
-        }
+            // The atlas draw list, followed by one draw list per point shadow face.
+            for bind_group_shadow_frustum_culling in bind_groups_shadow_frustum_culling
+                .iter()
+                .take(1 + shadow_maps.point_shadow_face_count() as usize)
+            {
+                compute_pipeline_frustum_culling
+                    .prepare(&mut compute_pass, bind_group_shadow_frustum_culling);
 
-        let mut indirect_draw_commands = Vec::with_capacity(batches.len());
-        let mut cumulative_count = 0;
-        for (mesh_id, instance_count) in batches {
-            let mesh = meshes[mesh_id as usize];
-            indirect_draw_commands.push(wgpu::util::DrawIndexedIndirectArgs {
-                index_count: mesh.index_count,
-                instance_count: 0,
-                first_index: mesh.index_offset,
-                base_vertex: mesh.vertex_offset as i32,
-                first_instance: cumulative_count,
-            });
-            cumulative_count += instance_count as u32;
+                compute_pass.dispatch_workgroups(instance_buffers.len().div_ceil(64) as u32, 1, 1);
+            }
         }
 
-        let indirect_draw_commands_buffer =
-            gpu.device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("indirect_draw_commands_buffer"),
-                    contents: &indirect_draw_commands
-                        .iter()
-                        .flat_map(|cmd| cmd.as_bytes())
-                        .copied()
-                        .collect::<Vec<_>>(),
-                    usage: wgpu::BufferUsages::STORAGE
-                        | wgpu::BufferUsages::COPY_DST
-                        | wgpu::BufferUsages::INDIRECT,
-                });
-
-        let indirect_instances_buffer =
-            graphics::pipeline::compute::frustum_culling::create_indirect_instances_buffer(
-                &gpu.device,
-                &vec![0; instances_len],
-            );
-
         {
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("compute_pass"),
+            let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("shadow_pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &shadow_maps.shadow_atlas_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
                 timestamp_writes: None,
             });
 
-            let instance_culling_information_buffer =
-                graphics::pipeline::compute::frustum_culling::create_instance_culling_information_buffer(
-                    &gpu.device,
-                    &instance_culling_information
+            for view_index in 0..shadow_maps.view_count() {
+                let (x, y) = ShadowMaps::tile_offset(view_index);
+                let tile_size = graphics::pipeline::render::shadow::SHADOW_TILE_SIZE;
+                shadow_pass.set_viewport(
+                    x as f32,
+                    y as f32,
+                    tile_size as f32,
+                    tile_size as f32,
+                    0.0,
+                    1.0,
                 );
 
-            let frustum = ecs::resource::Frustum::from_view_projection_matrix(&view_projection);
-            let frustum_buffer =
-                graphics::pipeline::compute::frustum_culling::create_frustum_buffer(
-                    &gpu.device,
-                    frustum,
+                render_pipeline_shadow.prepare(
+                    &mut shadow_pass,
+                    vertex_buffer.slice(..),
+                    index_buffer.slice(..),
+                    &bind_groups_shadow[0],
+                    view_index,
                 );
-
-            let instance_count_buffer =
-                graphics::pipeline::compute::frustum_culling::create_instance_count_buffer(
-                    &gpu.device,
-                    instances_len as u32,
+                instance_buffers.draw_shadows(&mut shadow_pass, 0, false);
+
+                render_pipeline_pbr.prepare_shadow_masked(
+                    &mut shadow_pass,
+                    vertex_buffer.slice(..),
+                    index_buffer.slice(..),
+                    &bind_groups_shadow_masked[0],
+                    &bind_group_bindless,
+                    view_index,
                 );
+                instance_buffers.draw_shadows(&mut shadow_pass, 0, true);
+            }
+        }
 
-            let bind_group_frustum_culling = compute_pipeline_frustum_culling
-                .create_bind_group_frustum_culling(
-                    &gpu.device,
-                    bounding_boxes_buffer.as_entire_binding(),
-                    instance_culling_information_buffer.as_entire_binding(),
-                    indirect_draw_commands_buffer.as_entire_binding(),
-                    indirect_instances_buffer.as_entire_binding(),
-                    frustum_buffer.as_entire_binding(),
-                    instance_count_buffer.as_entire_binding(),
-                );
+        for face in 0..shadow_maps.point_shadow_face_count() {
+            let draw_list = 1 + face as usize;
 
-            compute_pipeline_frustum_culling
-                .prepare(&mut compute_pass, &bind_group_frustum_culling);
+            let mut point_shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("point_shadow_pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &shadow_maps.point_shadow_face_views[face as usize],
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pipeline_shadow.prepare(
+                &mut point_shadow_pass,
+                vertex_buffer.slice(..),
+                index_buffer.slice(..),
+                &bind_groups_shadow[draw_list],
+                ShadowMaps::point_shadow_pass_view_index(face),
+            );
+            instance_buffers.draw_shadows(&mut point_shadow_pass, draw_list, false);
 
-            compute_pass.dispatch_workgroups(instances_len.div_ceil(64) as u32, 1, 1);
+            render_pipeline_pbr.prepare_shadow_masked(
+                &mut point_shadow_pass,
+                vertex_buffer.slice(..),
+                index_buffer.slice(..),
+                &bind_groups_shadow_masked[draw_list],
+                &bind_group_bindless,
+                ShadowMaps::point_shadow_pass_view_index(face),
+            );
+            instance_buffers.draw_shadows(&mut point_shadow_pass, draw_list, true);
         }
 
         {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("render_pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &msaa_buffer,
-                    resolve_target: Some(&output_texture_view),
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
+            let mut depth_prepass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("depth_prepass"),
+                color_attachments: &[],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &depth_buffer,
                     depth_ops: Some(wgpu::Operations {
@@ -212,66 +358,80 @@ mod system {
                 timestamp_writes: None,
             });
 
-            let camera_buffer = graphics::pipeline::render::pbr::create_camera_buffer(
-                &gpu.device,
-                graphics::pipeline::render::pbr::CameraMatrix {
-                    position: camera.position.extend(1.0).into(),
-                    view_projection: view_projection.to_cols_array(),
-                },
+            render_pipeline_pbr.prepare_depth_prepass(
+                &mut depth_prepass,
+                vertex_buffer.slice(..),
+                index_buffer.slice(..),
+                &bind_group_variable,
+                &bind_group_bindless,
             );
 
-            let instance_transforms_buffer =
-                graphics::pipeline::render::pbr::create_instance_transforms_buffer(
-                    &gpu.device,
-                    instance_transforms,
+            for double_sided in [false, true] {
+                render_pipeline_pbr.set_depth_prepass_pipeline(&mut depth_prepass, double_sided);
+                instance_buffers.draw(
+                    &mut depth_prepass,
+                    instance_buffers.opaque_draw_commands(double_sided),
                 );
+            }
+        }
 
-            let instance_materials_buffer =
-                graphics::pipeline::render::pbr::create_instance_materials_buffer(
-                    &gpu.device,
-                    instance_materials,
-                );
+        {
+            let mut ambient_occlusion_pass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("ambient_occlusion_pass"),
+                    timestamp_writes: None,
+                });
 
-            // TODO: query dynamically from world.
-            let ambient_light_buffer = graphics::pipeline::render::pbr::create_ambient_light_buffer(
-                &gpu.device,
-                graphics::pipeline::render::pbr::AmbientLight {
-                    color: [1.0, 1.0, 1.0],
-                    strength: 0.7,
-                },
+            compute_pipeline_ambient_occlusion.prepare(
+                &mut ambient_occlusion_pass,
+                &ambient_occlusion_targets.bind_group_ambient_occlusion,
             );
-
-            // TODO: query dynamically from world.
-            let point_lights = [graphics::pipeline::render::pbr::PointLight {
-                color: [1.0, 1.0, 1.0],
-                strength: 0.7,
-                position: [0.0, 3.0, 2.0],
-                range: 4.0,
-            }];
-            let point_lights_buffer = graphics::pipeline::render::pbr::create_point_lights_buffer(
-                &gpu.device,
-                &point_lights,
+            graphics::pipeline::compute::AmbientOcclusion::dispatch(
+                &mut ambient_occlusion_pass,
+                ambient_occlusion_targets.width,
+                ambient_occlusion_targets.height,
             );
-            let point_lights_length_buffer =
-                graphics::pipeline::render::pbr::create_point_lights_length_buffer(
-                    &gpu.device,
-                    point_lights.len() as u32,
-                );
 
-            let bind_group_variable = render_pipeline_pbr.create_bind_group_variable(
-                &gpu.device,
-                camera_buffer.as_entire_binding(),
-                instance_transforms_buffer.as_entire_binding(),
-                indirect_instances_buffer.as_entire_binding(),
-                instance_materials_buffer.as_entire_binding(),
+            compute_pipeline_ambient_occlusion.prepare_blur(
+                &mut ambient_occlusion_pass,
+                &ambient_occlusion_targets.bind_group_ambient_occlusion_blur,
             );
-
-            let bind_group_lights = render_pipeline_pbr.create_bind_group_lights(
-                &gpu.device,
-                ambient_light_buffer.as_entire_binding(),
-                point_lights_buffer.as_entire_binding(),
-                point_lights_length_buffer.as_entire_binding(),
+            graphics::pipeline::compute::AmbientOcclusion::dispatch(
+                &mut ambient_occlusion_pass,
+                ambient_occlusion_targets.width,
+                ambient_occlusion_targets.height,
             );
+        }
+
+        {
+            // Resolves into the HDR buffer, unless MSAA is disabled.
+            let (view, resolve_target) = match &**msaa_buffer {
+                Some(msaa_buffer) => (msaa_buffer, Some(&post_processing_targets.hdr_buffer)),
+                None => (&post_processing_targets.hdr_buffer, None),
+            };
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("render_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_buffer,
+                    depth_ops: Some(wgpu::Operations {
+                        // Keeps the depth of the prepass.
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
 
             render_pipeline_pbr.prepare(
                 &mut render_pass,
@@ -280,25 +440,16 @@ mod system {
                 &bind_group_variable,
                 &bind_group_bindless,
                 &bind_group_lights,
+                &bind_group_environment,
             );
 
-            render_pass.multi_draw_indexed_indirect(
-                &indirect_draw_commands_buffer,
-                0,
-                indirect_draw_commands.len() as u32,
-            );
-
-            let inverse_view_projection_buffer =
-                graphics::pipeline::render::skybox::create_inverse_view_projection_buffer(
-                    &gpu.device,
-                    &view_projection.inverse().to_cols_array(),
-                );
-
-            let bind_group_inverse_view_projection = render_pipeline_skybox
-                .create_bind_group_inverse_view_projection(
-                    &gpu.device,
-                    inverse_view_projection_buffer.as_entire_binding(),
+            for double_sided in [false, true] {
+                render_pipeline_pbr.set_pipeline(&mut render_pass, false, double_sided);
+                instance_buffers.draw(
+                    &mut render_pass,
+                    instance_buffers.opaque_draw_commands(double_sided),
                 );
+            }
 
             render_pipeline_skybox.prepare(
                 &mut render_pass,
@@ -307,9 +458,151 @@ mod system {
             );
 
             graphics::pipeline::render::Skybox::draw(&mut render_pass);
+
+            // Alpha blended instances are drawn last, over the skybox.
+            let transparent_draw_command_runs = instance_buffers.transparent_draw_command_runs();
+            if !transparent_draw_command_runs.is_empty() {
+                render_pipeline_pbr.prepare(
+                    &mut render_pass,
+                    vertex_buffer.slice(..),
+                    index_buffer.slice(..),
+                    &bind_group_variable,
+                    &bind_group_bindless,
+                    &bind_group_lights,
+                    &bind_group_environment,
+                );
+
+                for (draw_commands, double_sided) in transparent_draw_command_runs {
+                    render_pipeline_pbr.set_pipeline(&mut render_pass, true, double_sided);
+                    instance_buffers.draw(&mut render_pass, draw_commands);
+                }
+            }
+        }
+
+        if post_processing.bloom {
+            let bloom_mip_count = post_processing_targets.bloom_mip_views.len();
+
+            for mip_level in 0..bloom_mip_count {
+                let mut bloom_downsample_pass =
+                    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("bloom_downsample_pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &post_processing_targets.bloom_mip_views[mip_level],
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                        occlusion_query_set: None,
+                        timestamp_writes: None,
+                    });
+
+                let bind_group_bloom = if mip_level == 0 {
+                    &post_processing_targets.bind_group_bloom_hdr
+                } else {
+                    &post_processing_targets.bind_groups_bloom_mips[mip_level - 1]
+                };
+                render_pipeline_bloom.prepare_downsample(
+                    &mut bloom_downsample_pass,
+                    bind_group_bloom,
+                    mip_level as u32,
+                );
+
+                graphics::pipeline::render::Bloom::draw(&mut bloom_downsample_pass);
+            }
+
+            // Accumulates every mip back into the first one, from the smallest.
+            for mip_level in (1..bloom_mip_count).rev() {
+                let mut bloom_upsample_pass =
+                    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("bloom_upsample_pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &post_processing_targets.bloom_mip_views[mip_level - 1],
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                        occlusion_query_set: None,
+                        timestamp_writes: None,
+                    });
+
+                render_pipeline_bloom.prepare_upsample(
+                    &mut bloom_upsample_pass,
+                    &post_processing_targets.bind_groups_bloom_mips[mip_level],
+                );
+
+                graphics::pipeline::render::Bloom::draw(&mut bloom_upsample_pass);
+            }
+        }
+
+        // FXAA anti-aliases the frame when the main pass is not multisampled.
+        let fxaa = msaa_buffer.is_none();
+
+        {
+            // FXAA reads the tonemapped frame from the LDR buffer.
+            let view = if fxaa {
+                &post_processing_targets.ldr_buffer
+            } else {
+                &frame.view
+            };
+
+            let mut tonemapping_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("tonemapping_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pipeline_tonemapping.prepare(
+                &mut tonemapping_pass,
+                &post_processing_targets.bind_group_tonemapping,
+            );
+
+            graphics::pipeline::render::Tonemapping::draw(&mut tonemapping_pass);
+        }
+
+        if fxaa {
+            let mut fxaa_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("fxaa_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &frame.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pipeline_fxaa.prepare(&mut fxaa_pass, &post_processing_targets.bind_group_fxaa);
+
+            graphics::pipeline::render::Fxaa::draw(&mut fxaa_pass);
         }
 
         gpu.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        frame.present();
+
+        if let Some(frame_capture) = frame_capture {
+            if let Some(capture) = gpu.capture_frame() {
+                // The receiver may have given up waiting.
+                let _ = frame_capture.send(capture);
+            }
+        }
     }
 }
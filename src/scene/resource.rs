@@ -36,18 +36,20 @@ impl DerefMut for DeltaTime {
     }
 }
 
+/// When present, time advances by this duration on every update instead of following the wall
+/// clock, which makes simulations reproducible.
 #[derive(bevy_ecs::system::Resource)]
-pub struct Meshes(pub Vec<graphics::Mesh>);
+pub struct FixedDeltaTime(pub std::time::Duration);
 
-impl Deref for Meshes {
-    type Target = Vec<graphics::Mesh>;
+impl Deref for FixedDeltaTime {
+    type Target = std::time::Duration;
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl DerefMut for Meshes {
+impl DerefMut for FixedDeltaTime {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
@@ -104,6 +106,91 @@ impl DerefMut for BoundingBoxesBuffer {
     }
 }
 
+#[derive(bevy_ecs::system::Resource)]
+pub struct CameraBuffer(pub wgpu::Buffer);
+
+impl Deref for CameraBuffer {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for CameraBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct FrustumBuffer(pub wgpu::Buffer);
+
+impl Deref for FrustumBuffer {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for FrustumBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct InverseViewProjectionBuffer(pub wgpu::Buffer);
+
+impl Deref for InverseViewProjectionBuffer {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for InverseViewProjectionBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct TonemappingBuffer(pub wgpu::Buffer);
+
+impl Deref for TonemappingBuffer {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for TonemappingBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct AmbientOcclusionBuffer(pub wgpu::Buffer);
+
+impl Deref for AmbientOcclusionBuffer {
+    type Target = wgpu::Buffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for AmbientOcclusionBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 #[derive(bevy_ecs::system::Resource)]
 pub struct DepthBuffer(pub wgpu::TextureView);
 
@@ -121,11 +208,12 @@ impl DerefMut for DepthBuffer {
     }
 }
 
+/// `None` when MSAA is disabled, in which case the main pass renders straight into the HDR buffer.
 #[derive(bevy_ecs::system::Resource)]
-pub struct MsaaBuffer(pub wgpu::TextureView);
+pub struct MsaaBuffer(pub Option<wgpu::TextureView>);
 
 impl Deref for MsaaBuffer {
-    type Target = wgpu::TextureView;
+    type Target = Option<wgpu::TextureView>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -138,6 +226,41 @@ impl DerefMut for MsaaBuffer {
     }
 }
 
+/// Sample count of the main pass color and depth targets.
+#[derive(bevy_ecs::system::Resource)]
+pub struct MsaaSampleCount(pub u32);
+
+impl Deref for MsaaSampleCount {
+    type Target = u32;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for MsaaSampleCount {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct ColorGradingLut(pub wgpu::TextureView);
+
+impl Deref for ColorGradingLut {
+    type Target = wgpu::TextureView;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ColorGradingLut {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 #[derive(bevy_ecs::system::Resource)]
 pub struct ComputePipelineFrustumCulling(pub graphics::pipeline::compute::FrustumCulling);
 
@@ -155,6 +278,23 @@ impl DerefMut for ComputePipelineFrustumCulling {
     }
 }
 
+#[derive(bevy_ecs::system::Resource)]
+pub struct ComputePipelineAmbientOcclusion(pub graphics::pipeline::compute::AmbientOcclusion);
+
+impl Deref for ComputePipelineAmbientOcclusion {
+    type Target = graphics::pipeline::compute::AmbientOcclusion;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ComputePipelineAmbientOcclusion {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 #[derive(bevy_ecs::system::Resource)]
 pub struct RenderPipelinePbr(pub graphics::pipeline::render::Pbr);
 
@@ -189,6 +329,74 @@ impl DerefMut for RenderPipelineSkybox {
     }
 }
 
+#[derive(bevy_ecs::system::Resource)]
+pub struct RenderPipelineShadow(pub graphics::pipeline::render::Shadow);
+
+impl Deref for RenderPipelineShadow {
+    type Target = graphics::pipeline::render::Shadow;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for RenderPipelineShadow {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct RenderPipelineTonemapping(pub graphics::pipeline::render::Tonemapping);
+
+impl Deref for RenderPipelineTonemapping {
+    type Target = graphics::pipeline::render::Tonemapping;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for RenderPipelineTonemapping {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct RenderPipelineBloom(pub graphics::pipeline::render::Bloom);
+
+impl Deref for RenderPipelineBloom {
+    type Target = graphics::pipeline::render::Bloom;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for RenderPipelineBloom {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct RenderPipelineFxaa(pub graphics::pipeline::render::Fxaa);
+
+impl Deref for RenderPipelineFxaa {
+    type Target = graphics::pipeline::render::Fxaa;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for RenderPipelineFxaa {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 #[derive(bevy_ecs::system::Resource)]
 pub struct BindGroupBindless(pub wgpu::BindGroup);
 
@@ -222,3 +430,157 @@ impl DerefMut for BindGroupSkybox {
         &mut self.0
     }
 }
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct BindGroupVariable(pub wgpu::BindGroup);
+
+impl Deref for BindGroupVariable {
+    type Target = wgpu::BindGroup;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BindGroupVariable {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct BindGroupFrustumCulling(pub wgpu::BindGroup);
+
+impl Deref for BindGroupFrustumCulling {
+    type Target = wgpu::BindGroup;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BindGroupFrustumCulling {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct BindGroupsShadowFrustumCulling(pub Vec<wgpu::BindGroup>);
+
+impl Deref for BindGroupsShadowFrustumCulling {
+    type Target = Vec<wgpu::BindGroup>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BindGroupsShadowFrustumCulling {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct BindGroupLights(pub wgpu::BindGroup);
+
+impl Deref for BindGroupLights {
+    type Target = wgpu::BindGroup;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BindGroupLights {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct BindGroupInverseViewProjection(pub wgpu::BindGroup);
+
+impl Deref for BindGroupInverseViewProjection {
+    type Target = wgpu::BindGroup;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BindGroupInverseViewProjection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct BindGroupEnvironment(pub wgpu::BindGroup);
+
+impl Deref for BindGroupEnvironment {
+    type Target = wgpu::BindGroup;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BindGroupEnvironment {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct BindGroupsShadow(pub Vec<wgpu::BindGroup>);
+
+impl Deref for BindGroupsShadow {
+    type Target = Vec<wgpu::BindGroup>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BindGroupsShadow {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(bevy_ecs::system::Resource)]
+pub struct BindGroupsShadowMasked(pub Vec<wgpu::BindGroup>);
+
+impl Deref for BindGroupsShadowMasked {
+    type Target = Vec<wgpu::BindGroup>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BindGroupsShadowMasked {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Requests the next rendered frame to be read back and sent through the channel.
+#[derive(bevy_ecs::system::Resource)]
+pub struct FrameCapture(pub crossbeam::channel::Sender<graphics::Capture>);
+
+impl Deref for FrameCapture {
+    type Target = crossbeam::channel::Sender<graphics::Capture>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for FrameCapture {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
@@ -1,4 +1,13 @@
-use super::{rendering, resource::*, simulation};
+use super::{
+    ambient_occlusion::AmbientOcclusionTargets,
+    instance::InstanceBuffers,
+    light::{EnvironmentLighting, LightBuffers},
+    post_processing::PostProcessingTargets,
+    rendering,
+    resource::*,
+    shadow::ShadowMaps,
+    simulation,
+};
 use crate::asset;
 use crate::ecs;
 use crate::graphics;
@@ -8,6 +17,9 @@ use bevy_hierarchy::BuildChildren;
 use wgpu::util::DeviceExt;
 
 pub const MSAA_SAMPLE_COUNT: u32 = 4;
+/// Whether FXAA replaces MSAA, as a cheaper alternative. The sample count is baked into the
+/// pipelines and targets of the main pass, so the choice is made when the scene is set up.
+pub const FXAA: bool = false;
 
 pub struct Scene {
     simulator: simulation::Simulator,
@@ -15,6 +27,28 @@ pub struct Scene {
 
 impl Scene {
     pub fn setup(gpu: graphics::Gpu<'static>) -> Self {
+        Self::spawn(gpu, |_| {})
+    }
+
+    /// Sets up the scene so that time advances by `delta_time` on every update, rendering the same
+    /// sequence of frames on every run. The default camera is replaced if one is given.
+    pub fn setup_deterministic(
+        gpu: graphics::Gpu<'static>,
+        camera: Option<ecs::resource::Camera>,
+        delta_time: std::time::Duration,
+    ) -> Self {
+        Self::spawn(gpu, move |world| {
+            if let Some(camera) = camera {
+                world.insert_resource(camera);
+            }
+            world.insert_resource(FixedDeltaTime(delta_time));
+        })
+    }
+
+    fn spawn(
+        gpu: graphics::Gpu<'static>,
+        configure_world: impl FnOnce(&mut bevy_ecs::world::World),
+    ) -> Self {
         let (scene_to_renderer_sender, scene_to_renderer_receiver) = crossbeam::channel::bounded(1);
         let (renderer_to_scene_sender, renderer_to_scene_receiver) = crossbeam::channel::bounded(1);
 
@@ -22,6 +56,7 @@ impl Scene {
         let mut render_world = bevy_ecs::world::World::new();
 
         load_scene(&mut world, &mut render_world, &gpu);
+        configure_world(&mut world);
 
         render_world.insert_resource(gpu);
         renderer_to_scene_sender.send(render_world).unwrap();
@@ -41,6 +76,21 @@ impl Scene {
     pub fn resize(&mut self, width: u32, height: u32) {
         self.simulator.request_resize(width, height);
     }
+
+    /// Switches the camera to the next tonemapper.
+    pub fn cycle_tonemapper(&mut self) {
+        self.simulator.request_tonemapper_cycle();
+    }
+
+    /// Simulates and renders one frame, then reads it back. Returns `None` if the GPU renders to a
+    /// window surface, which cannot be captured.
+    pub fn capture_frame(&mut self) -> Option<graphics::Capture> {
+        let (frame_capture_sender, frame_capture_receiver) = crossbeam::channel::bounded(1);
+        self.simulator.request_capture(frame_capture_sender);
+        self.update();
+
+        frame_capture_receiver.recv().ok()
+    }
 }
 
 fn load_scene(
@@ -65,6 +115,19 @@ fn load_scene(
         ..Default::default()
     });
 
+    main_world.insert_resource(ecs::resource::AmbientLight {
+        color: glam::Vec3::ONE,
+        strength: 1.0,
+    });
+
+    main_world.insert_resource(ecs::resource::PostProcessing::default());
+    // OpenGL cannot sample the multisampled depth buffer for SSAO, so FXAA replaces MSAA there.
+    let msaa_sample_count = if FXAA || gpu.adapter.get_info().backend == wgpu::Backend::Gl {
+        1
+    } else {
+        MSAA_SAMPLE_COUNT
+    };
+
     let mut asset_loader = asset::AssetLoader::new();
 
     let model_id = asset_loader
@@ -82,6 +145,14 @@ fn load_scene(
         )
         .unwrap();
 
+    let color_grading_lut = asset_loader
+        .load_color_grading_lut(
+            asset::assets_path()
+                .join("color_grading")
+                .join("warm_film.cube"),
+        )
+        .unwrap();
+
     let asset::AssetLoader {
         mut mesh_map,
         texture_arrays,
@@ -127,6 +198,13 @@ fn load_scene(
             occlusion: asset::Occlusion::Solid(1.0.into()),
             metallic: asset::Metallic::Solid(0.5.into()),
             roughness: asset::Roughness::Solid(0.5.into()),
+            emissive: asset::Emissive::Solid {
+                r: 0.0.into(),
+                g: 0.0.into(),
+                b: 0.0.into(),
+            },
+            alpha_mode: asset::AlphaMode::Opaque,
+            double_sided: false,
         });
 
         let start_position = [0.0, 1.5, -0.5];
@@ -149,11 +227,12 @@ fn load_scene(
             ecs::component::Material {
                 material_id: icosphere_material_id,
             },
-            ecs::component::GlobalTransform(glam::Affine3A::from_scale_rotation_translation(
-                glam::Vec3::new(0.05, 0.05, 0.05),
-                glam::Quat::IDENTITY,
-                glam::Vec3::from(start_position),
-            )),
+            ecs::component::Transform {
+                translation: glam::Vec3::from(start_position),
+                rotation: glam::Quat::IDENTITY,
+                scale: glam::Vec3::new(0.05, 0.05, 0.05),
+            },
+            ecs::component::GlobalTransform::default(),
             physics::RigidBody(rigid_body_handle),
             physics::Collider(collider_handle),
         ));
@@ -172,7 +251,12 @@ fn load_scene(
         map: _materials_map,
     } = material_map;
 
-    render_world.insert_resource(Meshes(meshes));
+    let materials = materials
+        .into_iter()
+        .map(graphics::Material::from)
+        .collect::<Vec<_>>();
+
+    let instance_buffers = InstanceBuffers::new(&gpu.device, &meshes, &bounding_boxes, &materials);
 
     main_world.insert_resource(physics_world);
 
@@ -180,148 +264,43 @@ fn load_scene(
 
     let model = model_map.index(model_id).unwrap();
 
-    let root = commands.spawn(()).id();
-    let mut stack: Vec<(usize, bevy_ecs::entity::Entity)> = model
-        .root_nodes
-        .iter()
-        .map(|&node_index| (node_index, root))
-        .collect();
-
-    while let Some((node_index, parent_entity)) = stack.pop() {
-        let node = model.nodes.get(node_index).unwrap();
-
-        let objects = node
-            .object_group
-            .as_ref()
-            .map(|object_group| {
-                object_group
-                    .objects
-                    .iter()
-                    .map(
-                        |&asset::Object {
-                             mesh_id,
-                             material_id,
-                         }| {
-                            commands
-                                .spawn((
-                                    ecs::component::Mesh { mesh_id },
-                                    ecs::component::Material { material_id },
-                                    ecs::component::GlobalTransform::default(),
-                                ))
-                                .id()
-                        },
-                    )
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-        let mut entity_commands = commands.spawn(());
-        entity_commands.add_children(&objects);
-        let entity = entity_commands.id();
-
-        commands.entity(parent_entity).add_child(entity);
-
-        for &child_index in &node.children {
-            stack.push((child_index, entity));
-        }
-    }
-
-    let root = commands.spawn(()).id();
-    let mut stack: Vec<(usize, bevy_ecs::entity::Entity)> = model
-        .root_nodes
-        .iter()
-        .map(|&node_index| (node_index, root))
-        .collect();
-
-    while let Some((node_index, parent_entity)) = stack.pop() {
-        let node = model.nodes.get(node_index).unwrap();
-
-        let objects = node
-            .object_group
-            .as_ref()
-            .map(|object_group| {
-                object_group
-                    .objects
-                    .iter()
-                    .map(
-                        |&asset::Object {
-                             mesh_id,
-                             material_id,
-                         }| {
-                            commands
-                                .spawn((
-                                    ecs::component::Mesh { mesh_id },
-                                    ecs::component::Material { material_id },
-                                    ecs::component::GlobalTransform(
-                                        glam::Affine3A::from_translation(glam::Vec3::new(
-                                            0.5, 0.0, 0.0,
-                                        )),
-                                    ),
-                                ))
-                                .id()
-                        },
-                    )
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-        let mut entity_commands = commands.spawn(());
-        entity_commands.add_children(&objects);
-        let entity = entity_commands.id();
-
-        commands.entity(parent_entity).add_child(entity);
-
-        for &child_index in &node.children {
-            stack.push((child_index, entity));
-        }
+    for translation in [
+        glam::Vec3::new(0.0, 0.0, 0.0),
+        glam::Vec3::new(0.5, 0.0, 0.0),
+        glam::Vec3::new(-0.5, 0.0, 0.0),
+    ] {
+        spawn_model(
+            &mut commands,
+            model,
+            ecs::component::Transform::from_translation(translation),
+        );
     }
 
-    let root = commands.spawn(()).id();
-    let mut stack: Vec<(usize, bevy_ecs::entity::Entity)> = model
-        .root_nodes
-        .iter()
-        .map(|&node_index| (node_index, root))
-        .collect();
-
-    while let Some((node_index, parent_entity)) = stack.pop() {
-        let node = model.nodes.get(node_index).unwrap();
-
-        let objects = node
-            .object_group
-            .as_ref()
-            .map(|object_group| {
-                object_group
-                    .objects
-                    .iter()
-                    .map(
-                        |&asset::Object {
-                             mesh_id,
-                             material_id,
-                         }| {
-                            commands
-                                .spawn((
-                                    ecs::component::Mesh { mesh_id },
-                                    ecs::component::Material { material_id },
-                                    ecs::component::GlobalTransform(
-                                        glam::Affine3A::from_translation(glam::Vec3::new(
-                                            -0.5, 0.0, 0.0,
-                                        )),
-                                    ),
-                                ))
-                                .id()
-                        },
-                    )
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-        let mut entity_commands = commands.spawn(());
-        entity_commands.add_children(&objects);
-        let entity = entity_commands.id();
-
-        commands.entity(parent_entity).add_child(entity);
+    commands.spawn((
+        ecs::component::PointLight {
+            color: glam::Vec3::ONE,
+            strength: 10.0,
+            range: 10.0,
+            cast_shadows: true,
+        },
+        ecs::component::Transform::from_translation(glam::Vec3::new(0.0, 3.0, 2.0)),
+        ecs::component::GlobalTransform::default(),
+    ));
 
-        for &child_index in &node.children {
-            stack.push((child_index, entity));
-        }
-    }
+    commands.spawn((
+        ecs::component::DirectionalLight {
+            color: glam::Vec3::ONE,
+            strength: 2.0,
+        },
+        ecs::component::Transform {
+            rotation: glam::Quat::from_rotation_arc(
+                glam::Vec3::NEG_Z,
+                glam::Vec3::new(-0.4, -1.0, -0.6).normalize(),
+            ),
+            ..Default::default()
+        },
+        ecs::component::GlobalTransform::default(),
+    ));
 
     main_world.flush();
 
@@ -329,13 +308,30 @@ fn load_scene(
         &gpu.device,
         &bounding_boxes,
     );
-    render_world.insert_resource(BoundingBoxesBuffer(bounding_boxes_buffer));
+
+    let camera = main_world.get_resource::<ecs::resource::Camera>().unwrap();
+    let view_projection = camera.perspective() * camera.view_matrix();
+
+    let frustum_buffer = graphics::pipeline::compute::frustum_culling::create_frustum_buffer(
+        &gpu.device,
+        ecs::resource::Frustum::from_view_projection_matrix(&view_projection),
+    );
 
     let compute_pipeline_frustum_culling =
         graphics::pipeline::compute::FrustumCulling::new(&gpu.device);
+    let bind_group_frustum_culling = instance_buffers.create_bind_group_frustum_culling(
+        &gpu.device,
+        &compute_pipeline_frustum_culling,
+        &bounding_boxes_buffer,
+        &frustum_buffer,
+    );
     render_world.insert_resource(ComputePipelineFrustumCulling(
         compute_pipeline_frustum_culling,
     ));
+    render_world.insert_resource(BindGroupFrustumCulling(bind_group_frustum_culling));
+    render_world.insert_resource(FrustumBuffer(frustum_buffer));
+
+    render_world.insert_resource(BoundingBoxesBuffer(bounding_boxes_buffer));
 
     let vertex_buffer = gpu
         .device
@@ -355,49 +351,93 @@ fn load_scene(
         });
     render_world.insert_resource(IndexBuffer(index_buffer));
 
-    let material_buffer = graphics::pipeline::render::pbr::create_material_buffer(
+    let material_buffer =
+        graphics::pipeline::render::pbr::create_material_buffer(&gpu.device, &materials);
+
+    // Texture arrays leave out the cubemap faces, which are copied into the skybox instead.
+    let texture_bindings = match std::num::NonZeroU32::new(texture_arrays.textures.len() as u32) {
+        Some(texture_count) if gpu.texture_binding_arrays => {
+            graphics::pipeline::render::pbr::TextureBindings::BindingArray { texture_count }
+        }
+        _ => graphics::pipeline::render::pbr::TextureBindings::TextureArrays(
+            texture_arrays
+                .texture_arrays()
+                .filter(|&texture_array| texture_array != cubemap.texture_array)
+                .collect(),
+        ),
+    };
+
+    let render_pipeline_pbr = graphics::pipeline::render::Pbr::new(
+        &gpu.device,
+        graphics::gpu::HDR_TEXTURE_FORMAT,
+        msaa_sample_count,
+        &texture_bindings,
+    )
+    .unwrap_or_else(|error| panic!("{error}"));
+
+    let camera_buffer = graphics::pipeline::render::pbr::create_camera_buffer(
         &gpu.device,
-        &materials
-            .into_iter()
-            .map(graphics::Material::from)
-            .collect::<Vec<_>>(),
+        graphics::pipeline::render::pbr::CameraMatrix {
+            view_projection: view_projection.to_cols_array(),
+            position: camera.position.extend(1.0).into(),
+        },
     );
 
-    let render_pipeline_pbr =
-        graphics::pipeline::render::Pbr::new(&gpu.device, gpu.config.format, MSAA_SAMPLE_COUNT);
+    let bind_group_variable = instance_buffers.create_bind_group_variable(
+        &gpu.device,
+        &render_pipeline_pbr,
+        &camera_buffer,
+    );
+    render_world.insert_resource(BindGroupVariable(bind_group_variable));
+    render_world.insert_resource(CameraBuffer(camera_buffer));
 
-    let texture_array_handles = graphics::pipeline::render::pbr::create_texture_arrays_init(
+    let shadow_maps = ShadowMaps::new(&gpu.device);
+    let render_pipeline_shadow = graphics::pipeline::render::Shadow::new(&gpu.device);
+    let bind_groups_shadow = instance_buffers.create_bind_groups_shadow(
         &gpu.device,
-        &gpu.queue,
-        &graphics::pipeline::render::pbr::TextureArrays {
-            rg_bc5_unorm_512: texture_arrays.rg_bc5_unorm_512,
-            rg_bc5_unorm_1024: texture_arrays.rg_bc5_unorm_1024,
-            rg_bc5_unorm_2048: texture_arrays.rg_bc5_unorm_2048,
-            rg_bc5_unorm_4096: texture_arrays.rg_bc5_unorm_4096,
-            rgb_bc7_unorm_512: texture_arrays.rgb_bc7_unorm_512,
-            rgb_bc7_unorm_1024: texture_arrays.rgb_bc7_unorm_1024,
-            rgb_bc7_unorm_2048: texture_arrays.rgb_bc7_unorm_2048,
-            rgb_bc7_unorm_4096: texture_arrays.rgb_bc7_unorm_4096,
-            rgba_bc7_srgb_512: texture_arrays.rgba_bc7_srgb_512,
-            rgba_bc7_srgb_1024: texture_arrays.rgba_bc7_srgb_1024,
-            rgba_bc7_srgb_2048: texture_arrays.rgba_bc7_srgb_2048,
-            rgba_bc7_srgb_4096: texture_arrays.rgba_bc7_srgb_4096,
-        },
+        &render_pipeline_shadow,
+        &shadow_maps.shadow_pass_views_buffer,
+    );
+    let bind_groups_shadow_masked = instance_buffers.create_bind_groups_shadow_masked(
+        &gpu.device,
+        &render_pipeline_pbr,
+        &shadow_maps.shadow_pass_views_buffer,
     );
+    let bind_groups_shadow_frustum_culling = instance_buffers
+        .create_bind_groups_shadow_frustum_culling(
+            &gpu.device,
+            render_world.resource::<ComputePipelineFrustumCulling>(),
+            render_world.resource::<BoundingBoxesBuffer>(),
+            &shadow_maps.shadow_frustum_buffers,
+        );
+    render_world.insert_resource(RenderPipelineShadow(render_pipeline_shadow));
+    render_world.insert_resource(BindGroupsShadow(bind_groups_shadow));
+    render_world.insert_resource(BindGroupsShadowMasked(bind_groups_shadow_masked));
+    render_world.insert_resource(BindGroupsShadowFrustumCulling(
+        bind_groups_shadow_frustum_culling,
+    ));
+    render_world.insert_resource(instance_buffers);
 
-    let texture_array_views =
-        graphics::pipeline::render::pbr::create_texture_array_views(texture_array_handles);
+    let light_buffers = LightBuffers::new(&gpu.device);
+    let bind_group_lights =
+        light_buffers.create_bind_group_lights(&gpu.device, &render_pipeline_pbr, &shadow_maps);
+    render_world.insert_resource(shadow_maps);
+    render_world.insert_resource(light_buffers);
+    render_world.insert_resource(BindGroupLights(bind_group_lights));
+
+    let texture_views = graphics::pipeline::render::pbr::create_texture_views_init(
+        &gpu.device,
+        &gpu.queue,
+        &texture_arrays,
+        &texture_bindings,
+    );
 
     let bind_group_bindless = render_pipeline_pbr.create_bind_group_bindless(
         &gpu.device,
         material_buffer.as_entire_binding(),
-        texture_array_views,
-        wgpu::BindingResource::Sampler(&gpu.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("texture_array_sampler_base_color"),
-            ..Default::default()
-        })),
+        &texture_views,
         wgpu::BindingResource::Sampler(&gpu.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("texture_array_sampler_normal"),
+            label: Some("material_sampler"),
             mag_filter: wgpu::FilterMode::Linear,
             min_filter: wgpu::FilterMode::Linear,
             mipmap_filter: wgpu::FilterMode::Linear,
@@ -411,11 +451,11 @@ fn load_scene(
         &gpu.device,
         gpu.config.width,
         gpu.config.height,
-        MSAA_SAMPLE_COUNT,
+        msaa_sample_count,
     );
     render_world.insert_resource(DepthBuffer(depth_buffer_view));
 
-    let cubemap_size = cubemap.texture_array.size().0 as u32;
+    let cubemap_size = cubemap.texture_array.class().width;
     let texture_skybox = gpu.device.create_texture(&wgpu::TextureDescriptor {
         label: Some("texture_skybox"),
         size: wgpu::Extent3d {
@@ -445,7 +485,7 @@ fn load_scene(
         const BLOCK_SIZE: u32 = 4;
 
         let face_data = texture_arrays
-            .no_mip_rgb_bc6h_sfloat_1024
+            .get(cubemap.texture_array)
             .get(face_texture_id, 0)
             .unwrap();
 
@@ -474,22 +514,73 @@ fn load_scene(
         );
     }
 
-    let render_pipeline_skybox =
-        graphics::pipeline::render::Skybox::new(&gpu.device, gpu.config.format, MSAA_SAMPLE_COUNT);
+    let texture_skybox_view = texture_skybox.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("texture_skybox"),
+        format: Some(wgpu::TextureFormat::Bc6hRgbFloat),
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        aspect: wgpu::TextureAspect::All,
+        base_mip_level: 0,
+        mip_level_count: None,
+        base_array_layer: 0,
+        array_layer_count: None,
+    });
+
+    let environment_maps = graphics::pipeline::compute::ImageBasedLighting::new(&gpu.device)
+        .compute(&gpu.device, &gpu.queue, &texture_skybox_view);
+    let environment_lighting = EnvironmentLighting::new(&gpu.device, &environment_maps);
+
+    let compute_pipeline_ambient_occlusion =
+        graphics::pipeline::compute::AmbientOcclusion::new(&gpu.device, msaa_sample_count);
+    let ambient_occlusion_buffer =
+        graphics::pipeline::compute::ambient_occlusion::create_ambient_occlusion_buffer(
+            &gpu.device,
+            &graphics::pipeline::compute::ambient_occlusion::AmbientOcclusionSettings::new(
+                main_world.resource::<ecs::resource::Camera>().perspective(),
+            ),
+        );
+    let ambient_occlusion_targets = AmbientOcclusionTargets::new(
+        gpu,
+        &compute_pipeline_ambient_occlusion,
+        &ambient_occlusion_buffer,
+        render_world.resource::<DepthBuffer>(),
+    );
+
+    let bind_group_environment = environment_lighting.create_bind_group_environment(
+        &gpu.device,
+        render_world.resource::<RenderPipelinePbr>(),
+        &ambient_occlusion_targets.ambient_occlusion_view,
+    );
+    render_world.insert_resource(BindGroupEnvironment(bind_group_environment));
+    render_world.insert_resource(environment_lighting);
+    render_world.insert_resource(ComputePipelineAmbientOcclusion(
+        compute_pipeline_ambient_occlusion,
+    ));
+    render_world.insert_resource(AmbientOcclusionBuffer(ambient_occlusion_buffer));
+    render_world.insert_resource(ambient_occlusion_targets);
+
+    let render_pipeline_skybox = graphics::pipeline::render::Skybox::new(
+        &gpu.device,
+        graphics::gpu::HDR_TEXTURE_FORMAT,
+        msaa_sample_count,
+    );
+
+    let inverse_view_projection_buffer =
+        graphics::pipeline::render::skybox::create_inverse_view_projection_buffer(
+            &gpu.device,
+            &view_projection.inverse().to_cols_array(),
+        );
+    let bind_group_inverse_view_projection = render_pipeline_skybox
+        .create_bind_group_inverse_view_projection(
+            &gpu.device,
+            inverse_view_projection_buffer.as_entire_binding(),
+        );
+    render_world.insert_resource(BindGroupInverseViewProjection(
+        bind_group_inverse_view_projection,
+    ));
+    render_world.insert_resource(InverseViewProjectionBuffer(inverse_view_projection_buffer));
     let bind_group_skybox = render_pipeline_skybox.create_bind_group_skybox(
         &gpu.device,
-        wgpu::BindingResource::TextureView(&texture_skybox.create_view(
-            &wgpu::TextureViewDescriptor {
-                label: Some("texture_skybox"),
-                format: Some(wgpu::TextureFormat::Bc6hRgbFloat),
-                dimension: Some(wgpu::TextureViewDimension::Cube),
-                aspect: wgpu::TextureAspect::All,
-                base_mip_level: 0,
-                mip_level_count: None,
-                base_array_layer: 0,
-                array_layer_count: None,
-            },
-        )),
+        wgpu::BindingResource::TextureView(&texture_skybox_view),
         wgpu::BindingResource::Sampler(&gpu.device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("texture_sampler_skybox"),
             mag_filter: wgpu::FilterMode::Linear,
@@ -500,12 +591,113 @@ fn load_scene(
     render_world.insert_resource(RenderPipelineSkybox(render_pipeline_skybox));
     render_world.insert_resource(BindGroupSkybox(bind_group_skybox));
 
-    let msaa_buffer_view = graphics::gpu::create_msaa_buffer(
+    let msaa_buffer_view = (msaa_sample_count > 1).then(|| {
+        graphics::gpu::create_msaa_buffer(
+            &gpu.device,
+            gpu.config.width,
+            gpu.config.height,
+            msaa_sample_count,
+        )
+    });
+    render_world.insert_resource(MsaaBuffer(msaa_buffer_view));
+    render_world.insert_resource(MsaaSampleCount(msaa_sample_count));
+
+    let color_grading_lut_view =
+        graphics::pipeline::render::tonemapping::create_color_grading_lut_texture(
+            &gpu.device,
+            &gpu.queue,
+            &color_grading_lut,
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+    let render_pipeline_bloom = graphics::pipeline::render::Bloom::new(&gpu.device);
+    let render_pipeline_tonemapping =
+        graphics::pipeline::render::Tonemapping::new(&gpu.device, gpu.config.format);
+    let render_pipeline_fxaa =
+        graphics::pipeline::render::Fxaa::new(&gpu.device, gpu.config.format);
+    let tonemapping_buffer = graphics::pipeline::render::tonemapping::create_tonemapping_buffer(
         &gpu.device,
-        gpu.config.width,
-        gpu.config.height,
-        gpu.config.format,
-        MSAA_SAMPLE_COUNT,
+        &graphics::pipeline::render::tonemapping::TonemappingSettings::new(
+            main_world.resource::<ecs::resource::Camera>(),
+            main_world.resource::<ecs::resource::PostProcessing>(),
+        ),
     );
-    render_world.insert_resource(MsaaBuffer(msaa_buffer_view));
+    let post_processing_targets = PostProcessingTargets::new(
+        gpu,
+        &render_pipeline_bloom,
+        &render_pipeline_tonemapping,
+        &render_pipeline_fxaa,
+        &tonemapping_buffer,
+        &color_grading_lut_view,
+    );
+    render_world.insert_resource(ColorGradingLut(color_grading_lut_view));
+    render_world.insert_resource(RenderPipelineBloom(render_pipeline_bloom));
+    render_world.insert_resource(RenderPipelineTonemapping(render_pipeline_tonemapping));
+    render_world.insert_resource(RenderPipelineFxaa(render_pipeline_fxaa));
+    render_world.insert_resource(TonemappingBuffer(tonemapping_buffer));
+    render_world.insert_resource(post_processing_targets);
+}
+
+/// Spawns the entity hierarchy of a loaded model and returns its root entity.
+pub fn spawn_model(
+    commands: &mut bevy_ecs::system::Commands,
+    model: &asset::Model,
+    root_transform: ecs::component::Transform,
+) -> bevy_ecs::entity::Entity {
+    let root = commands
+        .spawn((root_transform, ecs::component::GlobalTransform::default()))
+        .id();
+    let mut stack: Vec<(usize, bevy_ecs::entity::Entity)> = model
+        .root_nodes
+        .iter()
+        .map(|&node_index| (node_index, root))
+        .collect();
+
+    while let Some((node_index, parent_entity)) = stack.pop() {
+        let node = model.nodes.get(node_index).unwrap();
+
+        let transform = ecs::component::Transform {
+            translation: node.translation,
+            rotation: node.rotation,
+            scale: node.scale,
+        };
+
+        let objects = node
+            .object_group
+            .as_ref()
+            .map(|object_group| {
+                object_group
+                    .objects
+                    .iter()
+                    .map(
+                        |&asset::Object {
+                             mesh_id,
+                             material_id,
+                         }| {
+                            commands
+                                .spawn((
+                                    ecs::component::Mesh { mesh_id },
+                                    ecs::component::Material { material_id },
+                                    ecs::component::Transform::default(),
+                                    ecs::component::GlobalTransform::default(),
+                                ))
+                                .id()
+                        },
+                    )
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let mut entity_commands =
+            commands.spawn((transform, ecs::component::GlobalTransform::default()));
+        entity_commands.add_children(&objects);
+        let entity = entity_commands.id();
+
+        commands.entity(parent_entity).add_child(entity);
+
+        for &child_index in &node.children {
+            stack.push((child_index, entity));
+        }
+    }
+
+    root
 }
@@ -0,0 +1,448 @@
+use super::instance::SHADOW_DRAW_LIST_COUNT;
+use super::light::ExtractedLights;
+use crate::ecs;
+use crate::graphics;
+
+use graphics::pipeline::render::shadow::{
+    self, CASCADE_COUNT, MAX_POINT_LIGHT_SHADOWS, SHADOW_TILE_COUNT, SHADOW_TILE_SIZE,
+};
+
+// Directional light cascades only cover the camera frustum up to this distance.
+const MAX_SHADOW_DISTANCE: f32 = 40.0;
+// Blend between uniform (0) and logarithmic (1) cascade splits.
+const CASCADE_SPLIT_LAMBDA: f32 = 0.75;
+// Distance behind a cascade, towards the light, in which occluders still cast shadows.
+const SHADOW_CASTER_DISTANCE: f32 = 50.0;
+const SPOT_LIGHT_SHADOW_NEAR: f32 = 0.05;
+
+// Shadow views are laid out in the atlas one per tile, in view order, so that the tile of a view
+// is derived from its index. Point light shadows are rendered into six consecutive layers of the
+// point shadow maps each.
+#[derive(bevy_ecs::system::Resource)]
+pub struct ShadowMaps {
+    view_count: u32,
+    point_shadow_count: u32,
+    pub shadow_atlas_view: wgpu::TextureView,
+    pub point_shadow_maps_view: wgpu::TextureView,
+    pub point_shadow_face_views: Vec<wgpu::TextureView>,
+    // One frustum per shadow draw list, the first one being unbounded.
+    pub shadow_frustum_buffers: Vec<wgpu::Buffer>,
+    pub shadow_sampler: wgpu::Sampler,
+    pub shadow_pass_views_buffer: wgpu::Buffer,
+    pub shadow_views_buffer: wgpu::Buffer,
+    pub shadow_cascades_buffer: wgpu::Buffer,
+}
+
+impl ShadowMaps {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let point_shadow_maps = shadow::create_point_shadow_maps(device);
+
+        Self {
+            view_count: 0,
+            point_shadow_count: 0,
+            shadow_atlas_view: shadow::create_shadow_atlas(device)
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            point_shadow_maps_view: point_shadow_maps.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("point_shadow_maps"),
+                dimension: Some(wgpu::TextureViewDimension::CubeArray),
+                ..Default::default()
+            }),
+            point_shadow_face_views: (0..shadow::POINT_SHADOW_FACE_COUNT as u32)
+                .map(|face| {
+                    point_shadow_maps.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("point_shadow_face"),
+                        dimension: Some(wgpu::TextureViewDimension::D2),
+                        base_array_layer: face,
+                        array_layer_count: Some(1),
+                        ..Default::default()
+                    })
+                })
+                .collect(),
+            shadow_frustum_buffers: (0..SHADOW_DRAW_LIST_COUNT)
+                .map(|_| {
+                    graphics::pipeline::compute::frustum_culling::create_frustum_buffer(
+                        device,
+                        ecs::resource::Frustum::unbounded(),
+                    )
+                })
+                .collect(),
+            shadow_sampler: shadow::create_shadow_sampler(device),
+            shadow_pass_views_buffer: shadow::create_shadow_pass_views_buffer(device),
+            shadow_views_buffer: shadow::create_shadow_views_buffer(device),
+            shadow_cascades_buffer: shadow::create_shadow_cascades_buffer(device),
+        }
+    }
+
+    pub fn view_count(&self) -> u32 {
+        self.view_count
+    }
+
+    pub fn point_shadow_face_count(&self) -> u32 {
+        self.point_shadow_count * 6
+    }
+
+    /// Returns the index of a point shadow face in the shadow pass views buffer.
+    pub fn point_shadow_pass_view_index(face: u32) -> u32 {
+        SHADOW_TILE_COUNT as u32 + face
+    }
+
+    /// Returns the pixel offset of the atlas tile of a view.
+    pub fn tile_offset(view_index: u32) -> (u32, u32) {
+        let tiles_per_row = shadow::SHADOW_ATLAS_SIZE / SHADOW_TILE_SIZE;
+
+        (
+            (view_index % tiles_per_row) * SHADOW_TILE_SIZE,
+            (view_index / tiles_per_row) * SHADOW_TILE_SIZE,
+        )
+    }
+
+    /// Assigns atlas tiles to the directional and spot lights, in order, until the atlas is full,
+    /// and point shadow cubes to the point lights casting shadows, and uploads their shadow views.
+    /// Lights left without a tile or cube do not cast shadows.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        camera: &ecs::resource::Camera,
+        extracted_lights: &mut ExtractedLights,
+    ) {
+        let split_depths = cascade_split_depths(camera);
+
+        let mut views = Vec::new();
+
+        for directional_light in &mut extracted_lights.directional_lights {
+            if views.len() + CASCADE_COUNT > SHADOW_TILE_COUNT {
+                directional_light.shadow_index = shadow::NO_SHADOW;
+                continue;
+            }
+
+            directional_light.shadow_index = views.len() as u32;
+
+            let mut near = camera.near;
+            for far in split_depths {
+                views.push(directional_light_cascade_view(
+                    camera,
+                    directional_light.direction.into(),
+                    near,
+                    far,
+                ));
+                near = far;
+            }
+        }
+
+        for spot_light in &mut extracted_lights.spot_lights {
+            if views.len() == SHADOW_TILE_COUNT {
+                spot_light.shadow_index = shadow::NO_SHADOW;
+                continue;
+            }
+
+            spot_light.shadow_index = views.len() as u32;
+            views.push(spot_light_view(spot_light));
+        }
+
+        self.view_count = views.len() as u32;
+
+        let mut point_shadow_views = Vec::new();
+
+        for (point_light, &cast_shadows) in extracted_lights
+            .point_lights
+            .iter_mut()
+            .zip(&extracted_lights.point_light_shadows)
+        {
+            if !cast_shadows || point_shadow_views.len() == MAX_POINT_LIGHT_SHADOWS * 6 {
+                point_light.shadow_index = shadow::NO_SHADOW;
+                continue;
+            }
+
+            point_light.shadow_index = (point_shadow_views.len() / 6) as u32;
+            point_shadow_views.extend(point_light_face_views(point_light));
+        }
+
+        self.point_shadow_count = (point_shadow_views.len() / 6) as u32;
+
+        queue.write_buffer(
+            &self.shadow_pass_views_buffer,
+            Self::point_shadow_pass_view_index(0) as wgpu::BufferAddress
+                * std::mem::size_of::<shadow::ShadowPassView>() as wgpu::BufferAddress,
+            bytemuck::cast_slice(
+                &point_shadow_views
+                    .iter()
+                    .map(|view_projection| shadow::ShadowPassView {
+                        view_projection: view_projection.to_cols_array(),
+                        _padding: [0.0; 48],
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        );
+
+        // The first frustum stays unbounded, as the atlas views draw every instance.
+        for (shadow_frustum_buffer, view_projection) in self.shadow_frustum_buffers[1..]
+            .iter()
+            .zip(&point_shadow_views)
+        {
+            queue.write_buffer(
+                shadow_frustum_buffer,
+                0,
+                bytemuck::cast_slice(&[ecs::resource::Frustum::from_view_projection_matrix(
+                    view_projection,
+                )]),
+            );
+        }
+
+        let tile_size = SHADOW_TILE_SIZE as f32 / shadow::SHADOW_ATLAS_SIZE as f32;
+
+        queue.write_buffer(
+            &self.shadow_pass_views_buffer,
+            0,
+            bytemuck::cast_slice(
+                &views
+                    .iter()
+                    .map(|(view_projection, _)| shadow::ShadowPassView {
+                        view_projection: view_projection.to_cols_array(),
+                        _padding: [0.0; 48],
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        );
+        queue.write_buffer(
+            &self.shadow_views_buffer,
+            0,
+            bytemuck::cast_slice(
+                &views
+                    .iter()
+                    .enumerate()
+                    .map(|(view_index, (view_projection, texel_size))| {
+                        let (x, y) = Self::tile_offset(view_index as u32);
+
+                        shadow::ShadowView {
+                            view_projection: view_projection.to_cols_array(),
+                            atlas_rect: [
+                                x as f32 / shadow::SHADOW_ATLAS_SIZE as f32,
+                                y as f32 / shadow::SHADOW_ATLAS_SIZE as f32,
+                                tile_size,
+                                tile_size,
+                            ],
+                            texel_size: *texel_size,
+                            _padding: [0.0; 3],
+                        }
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        );
+        queue.write_buffer(
+            &self.shadow_cascades_buffer,
+            0,
+            bytemuck::cast_slice(&[shadow::ShadowCascades {
+                camera_forward: (camera.target - camera.position).normalize().into(),
+                _padding: 0.0,
+                split_depths,
+            }]),
+        );
+    }
+}
+
+/// Splits the camera view depth up to the shadow distance into cascades, returning the far depth
+/// of each cascade.
+fn cascade_split_depths(camera: &ecs::resource::Camera) -> [f32; CASCADE_COUNT] {
+    let near = camera.near;
+    let far = camera.far.min(MAX_SHADOW_DISTANCE);
+
+    std::array::from_fn(|cascade| {
+        let fraction = (cascade + 1) as f32 / CASCADE_COUNT as f32;
+        let uniform_split = near + (far - near) * fraction;
+        let logarithmic_split = near * (far / near).powf(fraction);
+
+        uniform_split + (logarithmic_split - uniform_split) * CASCADE_SPLIT_LAMBDA
+    })
+}
+
+/// Fits an orthographic shadow view around the slice of the camera frustum between `near` and
+/// `far`. Returns the view-projection matrix and the world space size of a shadow texel.
+fn directional_light_cascade_view(
+    camera: &ecs::resource::Camera,
+    direction: glam::Vec3,
+    near: f32,
+    far: f32,
+) -> (glam::Mat4, f32) {
+    let corners = ecs::resource::Frustum::from_view_projection_matrix(
+        &(glam::Mat4::perspective_rh(camera.fov, camera.aspect_ratio, near, far)
+            * camera.view_matrix()),
+    )
+    .corners
+    .map(|corner| glam::Vec3::new(corner[0], corner[1], corner[2]));
+
+    // Fitting a sphere rather than a box keeps the cascade size constant as the camera rotates.
+    let center = corners.iter().sum::<glam::Vec3>() / corners.len() as f32;
+    let radius = corners
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0.0, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let view = glam::Mat4::look_at_rh(center, center + direction, up_vector(direction));
+    let mut projection = glam::Mat4::orthographic_rh(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        -radius - SHADOW_CASTER_DISTANCE,
+        radius,
+    );
+
+    // Snaps the projection to whole texels, so that shadow edges do not shimmer as the camera
+    // moves.
+    let texels_per_clip_unit = SHADOW_TILE_SIZE as f32 / 2.0;
+    let origin = (projection * view * glam::Vec4::W).truncate().truncate() * texels_per_clip_unit;
+    let offset = (origin.round() - origin) / texels_per_clip_unit;
+    projection.w_axis.x += offset.x;
+    projection.w_axis.y += offset.y;
+
+    (projection * view, 2.0 * radius / SHADOW_TILE_SIZE as f32)
+}
+
+/// Returns the perspective view-projection matrix enclosing the outer cone of a spot light, and
+/// the world space size of a shadow texel at unit distance.
+fn spot_light_view(spot_light: &graphics::pipeline::render::pbr::SpotLight) -> (glam::Mat4, f32) {
+    let position = glam::Vec3::from(spot_light.position);
+    let direction = glam::Vec3::from(spot_light.direction);
+    let fov =
+        (2.0 * spot_light.outer_cone_cos.clamp(-1.0, 1.0).acos()).min(std::f32::consts::PI - 0.01);
+
+    let view = glam::Mat4::look_at_rh(position, position + direction, up_vector(direction));
+    let projection = glam::Mat4::perspective_rh(
+        fov,
+        1.0,
+        SPOT_LIGHT_SHADOW_NEAR,
+        spot_light.range.max(SPOT_LIGHT_SHADOW_NEAR * 2.0),
+    );
+
+    (
+        projection * view,
+        2.0 * (fov / 2.0).tan() / SHADOW_TILE_SIZE as f32,
+    )
+}
+
+/// Returns the view-projection matrices of the six cube faces around a point light, in cubemap
+/// face order (+X, -X, +Y, -Y, +Z, -Z).
+fn point_light_face_views(
+    point_light: &graphics::pipeline::render::pbr::PointLight,
+) -> [glam::Mat4; 6] {
+    let position = glam::Vec3::from(point_light.position);
+
+    // Cubemap faces are addressed with the texture origin at the top left, so the projection is
+    // flipped vertically compared to a regular view.
+    let projection = glam::Mat4::from_scale(glam::Vec3::new(1.0, -1.0, 1.0))
+        * glam::Mat4::perspective_rh(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            shadow::POINT_SHADOW_NEAR,
+            point_light.range.max(shadow::POINT_SHADOW_NEAR * 2.0),
+        );
+
+    [
+        (glam::Vec3::X, glam::Vec3::NEG_Y),
+        (glam::Vec3::NEG_X, glam::Vec3::NEG_Y),
+        (glam::Vec3::Y, glam::Vec3::Z),
+        (glam::Vec3::NEG_Y, glam::Vec3::NEG_Z),
+        (glam::Vec3::Z, glam::Vec3::NEG_Y),
+        (glam::Vec3::NEG_Z, glam::Vec3::NEG_Y),
+    ]
+    .map(|(direction, up)| projection * glam::Mat4::look_at_rh(position, position + direction, up))
+}
+
+fn up_vector(direction: glam::Vec3) -> glam::Vec3 {
+    if direction.y.abs() > 0.99 {
+        glam::Vec3::Z
+    } else {
+        glam::Vec3::Y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cascade_splits_increase_up_to_the_shadow_distance() {
+        let camera = ecs::resource::Camera::default();
+        let split_depths = cascade_split_depths(&camera);
+
+        assert!(split_depths[0] > camera.near);
+        assert!(split_depths.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((split_depths[CASCADE_COUNT - 1] - MAX_SHADOW_DISTANCE).abs() < 1e-3);
+    }
+
+    #[test]
+    fn cascade_view_contains_its_frustum_slice() {
+        let camera = ecs::resource::Camera::default();
+        let direction = glam::Vec3::new(-1.0, -2.0, -0.5).normalize();
+        let (near, far) = (2.0, 8.0);
+
+        let (view_projection, _) = directional_light_cascade_view(&camera, direction, near, far);
+
+        let corners = ecs::resource::Frustum::from_view_projection_matrix(
+            &(glam::Mat4::perspective_rh(camera.fov, camera.aspect_ratio, near, far)
+                * camera.view_matrix()),
+        )
+        .corners;
+
+        for corner in corners {
+            let clip =
+                view_projection.project_point3(glam::Vec3::new(corner[0], corner[1], corner[2]));
+            // Snapping to texels may shift the cascade by up to one texel.
+            let extent = 1.0 + 2.0 / SHADOW_TILE_SIZE as f32;
+            assert!(
+                clip.x.abs() <= extent && clip.y.abs() <= extent,
+                "{clip} is outside the cascade"
+            );
+            assert!(
+                (0.0..=1.0).contains(&clip.z),
+                "{clip} is outside the cascade depth range"
+            );
+        }
+    }
+
+    // Mirrors `point_light_shadow` in the PBR shader.
+    #[test]
+    fn point_light_faces_store_depth_along_the_major_axis() {
+        let point_light = graphics::pipeline::render::pbr::PointLight {
+            color: [1.0; 3],
+            strength: 1.0,
+            position: [1.0, 2.0, 3.0],
+            range: 10.0,
+            shadow_index: 0,
+            _padding: [0; 3],
+        };
+        let face_views = point_light_face_views(&point_light);
+
+        let far = point_light.range;
+        let near = shadow::POINT_SHADOW_NEAR;
+
+        for (face, major_axis) in [
+            glam::Vec3::X,
+            glam::Vec3::NEG_X,
+            glam::Vec3::Y,
+            glam::Vec3::NEG_Y,
+            glam::Vec3::Z,
+            glam::Vec3::NEG_Z,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let view_depth = 4.0;
+            let light_to_point = major_axis * view_depth + major_axis.any_orthonormal_vector();
+            let clip = face_views[face]
+                .project_point3(glam::Vec3::from(point_light.position) + light_to_point);
+
+            assert!(
+                clip.x.abs() < 1.0 && clip.y.abs() < 1.0,
+                "face {face} misses {clip}"
+            );
+            let depth = far * (view_depth - near) / (view_depth * (far - near));
+            assert!(
+                (clip.z - depth).abs() < 1e-5,
+                "face {face} stores {} instead of {depth}",
+                clip.z
+            );
+        }
+    }
+}
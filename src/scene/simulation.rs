@@ -1,10 +1,17 @@
-use super::{resource, scene};
+use super::{
+    ambient_occlusion::AmbientOcclusionTargets, instance, light,
+    post_processing::PostProcessingTargets, resource,
+};
 use crate::ecs;
 use crate::graphics;
 
 pub struct Simulator {
     condvar_pair: std::sync::Arc<(std::sync::Mutex<bool>, std::sync::Condvar)>,
     resize_event: std::sync::Arc<crossbeam::atomic::AtomicCell<Option<ResizeEvent>>>,
+    capture_request: std::sync::Arc<
+        crossbeam::atomic::AtomicCell<Option<crossbeam::channel::Sender<graphics::Capture>>>,
+    >,
+    tonemapper_cycle_request: std::sync::Arc<crossbeam::atomic::AtomicCell<bool>>,
 }
 
 impl Simulator {
@@ -15,6 +22,10 @@ impl Simulator {
                 std::sync::Condvar::new(),
             )),
             resize_event: std::sync::Arc::new(crossbeam::atomic::AtomicCell::new(None)),
+            capture_request: std::sync::Arc::new(crossbeam::atomic::AtomicCell::new(None)),
+            tonemapper_cycle_request: std::sync::Arc::new(crossbeam::atomic::AtomicCell::new(
+                false,
+            )),
         }
     }
 
@@ -25,8 +36,12 @@ impl Simulator {
         renderer_to_scene_receiver: crossbeam::channel::Receiver<bevy_ecs::world::World>,
     ) -> std::thread::JoinHandle<()> {
         let mut update_schedule = schedule::update();
+        let mut extract_instances_state =
+            bevy_ecs::system::SystemState::<ExtractInstancesParam>::new(&mut world);
         let condvar_pair = self.condvar_pair.clone();
         let resize_event = self.resize_event.clone();
+        let capture_request = self.capture_request.clone();
+        let tonemapper_cycle_request = self.tonemapper_cycle_request.clone();
 
         std::thread::spawn(move || loop {
             let (lock, cvar) = &*condvar_pair;
@@ -48,29 +63,61 @@ impl Simulator {
                     camera.aspect_ratio = width as f32 / height as f32;
                 }
 
+                let msaa_sample_count = **render_world.resource::<resource::MsaaSampleCount>();
                 let mut gpu = render_world.get_resource_mut::<graphics::Gpu>().unwrap();
                 gpu.resize(width, height);
                 let new_depth_buffer = graphics::gpu::create_depth_buffer(
                     &gpu.device,
                     width,
                     height,
-                    scene::MSAA_SAMPLE_COUNT,
+                    msaa_sample_count,
                 );
-                let new_msaa_buffer = graphics::gpu::create_msaa_buffer(
-                    &gpu.device,
-                    width,
-                    height,
-                    gpu.config.format,
-                    scene::MSAA_SAMPLE_COUNT,
+                let new_msaa_buffer = (msaa_sample_count > 1).then(|| {
+                    graphics::gpu::create_msaa_buffer(&gpu.device, width, height, msaa_sample_count)
+                });
+                let new_post_processing_targets = PostProcessingTargets::new(
+                    render_world.resource::<graphics::Gpu>(),
+                    render_world.resource::<resource::RenderPipelineBloom>(),
+                    render_world.resource::<resource::RenderPipelineTonemapping>(),
+                    render_world.resource::<resource::RenderPipelineFxaa>(),
+                    render_world.resource::<resource::TonemappingBuffer>(),
+                    render_world.resource::<resource::ColorGradingLut>(),
+                );
+                let new_ambient_occlusion_targets = AmbientOcclusionTargets::new(
+                    render_world.resource::<graphics::Gpu>(),
+                    render_world.resource::<resource::ComputePipelineAmbientOcclusion>(),
+                    render_world.resource::<resource::AmbientOcclusionBuffer>(),
+                    &new_depth_buffer,
                 );
+                let new_bind_group_environment = render_world
+                    .resource::<light::EnvironmentLighting>()
+                    .create_bind_group_environment(
+                        &render_world.resource::<graphics::Gpu>().device,
+                        render_world.resource::<resource::RenderPipelinePbr>(),
+                        &new_ambient_occlusion_targets.ambient_occlusion_view,
+                    );
                 render_world.insert_resource::<resource::DepthBuffer>(resource::DepthBuffer(
                     new_depth_buffer,
                 ));
+                render_world.insert_resource(new_ambient_occlusion_targets);
+                render_world
+                    .insert_resource(resource::BindGroupEnvironment(new_bind_group_environment));
                 render_world
                     .insert_resource::<resource::MsaaBuffer>(resource::MsaaBuffer(new_msaa_buffer));
+                render_world.insert_resource(new_post_processing_targets);
+            }
+
+            if tonemapper_cycle_request.swap(false) {
+                if let Some(mut camera) = world.get_resource_mut::<ecs::resource::Camera>() {
+                    camera.tonemapper = camera.tonemapper.next();
+                }
+            }
+
+            if let Some(frame_capture_sender) = capture_request.take() {
+                render_world.insert_resource(resource::FrameCapture(frame_capture_sender));
             }
 
-            extract_world(&mut world, &mut render_world);
+            extract_world(&mut world, &mut render_world, &mut extract_instances_state);
 
             scene_to_renderer_sender.send(render_world).unwrap();
 
@@ -89,6 +136,17 @@ impl Simulator {
     pub fn request_resize(&mut self, width: u32, height: u32) {
         self.resize_event.store(Some(ResizeEvent { width, height }));
     }
+
+    pub fn request_tonemapper_cycle(&mut self) {
+        self.tonemapper_cycle_request.store(true);
+    }
+
+    pub fn request_capture(
+        &mut self,
+        frame_capture_sender: crossbeam::channel::Sender<graphics::Capture>,
+    ) {
+        self.capture_request.store(Some(frame_capture_sender));
+    }
 }
 
 struct ResizeEvent {
@@ -96,28 +154,149 @@ struct ResizeEvent {
     height: u32,
 }
 
+type ExtractInstancesParam<'w, 's> = (
+    bevy_ecs::system::Query<
+        'w,
+        's,
+        (
+            bevy_ecs::entity::Entity,
+            &'static ecs::component::Mesh,
+            &'static ecs::component::Material,
+            &'static ecs::component::GlobalTransform,
+        ),
+        bevy_ecs::query::Or<(
+            bevy_ecs::query::Changed<ecs::component::Mesh>,
+            bevy_ecs::query::Changed<ecs::component::Material>,
+            bevy_ecs::query::Changed<ecs::component::GlobalTransform>,
+        )>,
+    >,
+    bevy_ecs::removal_detection::RemovedComponents<'w, 's, ecs::component::Mesh>,
+    bevy_ecs::removal_detection::RemovedComponents<'w, 's, ecs::component::Material>,
+    bevy_ecs::removal_detection::RemovedComponents<'w, 's, ecs::component::GlobalTransform>,
+);
+
 fn extract_world(
     main_world: &mut bevy_ecs::world::World,
     render_world: &mut bevy_ecs::world::World,
+    extract_instances_state: &mut bevy_ecs::system::SystemState<ExtractInstancesParam>,
 ) {
     let camera = main_world.get_resource::<ecs::resource::Camera>().unwrap();
     render_world.insert_resource::<ecs::resource::Camera>(camera.clone());
 
-    // TODO: extract only visible entities.
-
-    let mut query = main_world.query::<(
-        bevy_ecs::entity::Entity,
-        &ecs::component::Mesh,
-        &ecs::component::Material,
-        &ecs::component::GlobalTransform,
-    )>();
-    render_world
-        .insert_or_spawn_batch(query.iter(main_world).map(
-            |(entity, mesh, material, global_transform)| {
-                (entity, (mesh.clone(), material.clone(), *global_transform))
-            },
-        ))
+    let post_processing = main_world
+        .get_resource::<ecs::resource::PostProcessing>()
         .unwrap();
+    render_world.insert_resource::<ecs::resource::PostProcessing>(post_processing.clone());
+
+    // The system state keeps its own change tick, so only instances that changed since the last
+    // extraction are sent to the renderer.
+    let (query, mut removed_meshes, mut removed_materials, mut removed_global_transforms) =
+        extract_instances_state.get_mut(main_world);
+
+    let changed = query
+        .iter()
+        .map(
+            |(entity, mesh, material, global_transform)| instance::ExtractedInstance {
+                entity,
+                mesh_id: mesh.mesh_id,
+                material_id: material.material_id,
+                transform: glam::Mat4::from(**global_transform).to_cols_array(),
+            },
+        )
+        .collect();
+
+    let removed = removed_meshes
+        .read()
+        .chain(removed_materials.read())
+        .chain(removed_global_transforms.read())
+        .collect();
+
+    render_world.insert_resource(instance::ExtractedInstances { changed, removed });
+
+    extract_lights(main_world, render_world);
+}
+
+fn extract_lights(
+    main_world: &mut bevy_ecs::world::World,
+    render_world: &mut bevy_ecs::world::World,
+) {
+    let ambient_light = main_world
+        .get_resource::<ecs::resource::AmbientLight>()
+        .map_or(bytemuck::Zeroable::zeroed(), |ambient_light| {
+            graphics::pipeline::render::pbr::AmbientLight {
+                color: ambient_light.color.into(),
+                strength: ambient_light.strength,
+            }
+        });
+
+    let (point_lights, point_light_shadows) = main_world
+        .query::<(
+            &ecs::component::PointLight,
+            &ecs::component::GlobalTransform,
+        )>()
+        .iter(main_world)
+        .map(|(point_light, global_transform)| {
+            (
+                graphics::pipeline::render::pbr::PointLight {
+                    color: point_light.color.into(),
+                    strength: point_light.strength,
+                    position: global_transform.translation.into(),
+                    range: point_light.range,
+                    shadow_index: graphics::pipeline::render::shadow::NO_SHADOW,
+                    _padding: [0; 3],
+                },
+                point_light.cast_shadows,
+            )
+        })
+        .unzip();
+
+    let directional_lights = main_world
+        .query::<(
+            &ecs::component::DirectionalLight,
+            &ecs::component::GlobalTransform,
+        )>()
+        .iter(main_world)
+        .map(|(directional_light, global_transform)| {
+            graphics::pipeline::render::pbr::DirectionalLight {
+                color: directional_light.color.into(),
+                strength: directional_light.strength,
+                direction: global_transform
+                    .transform_vector3(glam::Vec3::NEG_Z)
+                    .normalize()
+                    .into(),
+                shadow_index: graphics::pipeline::render::shadow::NO_SHADOW,
+            }
+        })
+        .collect();
+
+    let spot_lights = main_world
+        .query::<(&ecs::component::SpotLight, &ecs::component::GlobalTransform)>()
+        .iter(main_world)
+        .map(
+            |(spot_light, global_transform)| graphics::pipeline::render::pbr::SpotLight {
+                color: spot_light.color.into(),
+                strength: spot_light.strength,
+                position: global_transform.translation.into(),
+                range: spot_light.range,
+                direction: global_transform
+                    .transform_vector3(glam::Vec3::NEG_Z)
+                    .normalize()
+                    .into(),
+                inner_cone_cos: spot_light.inner_angle.cos(),
+                outer_cone_cos: spot_light.outer_angle.cos(),
+                shadow_index: graphics::pipeline::render::shadow::NO_SHADOW,
+                _padding: [0.0; 2],
+            },
+        )
+        .collect();
+
+    render_world.insert_resource(light::ExtractedLights {
+        ambient_light,
+        point_lights,
+        point_light_shadows,
+        directional_lights,
+        spot_lights,
+    });
 }
 
 mod schedule {
@@ -135,6 +314,7 @@ mod schedule {
                         .run_if(run_condition::should_step_physics),
                     system::move_camera,
                 ),
+                system::propagate_transforms,
             )
                 .chain(),
         );
@@ -147,11 +327,22 @@ mod system {
     use super::super::resource::*;
     use crate::ecs;
     use crate::physics;
-    use bevy_ecs::change_detection::{Res, ResMut};
+    use bevy_ecs::change_detection::{DetectChanges, Ref, Res, ResMut};
+    use bevy_ecs::entity::Entity;
+    use bevy_ecs::query::{Changed, Or, With};
+    use bevy_ecs::removal_detection::RemovedComponents;
     use bevy_ecs::system::Query;
+    use bevy_hierarchy::{Children, Parent};
 
-    pub fn update_time(mut timestamp: ResMut<Timestamp>, mut delta_time: ResMut<DeltaTime>) {
-        let now = std::time::Instant::now();
+    pub fn update_time(
+        mut timestamp: ResMut<Timestamp>,
+        mut delta_time: ResMut<DeltaTime>,
+        fixed_delta_time: Option<Res<FixedDeltaTime>>,
+    ) {
+        let now = match fixed_delta_time {
+            Some(fixed_delta_time) => **timestamp + **fixed_delta_time,
+            None => std::time::Instant::now(),
+        };
         **delta_time = now - **timestamp;
         **timestamp = now;
     }
@@ -178,19 +369,102 @@ mod system {
         last_physics_step_timestamp.0 = last_step;
     }
 
+    type ChangedTransformFilter = (
+        With<ecs::component::Transform>,
+        Or<(Changed<ecs::component::Transform>, Changed<Parent>)>,
+    );
+
+    type TransformQueryData<'a> = (
+        &'a ecs::component::Transform,
+        &'a mut ecs::component::GlobalTransform,
+        Option<&'a Children>,
+    );
+
+    /// Recomputes the global transforms below every entity whose transform or parent changed,
+    /// leaving static subtrees untouched.
+    pub fn propagate_transforms(
+        changed_query: Query<(Entity, Option<&Parent>), ChangedTransformFilter>,
+        mut removed_parents: RemovedComponents<Parent>,
+        hierarchy_query: Query<(Ref<ecs::component::Transform>, Option<Ref<Parent>>)>,
+        mut transform_query: Query<TransformQueryData>,
+    ) {
+        let orphans = removed_parents
+            .read()
+            .filter_map(|entity| changed_query.get(entity).is_err().then_some((entity, None)));
+        let dirty_roots = changed_query
+            .iter()
+            .map(|(entity, parent)| (entity, parent.map(Parent::get)))
+            .chain(orphans)
+            .collect::<Vec<_>>();
+
+        let mut stack = Vec::new();
+        for (entity, parent) in dirty_roots {
+            // The subtree is recomputed from its topmost changed ancestor instead.
+            if has_changed_ancestor(parent, &hierarchy_query) {
+                continue;
+            }
+
+            let parent_global_transform = parent
+                .and_then(|parent| transform_query.get(parent).ok())
+                .map_or(glam::Affine3A::IDENTITY, |(_, global_transform, _)| {
+                    **global_transform
+                });
+            stack.push((entity, parent_global_transform));
+
+            while let Some((entity, parent_global_transform)) = stack.pop() {
+                let Ok((transform, mut global_transform, children)) =
+                    transform_query.get_mut(entity)
+                else {
+                    // Children without a transform do not take part in the hierarchy.
+                    continue;
+                };
+
+                **global_transform = parent_global_transform * transform.compute_affine();
+
+                if let Some(children) = children {
+                    stack.extend(children.iter().map(|&child| (child, **global_transform)));
+                }
+            }
+        }
+    }
+
+    fn has_changed_ancestor(
+        mut parent: Option<Entity>,
+        hierarchy_query: &Query<(Ref<ecs::component::Transform>, Option<Ref<Parent>>)>,
+    ) -> bool {
+        while let Some(entity) = parent {
+            let Ok((transform, grandparent)) = hierarchy_query.get(entity) else {
+                return false;
+            };
+            if transform.is_changed() || grandparent.as_ref().is_some_and(DetectChanges::is_changed)
+            {
+                return true;
+            }
+            parent = grandparent.map(|grandparent| grandparent.get());
+        }
+
+        false
+    }
+
+    /// Writes the rigid body poses to the transforms, from which `propagate_transforms` moves
+    /// their children. Rigid bodies are simulated in world space, so they are root entities.
     pub fn sync_ecs_to_physics(
         physics_world: Res<physics::Physics>,
-        mut query: Query<(&physics::RigidBody, &mut ecs::component::GlobalTransform)>,
+        mut query: Query<(&physics::RigidBody, &mut ecs::component::Transform)>,
     ) {
-        for (physics::RigidBody(rigid_body_handle), mut global_transform) in query.iter_mut() {
+        for (physics::RigidBody(rigid_body_handle), mut transform) in query.iter_mut() {
             if let Some(rigid_body) = physics_world.rigid_body_set.get(*rigid_body_handle) {
-                let (scale, _, _) = global_transform.to_scale_rotation_translation();
                 let rigid_body_position = rigid_body.position();
-                **global_transform = glam::Affine3A::from_scale_rotation_translation(
-                    scale,
-                    glam::Quat::from_slice(rigid_body_position.rotation.coords.data.as_slice()),
-                    glam::f32::Vec3::from_slice(rigid_body_position.translation.vector.as_slice()),
-                );
+                let rotation =
+                    glam::Quat::from_slice(rigid_body_position.rotation.coords.data.as_slice());
+                let translation =
+                    glam::f32::Vec3::from_slice(rigid_body_position.translation.vector.as_slice());
+
+                // Resting bodies keep their transform unchanged, so their subtree is skipped.
+                if transform.rotation != rotation || transform.translation != translation {
+                    transform.rotation = rotation;
+                    transform.translation = translation;
+                }
             }
         }
     }
@@ -209,3 +483,102 @@ mod run_condition {
             >= std::time::Duration::from_secs_f32(physics::TIMESTEP)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::change_detection::DetectChanges;
+    use bevy_ecs::schedule::IntoSystemConfigs;
+    use bevy_hierarchy::BuildChildren;
+
+    fn spawn_with_child(
+        world: &mut bevy_ecs::world::World,
+        translation: glam::Vec3,
+    ) -> (bevy_ecs::entity::Entity, bevy_ecs::entity::Entity) {
+        let child = world
+            .spawn((
+                ecs::component::Transform::from_translation(glam::Vec3::X),
+                ecs::component::GlobalTransform::default(),
+            ))
+            .id();
+        let parent = world
+            .spawn((
+                ecs::component::Transform::from_translation(translation),
+                ecs::component::GlobalTransform::default(),
+            ))
+            .add_child(child)
+            .id();
+
+        (parent, child)
+    }
+
+    fn global_translation(
+        world: &bevy_ecs::world::World,
+        entity: bevy_ecs::entity::Entity,
+    ) -> glam::Vec3 {
+        world
+            .get::<ecs::component::GlobalTransform>(entity)
+            .unwrap()
+            .translation
+            .into()
+    }
+
+    #[test]
+    fn children_follow_moved_parents_and_static_subtrees_are_skipped() {
+        let mut world = bevy_ecs::world::World::new();
+        let mut schedule = bevy_ecs::schedule::Schedule::default();
+        schedule.add_systems((system::sync_ecs_to_physics, system::propagate_transforms).chain());
+
+        let mut physics_world = crate::physics::Physics::default();
+        let rigid_body_handle = physics_world
+            .rigid_body_set
+            .insert(rapier3d::dynamics::RigidBodyBuilder::dynamic().build());
+        world.insert_resource(physics_world);
+
+        let (moving_parent, moving_child) = spawn_with_child(&mut world, glam::Vec3::ZERO);
+        let (_, static_child) = spawn_with_child(&mut world, glam::Vec3::Y);
+        let (rigid_body, rigid_body_child) = spawn_with_child(&mut world, glam::Vec3::ZERO);
+        world
+            .entity_mut(rigid_body)
+            .insert(crate::physics::RigidBody(rigid_body_handle));
+        schedule.run(&mut world);
+        world.clear_trackers();
+
+        assert_eq!(global_translation(&world, moving_child), glam::Vec3::X);
+        assert_eq!(
+            global_translation(&world, static_child),
+            glam::Vec3::new(1.0, 1.0, 0.0)
+        );
+
+        world
+            .get_mut::<ecs::component::Transform>(moving_parent)
+            .unwrap()
+            .translation = glam::Vec3::Z;
+        world
+            .resource_mut::<crate::physics::Physics>()
+            .rigid_body_set
+            .get_mut(rigid_body_handle)
+            .unwrap()
+            .set_translation([0.0, 2.0, 0.0].into(), true);
+        schedule.run(&mut world);
+
+        assert_eq!(
+            global_translation(&world, moving_child),
+            glam::Vec3::new(1.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            global_translation(&world, rigid_body_child),
+            glam::Vec3::new(1.0, 2.0, 0.0)
+        );
+        assert!(world
+            .entity(moving_child)
+            .get_ref::<ecs::component::GlobalTransform>()
+            .unwrap()
+            .is_changed());
+        assert!(!world
+            .entity(static_child)
+            .get_ref::<ecs::component::GlobalTransform>()
+            .unwrap()
+            .is_changed());
+    }
+}